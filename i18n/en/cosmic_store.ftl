@@ -2,6 +2,7 @@ cosmic-app-store = COSMIC App Store
 back = Back
 cancel = Cancel
 check-for-updates = Check for updates
+close = Close
 checking-for-updates = Checking for updates...
 install = Install
 no-installed-applications = No installed applications.
@@ -9,14 +10,39 @@ no-updates = All installed applications are up to date.
 no-results = No results for "{$search}".
 notification-progress = Installations and updates are {$progress}% complete.
 open = Open
+retry = Retry
+rollback = Roll back
 see-all = See all
 uninstall = Uninstall
 update = Update
 update-all = Update all
+update-on-restart = Update on restart
+offline-update-preparing = Downloading updates...
+offline-update-ready = Updates will be installed when you restart.
+offline-update-failed = Failed to prepare updates: {$error}
+
+# Operation Failed Dialog
+error-not-found = The app or one of its dependencies could not be found.
+error-not-found-remedy = Check for updates to refresh the list of available apps, then try again.
+error-auth-denied = You do not have permission to make this change.
+error-auth-denied-remedy = Make sure you entered the correct password, or ask an administrator for help.
+error-network = The download could not be completed.
+error-network-remedy = Check your internet connection, then try again.
+error-no-space = There is not enough disk space.
+error-no-space-remedy = Free up some disk space, then try again.
+error-conflict = This change conflicts with something already installed or in progress.
+error-conflict-remedy = Wait for other installations and updates to finish, then try again.
+error-cancelled = The operation was cancelled.
+error-cancelled-remedy = Try again when you are ready.
+error-unavailable = The software source is not available right now.
+error-unavailable-remedy = Restart the app store or your computer, then try again.
+error-other = An unexpected error occurred.
+error-other-remedy = Try again. If the problem continues, report it with the details below.
 
 # Uninstall Dialog
 uninstall-app = Uninstall {$name}
 uninstall-app-warning = Are you sure you want to uninstall {$name}?
+uninstall-also-removes = This will also remove: {$packages}
 
 # Nav Pages
 explore = Explore
@@ -44,12 +70,34 @@ social-networking-apps = Social Networking Apps
 games = Games
 music-and-video-apps = Music & Video Apps
 apps-for-learning = Apps for Learning
+console-apps = Console Apps
+web-apps = Web Apps
+addons = Add-ons
+fonts = Fonts
+codecs = Codecs
+input-methods = Input Methods
+drivers = Drivers
+runtimes = Runtimes
 
 # Details Page
 source-installed = {$source} (installed)
 developer = Developer
 app-developers = {$app} Developers
 monthly-downloads = Flathub Monthly Downloads
+links = Links
+license = License
+license-free = Free and open source
+license-proprietary = Proprietary
+license-unknown = Unknown license
+url-homepage = Website
+url-bug-tracker = Report an issue
+url-help = Help
+url-faq = FAQ
+url-translate = Translate
+url-donation = Donate
+url-contact = Contact
+permissions = Permissions
+no-permissions = This app does not request any permissions.
 
 # Context Pages
 
@@ -62,3 +110,87 @@ theme = Theme
 match-desktop = Match desktop
 dark = Dark
 light = Light
+
+### Package managers
+package-managers = Package managers
+
+### Sources
+sources = Sources
+
+### Licenses
+licenses = Licenses
+hide-proprietary = Only show free and open source software
+hide-proprietary-description = Proprietary apps and apps with an unknown license are hidden unless installed
+
+### Diagnostics
+diagnostics-description = Backend and catalog status
+view = View
+
+## Diagnostics
+diagnostics = Diagnostics
+maintenance = Maintenance
+force-refresh = Refresh package sources
+refresh = Refresh
+clear-cache = Clear catalog cache
+clear = Clear
+status = Status
+backend-disabled = Disabled
+backend-failed = Failed: {$error}
+backend-loaded = Loaded
+backend-loading = Loading...
+rebuilding-catalog = Rebuilding catalog, this may take a moment...
+initialized-in = Initialized in
+caches-loaded-in = Catalogs loaded in
+locale = Locale
+items = Items
+cache = Cache
+cache-uncached = Not cached
+cache-hit = Loaded from cache
+cache-miss = Rebuilt: {$reason}
+path-tag = {$size} bytes, modified {$modified}
+
+# Parental controls
+parental-controls = Parental controls
+parental-max-age = Maximum age rating
+parental-max-age-off = No restriction
+parental-hide = Hide apps above the age rating
+parental-allow-unrated = Allow apps without an age rating
+parental-locked = Set by your administrator
+parental-blocked = Blocked by parental controls
+yes = Yes
+no = No
+age-rating = {$age}+
+content-rating-title = Age rating {$age}+
+content-rating-all-ages = Suitable for all ages
+content-intensity-none = None
+content-intensity-mild = Mild
+content-intensity-moderate = Moderate
+content-intensity-intense = Intense
+oars-violence-cartoon = Cartoon violence
+oars-violence-fantasy = Fantasy violence
+oars-violence-realistic = Realistic violence
+oars-violence-bloodshed = Bloodshed
+oars-violence-sexual = Sexual violence
+oars-violence-desecration = Desecration of human remains
+oars-violence-slavery = Depictions of slavery
+oars-violence-worship = Destruction of places of worship
+oars-drugs-alcohol = Alcohol
+oars-drugs-narcotics = Narcotics
+oars-drugs-tobacco = Tobacco
+oars-sex-nudity = Nudity
+oars-sex-themes = Sexual themes
+oars-sex-homosexuality = Homosexuality
+oars-sex-prostitution = Prostitution
+oars-sex-adultery = Adultery
+oars-sex-appearance = Sexualized characters
+oars-language-profanity = Profanity
+oars-language-humor = Inappropriate humor
+oars-language-discrimination = Discrimination
+oars-money-advertising = Advertising
+oars-money-gambling = Gambling
+oars-money-purchasing = In-app purchases
+oars-social-chat = Chat with other users
+oars-social-audio = Audio and video chat
+oars-social-contacts = Shares social network usernames
+oars-social-info = Shares personal information
+oars-social-location = Shares location
@@ -1,97 +1,220 @@
 use appstream::{
-    enums::{Bundle, Icon, ImageKind, Launchable},
+    enums::{Bundle, ComponentKind, Icon, ImageKind, Launchable, ProjectUrl},
     xmltree, Component,
 };
-use std::{error::Error, fmt::Write};
 
-fn get_translatable<'a>(translatable: &'a appstream::TranslatableString, locale: &str) -> &'a str {
-    match translatable.get_for_locale(locale) {
+use crate::{
+    fl,
+    license::{self, LicenseKind},
+    AppProvide, ContentRating, RichText,
+};
+
+/// Locales to look for translations in, from most to least specific. For example `pt-BR` or
+/// `pt_BR.UTF-8` give `pt_BR` and `pt`. The untranslated text is used after these.
+pub fn locale_fallbacks(locale: &str) -> Vec<String> {
+    // System locales may use BCP 47 tags, while appstream uses POSIX names
+    let locale = locale.replace('-', "_");
+    let (locale, modifier_opt) = match locale.split_once('@') {
+        Some((locale, modifier)) => (locale, Some(modifier)),
+        None => (locale.as_str(), None),
+    };
+    let locale = locale.split('.').next().unwrap_or_default();
+    let language = locale.split('_').next().unwrap_or_default();
+
+    let mut fallbacks: Vec<String> = Vec::with_capacity(4);
+    let mut push = |fallback: String| {
+        if !fallback.is_empty() && fallback != "C" && !fallbacks.contains(&fallback) {
+            fallbacks.push(fallback);
+        }
+    };
+    if let Some(modifier) = modifier_opt {
+        push(format!("{}@{}", locale, modifier));
+    }
+    push(locale.to_string());
+    if let Some(modifier) = modifier_opt {
+        push(format!("{}@{}", language, modifier));
+    }
+    push(language.to_string());
+    fallbacks
+}
+
+fn get_translatable<'a>(
+    translatable: &'a appstream::TranslatableString,
+    locales: &[String],
+) -> &'a str {
+    for locale in locales.iter() {
+        if let Some(some) = translatable.get_for_locale(locale) {
+            return some.as_str();
+        }
+    }
+    match translatable.get_default() {
         Some(some) => some.as_str(),
-        None => match translatable.get_default() {
-            Some(some) => some.as_str(),
-            None => "",
-        },
+        None => "",
     }
 }
 
-//TODO: handle p tags with xml:lang
+// Only used when the description elements are not available, as with YAML catalogs
 fn get_markup_translatable<'a>(
     translatable: &'a appstream::MarkupTranslatableString,
-    locale: &str,
+    locales: &[String],
 ) -> &'a str {
-    match translatable.get_for_locale(locale) {
+    for locale in locales.iter() {
+        if let Some(some) = translatable.get_for_locale(locale) {
+            return some.as_str();
+        }
+    }
+    match translatable.get_default() {
         Some(some) => some.as_str(),
-        None => match translatable.get_default() {
-            Some(some) => some.as_str(),
-            None => "",
-        },
+        None => "",
     }
 }
 
-fn write_node(
-    s: &mut String,
-    node: &xmltree::XMLNode,
-    recursion: usize,
-) -> Result<(), Box<dyn Error>> {
-    if recursion >= 4 {
-        return Err(format!("maximum recursion level reached").into());
+/// Rank of a translation language in the locale fallbacks, lower is better
+fn locale_rank(lang_opt: Option<&str>, locales: &[String]) -> Option<usize> {
+    match lang_opt {
+        Some(lang) if lang != "C" => {
+            let lang = lang.replace('-', "_");
+            locales.iter().position(|x| *x == lang)
+        }
+        _ => Some(locales.len()),
     }
-    match node {
-        xmltree::XMLNode::Element(element) => match element.name.as_str() {
-            //TODO: actually style these
-            "b" | "em" => {
-                for child in element.children.iter() {
-                    write_node(s, child, recursion + 1)?;
-                }
-            }
-            "code" | "pre" => {
-                for child in element.children.iter() {
-                    write_node(s, child, recursion + 1)?;
-                }
-            }
-            "li" => {
-                for child in element.children.iter() {
-                    write_node(s, child, recursion + 1)?;
-                }
-            }
-            "ol" | "p" | "ul" => {
-                if !s.is_empty() {
-                    writeln!(s)?;
-                }
-                for (i, child) in element.children.iter().enumerate() {
-                    if element.name == "ol" {
-                        write!(s, "{:2}. ", i + 1)?;
-                    } else if element.name == "ul" {
-                        write!(s, " * ")?;
-                    }
-                    write_node(s, child, recursion + 1)?;
-                }
-                if !s.ends_with("\n") {
-                    writeln!(s)?;
-                }
+}
+
+/// Rank of a translation in the locale fallbacks, lower is better
+fn lang_rank(element: &xmltree::Element, locales: &[String]) -> Option<usize> {
+    locale_rank(element.attributes.get("lang").map(|x| x.as_str()), locales)
+}
+
+/// Replace each untranslated element with its best translation. Translations follow the
+/// element they translate, and are selected separately for every paragraph and list item.
+fn select_translations(nodes: &[xmltree::XMLNode], locales: &[String]) -> Vec<xmltree::XMLNode> {
+    let mut selected: Vec<(xmltree::XMLNode, usize)> = Vec::with_capacity(nodes.len());
+    for node in nodes.iter() {
+        let xmltree::XMLNode::Element(element) = node else {
+            // Text is never replaced by translations
+            selected.push((node.clone(), 0));
+            continue;
+        };
+        let mut element = element.clone();
+        element.children = select_translations(&element.children, locales);
+        if !element.attributes.contains_key("lang") {
+            selected.push((xmltree::XMLNode::Element(element), locales.len()));
+            continue;
+        }
+        let Some(rank) = lang_rank(&element, locales) else {
+            continue;
+        };
+        match selected.last_mut() {
+            Some((last, last_rank)) if rank < *last_rank => {
+                *last = xmltree::XMLNode::Element(element);
+                *last_rank = rank;
             }
-            _ => {
-                return Err(format!("unknown element {:?}", element.name).into());
+            Some(_) => {}
+            None => log::debug!("translation {:?} without original", element.name),
+        }
+    }
+    selected.into_iter().map(|(node, _rank)| node).collect()
+}
+
+/// Localize description elements. Catalogs either have one description element per
+/// language, or translate paragraphs inside a single description element.
+fn localized_description(elements: &[&xmltree::Element], locales: &[String]) -> Option<RichText> {
+    let element = elements
+        .iter()
+        .filter_map(|element| Some((lang_rank(element, locales)?, element)))
+        .min_by_key(|(rank, _element)| *rank)?
+        .1;
+    Some(RichText::from_nodes(&select_translations(
+        &element.children,
+        locales,
+    )))
+}
+
+/// Keywords of the best translation, followed by untranslated keywords so that searching
+/// in English still works
+fn localized_keywords<'a>(
+    keywords: impl Iterator<Item = (Option<&'a str>, &'a str)>,
+    locales: &[String],
+) -> Vec<String> {
+    let ranked: Vec<_> = keywords
+        .filter_map(|(lang_opt, keyword)| {
+            let keyword = keyword.trim();
+            if keyword.is_empty() {
+                return None;
             }
-        },
-        xmltree::XMLNode::Text(text) => {
-            for line in text.trim().lines() {
-                writeln!(s, "{}", line.trim())?;
+            Some((locale_rank(lang_opt, locales)?, keyword))
+        })
+        .collect();
+    let best_rank = ranked
+        .iter()
+        .map(|(rank, _keyword)| *rank)
+        .min()
+        .unwrap_or(locales.len());
+    let mut localized = Vec::new();
+    for rank in [best_rank, locales.len()] {
+        for (keyword_rank, keyword) in ranked.iter() {
+            if *keyword_rank == rank && !localized.iter().any(|x| x == keyword) {
+                localized.push(keyword.to_string());
             }
         }
-        _ => {
-            return Err(format!("unknown node {:?}", node).into());
-        }
     }
-    Ok(())
+    localized
 }
 
-fn convert_markup(markup: &str) -> Result<String, Box<dyn Error>> {
-    let mut s = String::new();
-    for node in xmltree::Element::parse_all(markup.as_bytes())? {
-        write_node(&mut s, &node, 0)?;
+/// Keywords from a component element. Catalogs either translate individual keywords, or
+/// have one keywords element per language.
+pub fn keywords_from_element(component_element: &xmltree::Element, locale: &str) -> Vec<String> {
+    let locales = locale_fallbacks(locale);
+    let mut keywords = Vec::new();
+    for keywords_element in child_elements(component_element, "keywords") {
+        let keywords_lang_opt = keywords_element.attributes.get("lang");
+        for keyword_element in child_elements(keywords_element, "keyword") {
+            let Some(text) = keyword_element.get_text() else {
+                continue;
+            };
+            let lang_opt = keyword_element
+                .attributes
+                .get("lang")
+                .or(keywords_lang_opt)
+                .map(|x| x.as_str());
+            keywords.push((lang_opt, text));
+        }
     }
-    Ok(s)
+    localized_keywords(
+        keywords
+            .iter()
+            .map(|(lang_opt, text)| (*lang_opt, text.as_ref())),
+        &locales,
+    )
+}
+
+/// Keywords from the Keywords field of YAML catalogs, which maps languages to lists
+pub fn keywords_from_yaml(value: &serde_yaml::Value, locale: &str) -> Vec<String> {
+    let locales = locale_fallbacks(locale);
+    let Some(mapping) = value.as_mapping() else {
+        return Vec::new();
+    };
+    let keywords = mapping.iter().flat_map(|(lang, list)| {
+        let lang_opt = lang.as_str();
+        list.as_sequence()
+            .into_iter()
+            .flatten()
+            .filter_map(move |keyword| Some((lang_opt, keyword.as_str()?)))
+    });
+    localized_keywords(keywords, &locales)
+}
+
+pub fn child_elements<'a>(
+    element: &'a xmltree::Element,
+    name: &'a str,
+) -> impl Iterator<Item = &'a xmltree::Element> {
+    element
+        .children
+        .iter()
+        .filter_map(move |child| match child {
+            xmltree::XMLNode::Element(child) if child.name == name => Some(child),
+            _ => None,
+        })
 }
 
 // Replaced Icon due to skip_field not supported in bitcode
@@ -108,7 +231,7 @@ pub enum AppIcon {
 pub struct AppRelease {
     pub timestamp: Option<i64>,
     pub version: String,
-    pub description: Option<String>,
+    pub description: Option<RichText>,
     pub url: Option<String>,
 }
 
@@ -119,22 +242,136 @@ pub struct AppScreenshot {
     pub url: String,
 }
 
+// Replaced ProjectUrl to limit the kinds that are shown
+#[derive(
+    Clone, Copy, Debug, Hash, Eq, Ord, PartialEq, PartialOrd, bitcode::Decode, bitcode::Encode,
+)]
+pub enum AppUrlKind {
+    Homepage,
+    Help,
+    Faq,
+    BugTracker,
+    Translate,
+    Donation,
+    Contact,
+}
+
+impl AppUrlKind {
+    /// Kind for a key of the Url field in YAML catalogs
+    pub fn parse(key: &str) -> Option<Self> {
+        match key {
+            "homepage" => Some(Self::Homepage),
+            "help" => Some(Self::Help),
+            "faq" => Some(Self::Faq),
+            "bugtracker" => Some(Self::BugTracker),
+            "translate" => Some(Self::Translate),
+            "donation" => Some(Self::Donation),
+            "contact" => Some(Self::Contact),
+            _ => None,
+        }
+    }
+
+    pub fn title(&self) -> String {
+        match self {
+            Self::Homepage => fl!("url-homepage"),
+            Self::Help => fl!("url-help"),
+            Self::Faq => fl!("url-faq"),
+            Self::BugTracker => fl!("url-bug-tracker"),
+            Self::Translate => fl!("url-translate"),
+            Self::Donation => fl!("url-donation"),
+            Self::Contact => fl!("url-contact"),
+        }
+    }
+}
+
 #[derive(Clone, Debug, Hash, Eq, PartialEq, bitcode::Decode, bitcode::Encode)]
+pub struct AppUrl {
+    pub kind: AppUrlKind,
+    pub url: String,
+}
+
+impl AppUrl {
+    /// Parse the Url field of YAML catalogs, which maps kinds to URLs
+    pub fn from_yaml(value: &serde_yaml::Value) -> Vec<Self> {
+        let Some(mapping) = value.as_mapping() else {
+            return Vec::new();
+        };
+        let mut urls: Vec<Self> = mapping
+            .iter()
+            .filter_map(|(key, url)| {
+                let kind = AppUrlKind::parse(key.as_str()?)?;
+                let url = url.as_str()?.to_string();
+                Some(Self { kind, url })
+            })
+            .collect();
+        urls.sort_by_key(|url| url.kind);
+        urls
+    }
+}
+
+// Replaced ComponentKind to limit the kinds that are shown
+#[derive(Clone, Copy, Debug, Default, Hash, Eq, PartialEq, bitcode::Decode, bitcode::Encode)]
+pub enum AppKind {
+    /// Packages without appstream data, like system packages
+    Generic,
+    #[default]
+    DesktopApplication,
+    ConsoleApplication,
+    WebApplication,
+    Addon,
+    Font,
+    Codec,
+    InputMethod,
+    Driver,
+    Runtime,
+}
+
+impl AppKind {
+    /// Kind for a component, if components of that kind are supported
+    pub fn from_component_kind(kind: &ComponentKind) -> Option<Self> {
+        match kind {
+            ComponentKind::DesktopApplication => Some(Self::DesktopApplication),
+            ComponentKind::ConsoleApplication => Some(Self::ConsoleApplication),
+            ComponentKind::WebApplication => Some(Self::WebApplication),
+            ComponentKind::Addon => Some(Self::Addon),
+            ComponentKind::Font => Some(Self::Font),
+            ComponentKind::Codec => Some(Self::Codec),
+            ComponentKind::InputMethod => Some(Self::InputMethod),
+            ComponentKind::Driver => Some(Self::Driver),
+            ComponentKind::Runtime => Some(Self::Runtime),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Hash, Eq, PartialEq, bitcode::Decode, bitcode::Encode)]
 pub struct AppInfo {
     pub source_id: String,
     pub source_name: String,
     pub origin_opt: Option<String>,
+    pub kind: AppKind,
     pub name: String,
     pub summary: String,
     pub developer_name: String,
-    pub description: String,
+    pub description: RichText,
     pub pkgnames: Vec<String>,
     pub categories: Vec<String>,
+    /// Localized search keywords
+    pub keywords: Vec<String>,
     pub desktop_ids: Vec<String>,
     pub flatpak_refs: Vec<String>,
+    /// IDs of the components this add-on extends
+    pub extends: Vec<String>,
+    /// Media types, binaries and other items this component provides, parsed from the
+    /// catalog by `AppProvide`
+    pub provides: Vec<AppProvide>,
     pub icons: Vec<AppIcon>,
     pub releases: Vec<AppRelease>,
     pub screenshots: Vec<AppScreenshot>,
+    pub content_rating_opt: Option<ContentRating>,
+    /// SPDX license expression of the project
+    pub project_license_opt: Option<String>,
+    pub urls: Vec<AppUrl>,
     pub monthly_downloads: u64,
 }
 
@@ -147,20 +384,22 @@ impl AppInfo {
         locale: &str,
         monthly_downloads: u64,
     ) -> Self {
-        let name = get_translatable(&component.name, locale);
+        let kind = AppKind::from_component_kind(&component.kind).unwrap_or(AppKind::Generic);
+        let locales = locale_fallbacks(locale);
+        let name = get_translatable(&component.name, &locales);
         let summary = component
             .summary
             .as_ref()
-            .map_or("", |x| get_translatable(x, locale));
+            .map_or("", |x| get_translatable(x, &locales));
         let developer_name = component
             .developer_name
             .as_ref()
-            .map_or("", |x| get_translatable(x, locale));
+            .map_or("", |x| get_translatable(x, &locales));
         let description_markup = component
             .description
             .as_ref()
-            .map_or("", |x| get_markup_translatable(x, locale));
-        let description = match convert_markup(description_markup) {
+            .map_or("", |x| get_markup_translatable(x, &locales));
+        let description = match RichText::parse(description_markup) {
             Ok(ok) => ok,
             Err(err) => {
                 log::warn!(
@@ -169,7 +408,7 @@ impl AppInfo {
                     origin_opt,
                     err
                 );
-                String::new()
+                RichText::default()
             }
         };
         let categories = component
@@ -193,6 +432,28 @@ impl AppInfo {
                 _ => None,
             })
             .collect();
+        let mut urls: Vec<_> = component
+            .urls
+            .into_iter()
+            .filter_map(|project_url| {
+                let (kind, url) = match project_url {
+                    ProjectUrl::Homepage(url) => (AppUrlKind::Homepage, url),
+                    ProjectUrl::Help(url) => (AppUrlKind::Help, url),
+                    ProjectUrl::Faq(url) => (AppUrlKind::Faq, url),
+                    ProjectUrl::BugTracker(url) => (AppUrlKind::BugTracker, url),
+                    ProjectUrl::Translate(url) => (AppUrlKind::Translate, url),
+                    ProjectUrl::Donation(url) => (AppUrlKind::Donation, url),
+                    ProjectUrl::Contact(url) => (AppUrlKind::Contact, url),
+                    _ => return None,
+                };
+                Some(AppUrl {
+                    kind,
+                    url: url.into(),
+                })
+            })
+            .collect();
+        urls.sort_by_key(|url| url.kind);
+        let extends = component.extends.into_iter().map(|id| id.0).collect();
         let icons = component
             .icons
             .into_iter()
@@ -233,7 +494,7 @@ impl AppInfo {
             .into_iter()
             .filter_map(|release| {
                 let description = release.description.as_ref().and_then(|x| {
-                    match convert_markup(get_markup_translatable(x, locale)) {
+                    match RichText::parse(get_markup_translatable(x, &locales)) {
                         Ok(ok) => Some(ok),
                         Err(err) => {
                             //TODO: better handling of release description
@@ -265,7 +526,7 @@ impl AppInfo {
                         caption: screenshot
                             .caption
                             .as_ref()
-                            .map_or("", |x| get_translatable(x, locale))
+                            .map_or("", |x| get_translatable(x, &locales))
                             .to_string(),
                         url: image.url.into(),
                     });
@@ -278,18 +539,203 @@ impl AppInfo {
             source_id: source_id.to_string(),
             source_name: source_name.to_string(),
             origin_opt: origin_opt.map(|x| x.to_string()),
+            kind,
             name: name.to_string(),
             summary: summary.to_string(),
             developer_name: developer_name.to_string(),
             description,
             pkgnames: component.pkgname.map_or(Vec::new(), |x| vec![x]),
             categories,
+            keywords: Vec::new(),
             desktop_ids,
             flatpak_refs,
+            extends,
+            provides: Vec::new(),
             icons,
             releases,
             screenshots,
+            content_rating_opt: component.content_rating.as_ref().map(ContentRating::new),
+            project_license_opt: component.project_license.map(|license| license.0),
+            urls,
             monthly_downloads,
         }
     }
+
+    /// Localize descriptions from the component element this info was created from, which
+    /// unlike the parsed component has translations of individual paragraphs
+    pub fn localize_descriptions(&mut self, component_element: &xmltree::Element, locale: &str) {
+        let locales = locale_fallbacks(locale);
+        let descriptions: Vec<_> = child_elements(component_element, "description").collect();
+        if let Some(description) = localized_description(&descriptions, &locales) {
+            self.description = description;
+        }
+        for releases_element in child_elements(component_element, "releases") {
+            for release_element in child_elements(releases_element, "release") {
+                let Some(version) = release_element.attributes.get("version") else {
+                    continue;
+                };
+                let Some(release) = self.releases.iter_mut().find(|x| &x.version == version) else {
+                    continue;
+                };
+                let descriptions: Vec<_> = child_elements(release_element, "description").collect();
+                if let Some(description) = localized_description(&descriptions, &locales) {
+                    release.description = Some(description).filter(|x| !x.is_empty());
+                }
+            }
+        }
+    }
+
+    /// Classification of the project license, unknown if there is none
+    pub fn license_kind(&self) -> LicenseKind {
+        self.project_license_opt
+            .as_deref()
+            .map_or(LicenseKind::Unknown, license::kind)
+    }
+
+    /// Apply a merge component to this info. With replace, fields set by the merge component
+    /// overwrite existing ones. Otherwise lists are appended to and only empty fields are set.
+    pub fn merge(&mut self, other: &Self, replace: bool) {
+        fn merge_string(a: &mut String, b: &str, replace: bool) {
+            if !b.is_empty() && (replace || a.is_empty()) {
+                *a = b.to_string();
+            }
+        }
+
+        fn merge_vec<T: Clone + PartialEq>(a: &mut Vec<T>, b: &[T], replace: bool) {
+            if b.is_empty() {
+                return;
+            }
+            if replace {
+                *a = b.to_vec();
+            } else {
+                for item in b.iter() {
+                    if !a.contains(item) {
+                        a.push(item.clone());
+                    }
+                }
+            }
+        }
+
+        merge_string(&mut self.name, &other.name, replace);
+        merge_string(&mut self.summary, &other.summary, replace);
+        merge_string(&mut self.developer_name, &other.developer_name, replace);
+        if !other.description.is_empty() && (replace || self.description.is_empty()) {
+            self.description = other.description.clone();
+        }
+        merge_vec(&mut self.pkgnames, &other.pkgnames, replace);
+        merge_vec(&mut self.categories, &other.categories, replace);
+        merge_vec(&mut self.keywords, &other.keywords, replace);
+        merge_vec(&mut self.desktop_ids, &other.desktop_ids, replace);
+        merge_vec(&mut self.flatpak_refs, &other.flatpak_refs, replace);
+        merge_vec(&mut self.extends, &other.extends, replace);
+        merge_vec(&mut self.provides, &other.provides, replace);
+        merge_vec(&mut self.icons, &other.icons, replace);
+        merge_vec(&mut self.releases, &other.releases, replace);
+        merge_vec(&mut self.screenshots, &other.screenshots, replace);
+        if other.content_rating_opt.is_some() && (replace || self.content_rating_opt.is_none()) {
+            self.content_rating_opt = other.content_rating_opt.clone();
+        }
+        if other.project_license_opt.is_some() && (replace || self.project_license_opt.is_none()) {
+            self.project_license_opt = other.project_license_opt.clone();
+        }
+        merge_vec(&mut self.urls, &other.urls, replace);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn description(xml: &str, locale: &str) -> Option<String> {
+        let component = xmltree::Element::parse(xml.as_bytes()).unwrap();
+        let descriptions: Vec<_> = child_elements(&component, "description").collect();
+        localized_description(&descriptions, &locale_fallbacks(locale)).map(|x| x.to_string())
+    }
+
+    #[test]
+    fn fallbacks() {
+        assert_eq!(locale_fallbacks("de_AT"), ["de_AT", "de"]);
+        assert_eq!(locale_fallbacks("pt-BR.UTF-8"), ["pt_BR", "pt"]);
+        assert_eq!(
+            locale_fallbacks("sr_RS.UTF-8@latin"),
+            ["sr_RS@latin", "sr_RS", "sr@latin", "sr"]
+        );
+        assert_eq!(locale_fallbacks("fr"), ["fr"]);
+        assert!(locale_fallbacks("C").is_empty());
+        assert!(locale_fallbacks("").is_empty());
+    }
+
+    #[test]
+    fn translated_paragraphs() {
+        let xml = concat!(
+            "<component><description>",
+            "<p>Calendar</p>",
+            "<p xml:lang=\"de\">Kalender</p>",
+            "<p>Starts in January</p>",
+            "<p xml:lang=\"de\">Beginnt im Januar</p>",
+            "<p xml:lang=\"de_AT\">Beginnt im Jänner</p>",
+            "<p>Untranslated</p>",
+            "</description></component>",
+        );
+        assert_eq!(
+            description(xml, "de_AT").as_deref(),
+            Some("Kalender\nBeginnt im Jänner\nUntranslated")
+        );
+        assert_eq!(
+            description(xml, "de_DE.UTF-8").as_deref(),
+            Some("Kalender\nBeginnt im Januar\nUntranslated")
+        );
+        assert_eq!(
+            description(xml, "fr").as_deref(),
+            Some("Calendar\nStarts in January\nUntranslated")
+        );
+    }
+
+    #[test]
+    fn translated_list_items() {
+        let xml = concat!(
+            "<component><description>",
+            "<p>Features:</p>",
+            "<p xml:lang=\"de\">Funktionen:</p>",
+            "<ul>",
+            "<li>One</li>",
+            "<li xml:lang=\"fr\">Un</li>",
+            "<li xml:lang=\"de\">Eins</li>",
+            "<li>Two</li>",
+            "<li xml:lang=\"fr\">Deux</li>",
+            "</ul>",
+            "</description></component>",
+        );
+        assert_eq!(
+            description(xml, "de_AT").as_deref(),
+            Some("Funktionen:\n * Eins\n * Two")
+        );
+        assert_eq!(
+            description(xml, "fr_CA").as_deref(),
+            Some("Features:\n * Un\n * Deux")
+        );
+    }
+
+    #[test]
+    fn translated_description_elements() {
+        let xml = concat!(
+            "<component>",
+            "<description><p>Calendar</p></description>",
+            "<description xml:lang=\"de\"><p>Kalender</p></description>",
+            "<description xml:lang=\"fr\"><p>Calendrier</p></description>",
+            "</component>",
+        );
+        assert_eq!(description(xml, "de_AT").as_deref(), Some("Kalender"));
+        assert_eq!(description(xml, "fr_FR").as_deref(), Some("Calendrier"));
+        assert_eq!(description(xml, "it").as_deref(), Some("Calendar"));
+
+        // Without an untranslated description, other languages are not used as fallback
+        let xml = concat!(
+            "<component>",
+            "<description xml:lang=\"de\"><p>Kalender</p></description>",
+            "</component>",
+        );
+        assert_eq!(description(xml, "de_AT").as_deref(), Some("Kalender"));
+        assert_eq!(description(xml, "it"), None);
+    }
 }
@@ -1,27 +1,38 @@
 use appstream::{
-    enums::{ComponentKind, Icon, ImageKind, Launchable, ReleaseKind, ReleaseUrgency},
+    enums::{Icon, ImageKind, Launchable, ReleaseKind, ReleaseUrgency},
     url::Url,
     xmltree, Component, Image, MarkupTranslatableString, ParseError, Release, Screenshot,
 };
 use cosmic::widget;
 use flate2::read::GzDecoder;
+use memmap2::Mmap;
 use rayon::prelude::*;
 use serde::Deserialize;
+use sha2::{Digest, Sha256};
 use std::{
+    borrow::Cow,
     cmp,
     collections::{BTreeMap, HashMap, HashSet},
     error::Error,
-    fs,
-    io::{Read, Write},
+    fmt, fs,
+    io::{self, BufReader, Read, Write},
+    mem,
     path::{Path, PathBuf},
-    sync::Arc,
+    str,
+    sync::{Arc, Mutex},
     time::{Instant, SystemTime},
 };
 
-use crate::{stats, AppIcon, AppId, AppInfo};
+use crate::{
+    app_info::{self, AppScreenshot, AppUrl},
+    component_reader::ComponentReader,
+    remote_icon, stats, AppIcon, AppId, AppInfo, AppKind, AppProvide, ContentRating, RichText,
+};
 
 const PREFIXES: &'static [&'static str] = &["/usr/share", "/var/lib", "/var/cache"];
 const CATALOGS: &'static [&'static str] = &["swcatalog", "app-info"];
+const CATALOG_CACHE_DIR: &'static str = "catalogs";
+const CACHED_ICON_SIZES: &'static [u32] = &[128, 64, 48];
 
 #[derive(
     Clone,
@@ -41,6 +52,239 @@ pub struct AppstreamCacheTag {
     pub size: u64,
 }
 
+/// How a merge component is combined with the component of the same ID
+#[derive(Clone, Copy, Debug, Eq, PartialEq, bitcode::Decode, bitcode::Encode)]
+enum MergeKind {
+    Append,
+    Replace,
+    RemoveComponent,
+}
+
+impl MergeKind {
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "append" => Some(Self::Append),
+            "replace" => Some(Self::Replace),
+            "remove-component" => Some(Self::RemoveComponent),
+            _ => None,
+        }
+    }
+}
+
+/// Components parsed from one appstream file
+#[derive(Debug, Default, bitcode::Decode, bitcode::Encode)]
+struct Catalog {
+    /// Priority of the collection, higher priority catalogs override lower ones
+    priority: i32,
+    infos: Vec<(AppId, Arc<AppInfo>)>,
+    merges: Vec<(AppId, MergeKind, Arc<AppInfo>)>,
+}
+
+/// Cached contents of one appstream file, so unchanged files are not parsed again
+#[derive(Debug, bitcode::Decode, bitcode::Encode)]
+struct CatalogCache {
+    path: String,
+    tag: AppstreamCacheTag,
+    locale: String,
+    catalog: Catalog,
+}
+
+/// Location of details that are loaded lazily from the cache file
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, bitcode::Decode, bitcode::Encode)]
+pub struct AppDetailsOffsets {
+    /// Description stored as UTF-8
+    description_start: u64,
+    description_len: u64,
+    /// Release descriptions and screenshots stored as [`AppDetails`]
+    details_start: u64,
+    details_len: u64,
+}
+
+/// Heavy fields of [`AppInfo`] that are only needed for the details page
+#[derive(Debug, Default, bitcode::Decode, bitcode::Encode)]
+struct AppDetails {
+    description: RichText,
+    release_descriptions: Vec<Option<RichText>>,
+    screenshots: Vec<AppScreenshot>,
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub enum AppstreamCacheStatus {
+    /// Infos were not loaded through the cache, for example when provided directly by a backend
+    #[default]
+    Uncached,
+    /// Infos were loaded from the cache
+    Hit,
+    /// Infos were loaded from original sources, for the specified reason
+    Miss(String),
+}
+
+/// Identifies cache files written by this program
+const CACHE_MAGIC: &'static [u8; 4] = b"CSAC";
+/// Version of the cache format, must be changed when the format or any cached type changes
+const CACHE_VERSION: u32 = 13;
+/// Length of the magic and version at the start of every cache file
+const HEADER_LEN: usize = 8;
+/// Extension given to cache files that failed to decode, so they are kept for inspection
+/// but never read again
+const QUARANTINE_EXTENSION: &'static str = "corrupt";
+
+#[derive(Debug)]
+pub enum AppstreamCacheError {
+    /// The user cache directory could not be found
+    NoCacheDir,
+    /// A cache file could not be read or written
+    Io(PathBuf, io::Error),
+    /// A cache file is damaged and was quarantined
+    Corrupt(PathBuf, String),
+    /// A cache file was written by a different version of the format
+    Version(PathBuf, u32),
+    /// The cache does not match the current appstream files or settings
+    Outdated(&'static str),
+}
+
+impl AppstreamCacheError {
+    /// True if the cache has to be rebuilt for reasons other than changed appstream data
+    pub fn is_rebuild(&self) -> bool {
+        matches!(self, Self::Corrupt(..) | Self::Version(..))
+    }
+}
+
+impl fmt::Display for AppstreamCacheError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::NoCacheDir => write!(f, "failed to find cache directory"),
+            Self::Io(path, err) => write!(f, "failed to access {:?}: {}", path, err),
+            Self::Corrupt(path, reason) => write!(f, "corrupt cache {:?}: {}", path, reason),
+            Self::Version(path, version) => write!(
+                f,
+                "cache {:?} has version {}, expected {}",
+                path, version, CACHE_VERSION
+            ),
+            Self::Outdated(reason) => write!(f, "cache is outdated: {}", reason),
+        }
+    }
+}
+
+impl Error for AppstreamCacheError {}
+
+fn io_error(path: &Path, err: io::Error) -> AppstreamCacheError {
+    AppstreamCacheError::Io(path.to_path_buf(), err)
+}
+
+fn corrupt<T: fmt::Display>(path: &Path, reason: T) -> AppstreamCacheError {
+    AppstreamCacheError::Corrupt(path.to_path_buf(), reason.to_string())
+}
+
+/// Check the magic and version of a cache file, returning the data after the header
+fn check_header<'a>(path: &Path, data: &'a [u8]) -> Result<&'a [u8], AppstreamCacheError> {
+    if data.len() < HEADER_LEN || &data[..4] != CACHE_MAGIC {
+        return Err(corrupt(path, "invalid header"));
+    }
+    let version = u32::from_le_bytes([data[4], data[5], data[6], data[7]]);
+    if version != CACHE_VERSION {
+        return Err(AppstreamCacheError::Version(path.to_path_buf(), version));
+    }
+    Ok(&data[HEADER_LEN..])
+}
+
+/// Write a cache file atomically, with the header followed by the contents written by `f`
+fn write_cache_file<F: FnOnce(&mut fs::File) -> io::Result<()>>(
+    path: &Path,
+    f: F,
+) -> Result<(), AppstreamCacheError> {
+    atomicwrites::AtomicFile::new(path, atomicwrites::OverwriteBehavior::AllowOverwrite)
+        .write(|file| {
+            file.write_all(CACHE_MAGIC)?;
+            file.write_all(&CACHE_VERSION.to_le_bytes())?;
+            f(file)
+        })
+        .map_err(|err| match err {
+            atomicwrites::Error::Internal(err) | atomicwrites::Error::User(err) => {
+                io_error(path, err)
+            }
+        })
+}
+
+fn is_quarantined(path: &Path) -> bool {
+    path.extension()
+        .map_or(false, |x| x == QUARANTINE_EXTENSION)
+}
+
+/// Move a corrupt cache file aside, replacing any earlier quarantined copy
+fn quarantine(path: &Path) {
+    let quarantine_path = path.with_extension(QUARANTINE_EXTENSION);
+    match fs::rename(path, &quarantine_path) {
+        Ok(()) => {
+            log::warn!("quarantined corrupt cache {:?}", quarantine_path);
+        }
+        Err(err) => {
+            log::warn!("failed to quarantine corrupt cache {:?}: {}", path, err);
+        }
+    }
+}
+
+fn remove_outdated(path: &Path) {
+    match fs::remove_file(path) {
+        Ok(()) => {
+            log::info!("removed outdated cache file {:?}", path);
+        }
+        Err(err) => {
+            log::info!("failed to remove outdated cache file {:?}: {}", path, err);
+        }
+    }
+}
+
+/// Names of caches that will be rebuilt from scratch on the next load, because they were
+/// written by an older version, are corrupt, or have no index. Only reads file headers.
+pub fn rebuild_needed() -> Vec<String> {
+    let Some(root) = dirs::cache_dir().map(|x| x.join("cosmic-store")) else {
+        return Vec::new();
+    };
+    let Ok(read_dir) = fs::read_dir(&root) else {
+        return Vec::new();
+    };
+    let mut names = Vec::new();
+    for entry in read_dir.filter_map(|x| x.ok()) {
+        let dir = entry.path();
+        let Some(name) = entry.file_name().to_str().map(|x| x.to_string()) else {
+            continue;
+        };
+        // Caches from before versioned headers used versioned file names
+        let legacy = fs::read_dir(&dir).map_or(false, |read_dir| {
+            read_dir.filter_map(|x| x.ok()).any(|x| {
+                x.file_name()
+                    .to_str()
+                    .map_or(false, |x| x.starts_with("appstream_cache-"))
+            })
+        });
+        if legacy {
+            names.push(name);
+            continue;
+        }
+        let cache_path = dir.join(AppstreamCache::cache_filename());
+        if !cache_path.is_file() {
+            // Only directories that held an appstream cache are rebuilt
+            if dir.join(CATALOG_CACHE_DIR).is_dir() {
+                names.push(name);
+            }
+            continue;
+        }
+        let mut header = [0; HEADER_LEN];
+        let res = fs::File::open(&cache_path).and_then(|mut file| file.read_exact(&mut header));
+        match res {
+            Ok(()) => {
+                if check_header(&cache_path, &header).is_err() {
+                    names.push(name);
+                }
+            }
+            Err(_) => names.push(name),
+        }
+    }
+    names.sort();
+    names
+}
+
 #[derive(Debug, Default, bitcode::Decode, bitcode::Encode)]
 pub struct AppstreamCache {
     pub source_id: String,
@@ -51,6 +295,20 @@ pub struct AppstreamCache {
     pub locale: String,
     pub infos: HashMap<AppId, Arc<AppInfo>>,
     pub pkgnames: HashMap<String, HashSet<AppId>>,
+    pub provides: HashMap<AppProvide, HashSet<AppId>>,
+    // Only set when infos were loaded from the cache without their details
+    pub details_offsets: HashMap<AppId, AppDetailsOffsets>,
+    // Not stored in the cache
+    #[bitcode(skip)]
+    details_mmap_opt: Option<Mmap>,
+    #[bitcode(skip)]
+    pub status: AppstreamCacheStatus,
+    // Stock icon paths by name and size, looked up from the theme once per load
+    #[bitcode(skip)]
+    stock_icon_paths: Mutex<HashMap<(String, u16), Option<PathBuf>>>,
+    // Replaces the user cache directory, so tests do not touch it
+    #[bitcode(skip)]
+    cache_root_opt: Option<PathBuf>,
 }
 
 impl AppstreamCache {
@@ -183,268 +441,633 @@ impl AppstreamCache {
     }
 
     /// Directory where cache should be stored
-    fn cache_dir(&self, cache_name: &str) -> Option<PathBuf> {
-        dirs::cache_dir().map(|x| x.join("cosmic-store").join(cache_name))
+    fn cache_dir(&self, cache_name: &str) -> Result<PathBuf, AppstreamCacheError> {
+        if let Some(cache_root) = &self.cache_root_opt {
+            return Ok(cache_root.join(cache_name));
+        }
+        dirs::cache_dir()
+            .map(|x| x.join("cosmic-store").join(cache_name))
+            .ok_or(AppstreamCacheError::NoCacheDir)
     }
 
-    /// Versioned filename of cache
+    /// Filename of cache, the format version is stored in the file header
     fn cache_filename() -> &'static str {
-        "appstream_cache-v0-1.bitcode-v0-6"
+        "appstream_cache.bitcode"
     }
 
-    /// Remove all files from cache not matching filename
-    pub fn clean_cache(&self, cache_name: &str) {
+    /// Remove all files from cache not matching filename, except quarantined files
+    pub fn clean_cache(&self, cache_name: &str) -> Result<(), AppstreamCacheError> {
         let start = Instant::now();
 
-        let cache_dir = match self.cache_dir(cache_name) {
-            Some(some) => some,
-            None => {
-                log::warn!("failed to find cache directory");
-                return;
-            }
-        };
-
+        let cache_dir = self.cache_dir(cache_name)?;
         if !cache_dir.is_dir() {
-            match fs::create_dir_all(&cache_dir) {
-                Ok(()) => {}
-                Err(err) => {
-                    log::warn!("failed to create cache directory {:?}: {}", cache_dir, err);
-                    return;
-                }
-            }
+            fs::create_dir_all(&cache_dir).map_err(|err| io_error(&cache_dir, err))?;
         }
 
-        let read_dir = match fs::read_dir(&cache_dir) {
-            Ok(ok) => ok,
-            Err(err) => {
-                log::warn!("failed to read cache directory {:?}: {}", cache_dir, err);
-                return;
-            }
-        };
-
+        let read_dir = fs::read_dir(&cache_dir).map_err(|err| io_error(&cache_dir, err))?;
         for entry_res in read_dir {
-            let entry = match entry_res {
-                Ok(ok) => ok,
-                Err(err) => {
-                    log::warn!(
-                        "failed to read entry in cache directory {:?}: {}",
-                        cache_dir,
-                        err
-                    );
-                    continue;
-                }
-            };
+            let entry = entry_res.map_err(|err| io_error(&cache_dir, err))?;
 
             let path = entry.path();
             if path.is_dir() {
-                log::warn!("unexpected directory in cache: {:?}", path);
+                if entry.file_name() == CATALOG_CACHE_DIR {
+                    self.clean_catalog_cache(cache_name, &path)?;
+                } else {
+                    log::warn!("unexpected directory in cache: {:?}", path);
+                }
                 continue;
             }
 
-            if entry.file_name() != Self::cache_filename() {
-                match fs::remove_file(&path) {
-                    Ok(()) => {
-                        log::info!("removed outdated cache file {:?}", entry.path());
-                    }
-                    Err(err) => {
-                        log::info!(
-                            "failed to remove outdated cache file {:?}: {}",
-                            entry.path(),
-                            err
-                        );
-                    }
-                }
+            if entry.file_name() != Self::cache_filename() && !is_quarantined(&path) {
+                remove_outdated(&path);
             }
         }
 
         let duration = start.elapsed();
         log::info!("cleaned cache {:?} in {:?}", cache_name, duration);
+        Ok(())
     }
 
-    /// Reload from cache, returns true if loaded and false if out of date
-    //TODO: return errors instead of handling them internally?
-    pub fn load_cache(&mut self, cache_name: &str) -> bool {
-        let start = Instant::now();
-
-        let cache_dir = match self.cache_dir(cache_name) {
-            Some(some) => some,
-            None => {
-                log::warn!("failed to find cache directory");
-                return false;
+    /// Remove cached contents of appstream files that are no longer used
+    fn clean_catalog_cache(&self, cache_name: &str, dir: &Path) -> Result<(), AppstreamCacheError> {
+        let mut expected = HashSet::new();
+        for path in self.path_tags.keys() {
+            expected.insert(self.catalog_cache_path(cache_name, path)?);
+        }
+        let read_dir = fs::read_dir(dir).map_err(|err| io_error(dir, err))?;
+        for entry_res in read_dir {
+            let path = entry_res.map_err(|err| io_error(dir, err))?.path();
+            if !expected.contains(&path) && !is_quarantined(&path) {
+                remove_outdated(&path);
             }
-        };
+        }
+        Ok(())
+    }
+
+    /// Remove cache files, so the next reload will load from originals
+    pub fn remove_cache(&self, cache_name: &str) -> Result<(), AppstreamCacheError> {
+        let cache_dir = self.cache_dir(cache_name)?;
         let cache_path = cache_dir.join(Self::cache_filename());
+        if cache_path.is_file() {
+            fs::remove_file(&cache_path).map_err(|err| io_error(&cache_path, err))?;
+            log::info!("removed cache file {:?}", cache_path);
+        }
+        let catalog_cache_dir = cache_dir.join(CATALOG_CACHE_DIR);
+        if catalog_cache_dir.is_dir() {
+            fs::remove_dir_all(&catalog_cache_dir)
+                .map_err(|err| io_error(&catalog_cache_dir, err))?;
+            log::info!("removed catalog cache {:?}", catalog_cache_dir);
+        }
+        Ok(())
+    }
 
-        let data = match fs::read(&cache_path) {
-            Ok(ok) => ok,
-            Err(err) => {
-                log::warn!("failed to read cache {:?}: {}", cache_path, err);
-                return false;
-            }
-        };
+    /// Reload from cache, fails if the cache is missing, corrupt or out of date.
+    /// Corrupt cache files are quarantined so they are not read again.
+    pub fn load_cache(&mut self, cache_name: &str) -> Result<(), AppstreamCacheError> {
+        let start = Instant::now();
 
-        let cache = match bitcode::decode::<Self>(&data) {
-            Ok(ok) => ok,
-            Err(err) => {
-                log::warn!("failed to decode cache {:?}: {}", cache_name, err);
-                return false;
-            }
-        };
+        let cache_path = self.cache_dir(cache_name)?.join(Self::cache_filename());
+        let file = fs::File::open(&cache_path).map_err(|err| io_error(&cache_path, err))?;
+
+        // Safety: the cache is only replaced atomically by renaming, so the mapped file is not
+        // modified while in use
+        let mmap = unsafe { Mmap::map(&file) }.map_err(|err| io_error(&cache_path, err))?;
+
+        // After the header is the length of the details section, followed by details that
+        // are read lazily, followed by the encoded cache
+        let cache = check_header(&cache_path, &mmap)
+            .and_then(|data| {
+                let details_len = data
+                    .get(..8)
+                    .and_then(|x| x.try_into().ok())
+                    .map(u64::from_le_bytes)
+                    .ok_or_else(|| corrupt(&cache_path, "truncated header"))?;
+                let index_start = usize::try_from(details_len)
+                    .ok()
+                    .and_then(|x| x.checked_add(HEADER_LEN + 8))
+                    .filter(|x| *x <= mmap.len())
+                    .ok_or_else(|| corrupt(&cache_path, "invalid details length"))?;
+                bitcode::decode::<Self>(&mmap[index_start..])
+                    .map_err(|err| corrupt(&cache_path, err))
+            })
+            .map_err(|err| {
+                if let AppstreamCacheError::Corrupt(_, _) = err {
+                    quarantine(&cache_path);
+                }
+                err
+            })?;
 
         if cache.path_tags != self.path_tags {
-            log::info!("cache {:?} path tags mismatch, needs refresh", cache_name);
-            return false;
+            return Err(AppstreamCacheError::Outdated("appstream files changed"));
         }
 
-        //TODO: icons_paths intentionally ignored, should it be?
+        // Icon paths are resolved when loading, so they must match too
+        if cache.icons_paths != self.icons_paths {
+            return Err(AppstreamCacheError::Outdated("icon paths changed"));
+        }
 
         if cache.locale != self.locale {
-            log::info!("cache {:?} locale mismatch, needs refresh", cache_name);
-            return false;
+            return Err(AppstreamCacheError::Outdated("locale changed"));
         }
 
-        // Everything matches, copy infos and pkgnames
+        // Everything matches, copy infos and indexes, and keep the file mapped for details
         self.infos = cache.infos;
         self.pkgnames = cache.pkgnames;
+        self.provides = cache.provides;
+        self.details_offsets = cache.details_offsets;
+        self.details_mmap_opt = Some(mmap);
+        self.stock_icon_paths = Mutex::default();
 
         let duration = start.elapsed();
         log::info!("loaded cache {:?} in {:?}", cache_name, duration);
-        true
+        Ok(())
     }
 
     /// Save to cache
-    //TODO: return errors instead of handling them internally?
-    pub fn save_cache(&self, cache_name: &str) {
+    pub fn save_cache(&self, cache_name: &str) -> Result<(), AppstreamCacheError> {
         let start = Instant::now();
 
-        let bitcode = bitcode::encode::<Self>(self);
+        // Details are moved out of the infos, so that loading the cache only decodes the
+        // fields needed for browsing and searching
+        let mut details = Vec::new();
+        let mut index = Self {
+            source_id: self.source_id.clone(),
+            source_name: self.source_name.clone(),
+            path_tags: self.path_tags.clone(),
+            icons_paths: self.icons_paths.clone(),
+            locale: self.locale.clone(),
+            infos: HashMap::with_capacity(self.infos.len()),
+            pkgnames: self.pkgnames.clone(),
+            provides: self.provides.clone(),
+            details_offsets: HashMap::with_capacity(self.infos.len()),
+            details_mmap_opt: None,
+            status: AppstreamCacheStatus::default(),
+            stock_icon_paths: Mutex::default(),
+            cache_root_opt: None,
+        };
+        for (id, info) in self.infos.iter() {
+            let mut info = (*self.details(id, info)).clone();
+            let description = mem::take(&mut info.description);
+            // Plain text is stored separately so it can be searched without decoding
+            let description_text = description.to_string();
+            let app_details = AppDetails {
+                description,
+                release_descriptions: info
+                    .releases
+                    .iter_mut()
+                    .map(|release| release.description.take())
+                    .collect(),
+                screenshots: mem::take(&mut info.screenshots),
+            };
+            let app_details_data = bitcode::encode(&app_details);
+
+            // Offsets include the header and length
+            let description_start = (HEADER_LEN + 8 + details.len()) as u64;
+            details.extend_from_slice(description_text.as_bytes());
+            let details_start = (HEADER_LEN + 8 + details.len()) as u64;
+            details.extend_from_slice(&app_details_data);
+            index.details_offsets.insert(
+                id.clone(),
+                AppDetailsOffsets {
+                    description_start,
+                    description_len: description_text.len() as u64,
+                    details_start,
+                    details_len: app_details_data.len() as u64,
+                },
+            );
+            index.infos.insert(id.clone(), Arc::new(info));
+        }
+        let bitcode = bitcode::encode::<Self>(&index);
 
-        let cache_dir = match self.cache_dir(cache_name) {
-            Some(some) => some,
+        let cache_path = self.cache_dir(cache_name)?.join(Self::cache_filename());
+        write_cache_file(&cache_path, |file| {
+            file.write_all(&(details.len() as u64).to_le_bytes())?;
+            file.write_all(&details)?;
+            file.write_all(&bitcode)
+        })?;
+
+        let duration = start.elapsed();
+        log::info!("saved cache {:?} in {:?}", cache_name, duration);
+        Ok(())
+    }
+
+    /// Parse one original appstream file
+    fn parse_path(&self, path: &str) -> Option<Catalog> {
+        let file_name = match Path::new(path).file_name() {
+            Some(file_name_os) => match file_name_os.to_str() {
+                Some(some) => some,
+                None => {
+                    log::error!("failed to convert to UTF-8: {:?}", file_name_os);
+                    return None;
+                }
+            },
             None => {
-                log::warn!("failed to find user cache directory");
-                return;
+                log::error!("path has no file name: {:?}", path);
+                return None;
             }
         };
-        let cache_path = cache_dir.join(Self::cache_filename());
 
-        match atomicwrites::AtomicFile::new(
-            &cache_path,
-            atomicwrites::OverwriteBehavior::AllowOverwrite,
-        )
-        .write(|file| file.write_all(&bitcode))
-        {
-            Ok(()) => {}
+        //TODO: memory map?
+        let mut file = match fs::File::open(&path) {
+            Ok(ok) => ok,
             Err(err) => {
-                log::warn!("failed to write cache {:?}: {}", cache_path, err);
-                return;
+                log::error!("failed to open {:?}: {}", path, err);
+                return None;
             }
+        };
+
+        if file_name.ends_with(".xml.gz") {
+            let mut gz = GzDecoder::new(&mut file);
+            match self.parse_xml(path, &mut gz) {
+                Ok(catalog) => Some(catalog),
+                Err(err) => {
+                    log::error!("failed to parse {:?}: {}", path, err);
+                    None
+                }
+            }
+        } else if file_name.ends_with(".yml.gz") {
+            let mut gz = GzDecoder::new(&mut file);
+            match self.parse_yaml(path, &mut gz) {
+                Ok(catalog) => Some(catalog),
+                Err(err) => {
+                    log::error!("failed to parse {:?}: {}", path, err);
+                    None
+                }
+            }
+        } else if file_name.ends_with(".xml") {
+            match self.parse_xml(path, &mut file) {
+                Ok(catalog) => Some(catalog),
+                Err(err) => {
+                    log::error!("failed to parse {:?}: {}", path, err);
+                    None
+                }
+            }
+        } else if file_name.ends_with(".yml") {
+            match self.parse_yaml(path, &mut file) {
+                Ok(catalog) => Some(catalog),
+                Err(err) => {
+                    log::error!("failed to parse {:?}: {}", path, err);
+                    None
+                }
+            }
+        } else {
+            log::error!("unknown appstream file type: {:?}", path);
+            None
         }
+    }
 
-        let duration = start.elapsed();
-        log::info!("saved cache {:?} in {:?}", cache_name, duration);
+    /// Path where the parsed contents of one appstream file are cached
+    fn catalog_cache_path(
+        &self,
+        cache_name: &str,
+        path: &str,
+    ) -> Result<PathBuf, AppstreamCacheError> {
+        let file_name = format!("{:x}.bitcode", Sha256::digest(path.as_bytes()));
+        Ok(self
+            .cache_dir(cache_name)?
+            .join(CATALOG_CACHE_DIR)
+            .join(file_name))
     }
 
-    /// Reload from original package sources
-    pub fn load_original(&mut self) {
+    /// Load the parsed contents of one appstream file, if they are cached and up to date
+    fn load_catalog_cache(
+        &self,
+        cache_name: &str,
+        path: &str,
+        tag: &AppstreamCacheTag,
+    ) -> Result<Catalog, AppstreamCacheError> {
+        let cache_path = self.catalog_cache_path(cache_name, path)?;
+        let data = fs::read(&cache_path).map_err(|err| io_error(&cache_path, err))?;
+        let catalog_cache = check_header(&cache_path, &data)
+            .and_then(|data| {
+                bitcode::decode::<CatalogCache>(data).map_err(|err| corrupt(&cache_path, err))
+            })
+            .map_err(|err| {
+                if let AppstreamCacheError::Corrupt(_, _) = err {
+                    quarantine(&cache_path);
+                }
+                err
+            })?;
+        if catalog_cache.path != path || &catalog_cache.tag != tag {
+            return Err(AppstreamCacheError::Outdated("appstream file changed"));
+        }
+        if catalog_cache.locale != self.locale {
+            return Err(AppstreamCacheError::Outdated("locale changed"));
+        }
+        Ok(catalog_cache.catalog)
+    }
+
+    /// Save the parsed contents of one appstream file
+    fn save_catalog_cache(
+        &self,
+        cache_name: &str,
+        path: &str,
+        tag: &AppstreamCacheTag,
+        catalog: Catalog,
+    ) -> (Catalog, Result<(), AppstreamCacheError>) {
+        let cache_path = match self.catalog_cache_path(cache_name, path) {
+            Ok(ok) => ok,
+            Err(err) => return (catalog, Err(err)),
+        };
+        let catalog_cache = CatalogCache {
+            path: path.to_string(),
+            tag: *tag,
+            locale: self.locale.clone(),
+            catalog,
+        };
+        let bitcode = bitcode::encode(&catalog_cache);
+        let res = match cache_path.parent() {
+            Some(parent) => fs::create_dir_all(parent).map_err(|err| io_error(parent, err)),
+            None => Ok(()),
+        }
+        .and_then(|()| write_cache_file(&cache_path, |file| file.write_all(&bitcode)));
+        (catalog_cache.catalog, res)
+    }
+
+    fn clear(&mut self) {
         self.infos.clear();
         self.pkgnames.clear();
+        self.provides.clear();
+        self.details_offsets.clear();
+        self.details_mmap_opt = None;
+        self.stock_icon_paths = Mutex::default();
+    }
+
+    /// Reload from original package sources
+    pub fn load_original(&mut self) {
+        self.clear();
 
         let path_results: Vec<_> = self
             .path_tags
             .par_iter()
-            .filter_map(|(path, _tag)| {
-                let file_name = match Path::new(path).file_name() {
-                    Some(file_name_os) => match file_name_os.to_str() {
-                        Some(some) => some,
-                        None => {
-                            log::error!("failed to convert to UTF-8: {:?}", file_name_os);
-                            return None;
-                        }
-                    },
-                    None => {
-                        log::error!("path has no file name: {:?}", path);
-                        return None;
-                    }
-                };
+            .filter_map(|(path, _tag)| Some((path.clone(), self.parse_path(path)?)))
+            .collect();
 
-                //TODO: memory map?
-                let mut file = match fs::File::open(&path) {
-                    Ok(ok) => ok,
-                    Err(err) => {
-                        log::error!("failed to open {:?}: {}", path, err);
-                        return None;
-                    }
-                };
+        self.apply_catalogs(path_results);
+    }
 
-                if file_name.ends_with(".xml.gz") {
-                    let mut gz = GzDecoder::new(&mut file);
-                    match self.parse_xml(path, &mut gz) {
-                        Ok(infos) => Some(infos),
-                        Err(err) => {
-                            log::error!("failed to parse {:?}: {}", path, err);
-                            None
-                        }
-                    }
-                } else if file_name.ends_with(".yml.gz") {
-                    let mut gz = GzDecoder::new(&mut file);
-                    match self.parse_yaml(path, &mut gz) {
-                        Ok(infos) => Some(infos),
-                        Err(err) => {
-                            log::error!("failed to parse {:?}: {}", path, err);
-                            None
+    /// Reload using the cached contents of appstream files that did not change, and parse
+    /// only the files that did
+    pub fn load_incremental(&mut self, cache_name: &str) {
+        let start = Instant::now();
+        self.clear();
+
+        let path_results: Vec<_> = self
+            .path_tags
+            .par_iter()
+            .filter_map(|(path, tag)| {
+                match self.load_catalog_cache(cache_name, path, tag) {
+                    Ok(catalog) => return Some((path.clone(), catalog, false)),
+                    Err(AppstreamCacheError::Io(_, err))
+                        if err.kind() == io::ErrorKind::NotFound => {}
+                    Err(err) => log::info!("catalog cache for {:?} not used: {}", path, err),
+                }
+                let catalog = self.parse_path(path)?;
+                let (catalog, res) = self.save_catalog_cache(cache_name, path, tag, catalog);
+                if let Err(err) = res {
+                    log::warn!("failed to save catalog cache for {:?}: {}", path, err);
+                }
+                Some((path.clone(), catalog, true))
+            })
+            .collect();
+
+        let parsed = path_results.iter().filter(|x| x.2).count();
+        self.apply_catalogs(
+            path_results
+                .into_iter()
+                .map(|(path, catalog, _parsed)| (path, catalog))
+                .collect(),
+        );
+
+        let duration = start.elapsed();
+        log::info!(
+            "loaded {:?} with {} of {} files parsed in {:?}",
+            cache_name,
+            parsed,
+            self.path_tags.len(),
+            duration
+        );
+    }
+
+    /// Combine parsed appstream files into infos
+    fn apply_catalogs(&mut self, mut path_results: Vec<(String, Catalog)>) {
+        // Apply catalogs from lowest to highest priority, and by path for equal priorities,
+        // so the result does not depend on parsing order
+        path_results.sort_by(|a, b| a.1.priority.cmp(&b.1.priority).then(a.0.cmp(&b.0)));
+
+        for (path, catalog) in path_results.iter_mut() {
+            for (id, info) in catalog.infos.drain(..) {
+                if self.infos.insert(id.clone(), info).is_some() {
+                    log::debug!("replaced duplicate info {:?} from {:?}", id, path);
+                }
+            }
+        }
+
+        // Merge components are applied after all regular components are known
+        for (path, catalog) in path_results.iter_mut() {
+            for (id, merge_kind, merge_info) in catalog.merges.drain(..) {
+                match merge_kind {
+                    MergeKind::RemoveComponent => {
+                        if self.infos.remove(&id).is_none() {
+                            log::debug!("no info {:?} to remove from {:?}", id, path);
                         }
                     }
-                } else if file_name.ends_with(".xml") {
-                    match self.parse_xml(path, &mut file) {
-                        Ok(infos) => Some(infos),
-                        Err(err) => {
-                            log::error!("failed to parse {:?}: {}", path, err);
-                            None
+                    MergeKind::Append | MergeKind::Replace => match self.infos.get_mut(&id) {
+                        Some(info) => {
+                            Arc::make_mut(info)
+                                .merge(&merge_info, merge_kind == MergeKind::Replace);
                         }
-                    }
-                } else if file_name.ends_with(".yml") {
-                    match self.parse_yaml(path, &mut file) {
-                        Ok(infos) => Some(infos),
-                        Err(err) => {
-                            log::error!("failed to parse {:?}: {}", path, err);
-                            None
+                        None => {
+                            log::debug!("no info {:?} to merge from {:?}", id, path);
                         }
-                    }
-                } else {
-                    log::error!("unknown appstream file type: {:?}", path);
-                    None
+                    },
                 }
+            }
+        }
+
+        self.resolve_icons();
+
+        for (id, info) in self.infos.iter() {
+            for pkgname in &info.pkgnames {
+                self.pkgnames
+                    .entry(pkgname.clone())
+                    .or_insert_with(|| HashSet::new())
+                    .insert(id.clone());
+            }
+            for provide in &info.provides {
+                self.provides
+                    .entry(provide.clone())
+                    .or_insert_with(|| HashSet::new())
+                    .insert(id.clone());
+            }
+        }
+    }
+
+    /// Replace cached icons with the paths they resolve to, so that icon lookups done for
+    /// every search result need no file system access. Icons without files are removed.
+    fn resolve_icons(&mut self) {
+        let start = Instant::now();
+        let resolved: Vec<_> = self
+            .infos
+            .par_iter()
+            .filter_map(|(id, info)| {
+                if !info
+                    .icons
+                    .iter()
+                    .any(|x| matches!(x, AppIcon::Cached(..) | AppIcon::Local(..)))
+                {
+                    return None;
+                }
+                let icons = info
+                    .icons
+                    .iter()
+                    .filter_map(|info_icon| match info_icon {
+                        AppIcon::Cached(name, width, height, scale) => {
+                            let icon_path = self.icon_path(
+                                info.origin_opt.as_deref(),
+                                name,
+                                *width,
+                                *height,
+                                *scale,
+                            )?;
+                            Some(AppIcon::Local(
+                                icon_path.to_str()?.to_string(),
+                                *width,
+                                *height,
+                                *scale,
+                            ))
+                        }
+                        AppIcon::Local(path, _width, _height, _scale) => {
+                            if Path::new(path).is_file() {
+                                Some(info_icon.clone())
+                            } else {
+                                None
+                            }
+                        }
+                        _ => Some(info_icon.clone()),
+                    })
+                    .collect();
+                Some((id.clone(), icons))
             })
             .collect();
+        for (id, icons) in resolved {
+            if let Some(info) = self.infos.get_mut(&id) {
+                Arc::make_mut(info).icons = icons;
+            }
+        }
+        let duration = start.elapsed();
+        log::info!("resolved icons of {:?} in {:?}", self.source_id, duration);
+    }
 
-        for infos in path_results {
-            for (id, info) in infos {
-                for pkgname in &info.pkgnames {
-                    self.pkgnames
-                        .entry(pkgname.clone())
-                        .or_insert_with(|| HashSet::new())
-                        .insert(id.clone());
-                }
-                match self.infos.insert(id.clone(), info) {
-                    Some(_old) => {
-                        //TODO: merge based on priority
-                        log::debug!("found duplicate info {:?}", id);
-                    }
-                    None => {}
+    /// Directories to watch for changes to the appstream files. Parents of the files are
+    /// watched for added or replaced files, and their parents for replaced directories, as
+    /// when a flatpak remote switches to a new appstream commit.
+    pub fn watch_paths(&self) -> Vec<PathBuf> {
+        let mut watch_paths = Vec::new();
+        for path in self.path_tags.keys() {
+            for dir in Path::new(path).ancestors().skip(1).take(2) {
+                if dir.is_dir() && !watch_paths.iter().any(|x| x == dir) {
+                    watch_paths.push(dir.to_path_buf());
                 }
             }
         }
+        watch_paths
     }
 
     /// Either load from cache or load from originals. Cache is cleaned before loading and saved after.
     pub fn reload(&mut self) {
         let source_id = self.source_id.clone();
-        self.clean_cache(&source_id);
-        if !self.load_cache(&source_id) {
-            self.load_original();
-            self.save_cache(&source_id);
+        if let Err(err) = self.clean_cache(&source_id) {
+            log::warn!("failed to clean cache {:?}: {}", source_id, err);
+        }
+        match self.load_cache(&source_id) {
+            Ok(()) => {
+                self.status = AppstreamCacheStatus::Hit;
+            }
+            Err(err) => {
+                if err.is_rebuild() {
+                    log::warn!("rebuilding cache {:?}: {}", source_id, err);
+                } else {
+                    log::info!("cache {:?} needs refresh: {}", source_id, err);
+                }
+                self.status = AppstreamCacheStatus::Miss(err.to_string());
+                self.load_incremental(&source_id);
+                if let Err(err) = self.save_cache(&source_id) {
+                    log::warn!("failed to save cache {:?}: {}", source_id, err);
+                }
+            }
+        }
+    }
+
+    fn details_data(&self, start: u64, len: u64) -> Option<&[u8]> {
+        let mmap = self.details_mmap_opt.as_ref()?;
+        let start = usize::try_from(start).ok()?;
+        let end = start.checked_add(usize::try_from(len).ok()?)?;
+        mmap.get(start..end)
+    }
+
+    /// IDs of apps providing an item, like a media type or binary
+    pub fn provided_by(&self, provide: &AppProvide) -> impl Iterator<Item = &AppId> {
+        self.provides.get(provide).into_iter().flatten()
+    }
+
+    /// ID an app was renamed to, if the ID is not known but another app provides it
+    pub fn renamed_id(&self, id: &AppId) -> Option<&AppId> {
+        if self.infos.contains_key(id) {
+            return None;
         }
+        // Provided IDs are compared like app IDs, ignoring the desktop suffix
+        self.provides
+            .iter()
+            .find_map(|(provide, ids)| match provide {
+                AppProvide::Id(provided) if AppId::new(provided) == *id => ids.iter().next(),
+                _ => None,
+            })
+    }
+
+    pub fn description<'a>(&'a self, id: &AppId, info: &'a AppInfo) -> Cow<'a, str> {
+        if !info.description.is_empty() {
+            return Cow::Owned(info.description.to_string());
+        }
+        self.details_offsets
+            .get(id)
+            .and_then(|offsets| {
+                self.details_data(offsets.description_start, offsets.description_len)
+            })
+            .and_then(|data| str::from_utf8(data).ok())
+            .map_or(Cow::Borrowed(""), Cow::Borrowed)
+    }
+
+    /// Info with details filled in, if it was loaded from the cache without them
+    pub fn details(&self, id: &AppId, info: &Arc<AppInfo>) -> Arc<AppInfo> {
+        let Some(offsets) = self.details_offsets.get(id) else {
+            return info.clone();
+        };
+        let app_details = match self
+            .details_data(offsets.details_start, offsets.details_len)
+            .map(|data| bitcode::decode::<AppDetails>(data))
+        {
+            Some(Ok(ok)) => ok,
+            Some(Err(err)) => {
+                log::warn!("failed to decode details of {:?}: {}", id, err);
+                return info.clone();
+            }
+            None => {
+                log::warn!("failed to find details of {:?}", id);
+                return info.clone();
+            }
+        };
+        let mut info = (**info).clone();
+        info.description = app_details.description;
+        for (release, description) in info
+            .releases
+            .iter_mut()
+            .zip(app_details.release_descriptions)
+        {
+            release.description = description;
+        }
+        info.screenshots = app_details.screenshots;
+        Arc::new(info)
     }
 
     pub fn icon_path(
@@ -457,172 +1080,249 @@ impl AppstreamCache {
     ) -> Option<PathBuf> {
         //TODO: what to do with no origin?
         let origin = origin_opt?;
-        //TODO: what to do with no width or height?
-        let width = width_opt?;
-        let height = height_opt?;
-        let size = match scale_opt {
-            //TODO: should a scale of 0 or 1 not add @scale?
-            Some(scale) => format!("{}x{}@{}", width, height, scale),
-            None => format!("{}x{}", width, height),
+        // Icons are square, so a missing dimension is taken from the other one. If both are
+        // missing, the sizes that catalogs usually provide are tried from largest to smallest.
+        let sizes: Vec<(u32, u32)> = match (width_opt, height_opt) {
+            (Some(width), Some(height)) => vec![(width, height)],
+            (Some(size), None) | (None, Some(size)) => vec![(size, size)],
+            (None, None) => CACHED_ICON_SIZES
+                .iter()
+                .map(|size| (*size, *size))
+                .collect(),
         };
-
-        for icons_path in self.icons_paths.iter() {
-            let icon_path = Path::new(icons_path).join(origin).join(&size).join(name);
-            if icon_path.is_file() {
-                return Some(icon_path);
-            }
-        }
-
         //TODO: smarter removal of .desktop
         let fallback_name = name.replace(".desktop", "");
-        for icons_path in self.icons_paths.iter() {
-            let icon_path = Path::new(icons_path)
-                .join(origin)
-                .join(&size)
-                .join(&fallback_name);
-            if icon_path.is_file() {
-                return Some(icon_path);
+
+        for (width, height) in sizes {
+            let size = match scale_opt {
+                // Icons for a scale of 1 are stored without a suffix
+                Some(scale) if scale > 1 => format!("{}x{}@{}", width, height, scale),
+                _ => format!("{}x{}", width, height),
+            };
+
+            for file_name in [name, fallback_name.as_str()] {
+                for icons_path in self.icons_paths.iter() {
+                    let icon_path = Path::new(icons_path)
+                        .join(origin)
+                        .join(&size)
+                        .join(file_name);
+                    if icon_path.is_file() {
+                        return Some(icon_path);
+                    }
+                }
             }
         }
 
         None
     }
 
+    /// Path of a stock icon from the theme, memoized so that icon lookups done for every
+    /// search result need no file system access after the first
+    fn stock_icon_path(&self, name: &str, size: u16) -> Option<PathBuf> {
+        let key = (name.to_string(), size);
+        if let Some(path_opt) = self.stock_icon_paths.lock().unwrap().get(&key) {
+            return path_opt.clone();
+        }
+        // The theme is searched without holding the lock
+        let path_opt = widget::icon::from_name(name).size(size).path();
+        self.stock_icon_paths
+            .lock()
+            .unwrap()
+            .insert(key, path_opt.clone());
+        path_opt
+    }
+
+    /// Icon sized for the details page
     pub fn icon(&self, info: &AppInfo) -> widget::icon::Handle {
-        let mut icon_opt = None;
-        let mut cached_size = 0;
-        for info_icon in info.icons.iter() {
-            //TODO: support other types of icons
-            match info_icon {
-                AppIcon::Cached(name, width, height, scale) => {
-                    let size = cmp::min(width.unwrap_or(0), height.unwrap_or(0));
-                    if size < cached_size {
-                        // Skip if size is less than cached size
-                        continue;
-                    }
-                    if let Some(icon_path) =
-                        self.icon_path(info.origin_opt.as_deref(), name, *width, *height, *scale)
-                    {
-                        icon_opt = Some(widget::icon::from_path(icon_path));
-                        cached_size = size;
+        self.icon_sized(info, 128, 1.0)
+    }
+
+    /// Icon that best fits a logical size at the specified output scale
+    pub fn icon_sized(&self, info: &AppInfo, size: u16, scale: f32) -> widget::icon::Handle {
+        let scale = scale.max(1.0).ceil() as u32;
+        let target = u32::from(size) * scale;
+
+        // Rank icons by fit: the smallest icon at least as large as the target, then stock
+        // icons that the theme scales, then the largest icon that is too small. Icons made
+        // for the output scale are preferred between icons of the same size.
+        let mut ranked: Vec<_> = info
+            .icons
+            .iter()
+            .map(|info_icon| {
+                let (class, rank, icon_scale) = match info_icon {
+                    AppIcon::Stock(_) => (1, 0, scale),
+                    AppIcon::Cached(_, width, height, icon_scale)
+                    | AppIcon::Remote(_, width, height, icon_scale)
+                    | AppIcon::Local(_, width, height, icon_scale) => {
+                        let icon_scale = icon_scale.unwrap_or(1).max(1);
+                        let logical = match (width, height) {
+                            (Some(width), Some(height)) => cmp::min(*width, *height),
+                            (Some(size), None) | (None, Some(size)) => *size,
+                            (None, None) => 0,
+                        };
+                        let physical = logical * icon_scale;
+                        if physical >= target {
+                            (0, physical, icon_scale)
+                        } else {
+                            (2, u32::MAX - physical, icon_scale)
+                        }
                     }
-                }
+                };
+                ((class, rank, icon_scale != scale), info_icon)
+            })
+            .collect();
+        ranked.sort_by_key(|(key, _)| *key);
+
+        for (_key, info_icon) in ranked {
+            let icon_path_opt = match info_icon {
+                AppIcon::Cached(name, width, height, icon_scale) => self.icon_path(
+                    info.origin_opt.as_deref(),
+                    name,
+                    *width,
+                    *height,
+                    *icon_scale,
+                ),
                 AppIcon::Stock(stock) => {
-                    if cached_size != 0 {
-                        // Skip if a cached icon was found
-                        continue;
-                    }
-                    if let Some(icon_path) = widget::icon::from_name(stock.clone()).size(128).path()
-                    {
-                        icon_opt = Some(widget::icon::from_path(icon_path));
-                    }
-                }
-                AppIcon::Remote(_url, _width, _height, _scale) => {
-                    //TODO
-                }
-                AppIcon::Local(path, width, height, _scale) => {
-                    let size = cmp::min(width.unwrap_or(0), height.unwrap_or(0));
-                    if size < cached_size {
-                        // Skip if size is less than cached size
-                        continue;
-                    }
-                    let icon_path = Path::new(path);
-                    if icon_path.is_file() {
-                        icon_opt = Some(widget::icon::from_path(icon_path.to_path_buf()));
-                        cached_size = size;
-                    }
+                    self.stock_icon_path(stock, cmp::min(target, u32::from(u16::MAX)) as u16)
                 }
+                // Remote icons are downloaded by the app, see remote_icon::fetch
+                AppIcon::Remote(url, _width, _height, _scale) => remote_icon::cached(url),
+                // Local icons are checked when loaded, see resolve_icons
+                AppIcon::Local(path, _width, _height, _scale) => Some(PathBuf::from(path)),
+            };
+            if let Some(icon_path) = icon_path_opt {
+                return widget::icon::from_path(icon_path);
             }
         }
-        icon_opt.unwrap_or_else(|| {
-            log::debug!("failed to get icon from {:?}", info.icons);
-            widget::icon::from_name("package-x-generic")
-                .size(128)
-                .handle()
-        })
+
+        log::debug!("failed to get icon from {:?}", info.icons);
+        widget::icon::from_name("package-x-generic")
+            .size(size)
+            .handle()
     }
 
-    fn parse_xml<P: AsRef<Path>, R: Read>(
+    fn parse_xml<P: AsRef<Path>, R: Read + Send>(
         &self,
         path: P,
         reader: R,
-    ) -> Result<Vec<(AppId, Arc<AppInfo>)>, Box<dyn Error>> {
+    ) -> Result<Catalog, Box<dyn Error>> {
         let start = Instant::now();
         let path = path.as_ref();
-        //TODO: just running this and not saving the results makes a huge memory leak!
-        let e = xmltree::Element::parse(reader)?;
-        let _version = e
+        // Components are streamed so the whole document is never in memory
+        let mut component_reader = ComponentReader::new(BufReader::new(reader))?;
+        let _version = component_reader
             .attributes
             .get("version")
             .ok_or_else(|| ParseError::missing_attribute("version", "collection"))?;
-        let origin_opt = e.attributes.get("origin");
-        let _arch_opt = e.attributes.get("architecture");
-        let infos: Vec<_> = e
-            .children
-            .par_iter()
-            .filter_map(|node| {
-                if let xmltree::XMLNode::Element(ref e) = node {
-                    if &*e.name == "component" {
-                        match Component::try_from(e) {
-                            Ok(component) => {
-                                if component.kind != ComponentKind::DesktopApplication {
-                                    // Skip anything that is not a desktop application
-                                    //TODO: should we allow more components?
-                                    return None;
-                                }
-
-                                let id = AppId::new(&component.id.0);
-                                let monthly_downloads = stats::monthly_downloads(&id).unwrap_or(0);
-                                return Some((
-                                    id,
-                                    Arc::new(AppInfo::new(
-                                        &self.source_id,
-                                        &self.source_name,
-                                        origin_opt.map(|x| x.as_str()),
-                                        component,
-                                        &self.locale,
-                                        monthly_downloads,
-                                    )),
-                                ));
+        let origin_opt = component_reader.attributes.get("origin").cloned();
+        let _arch_opt = component_reader.attributes.get("architecture");
+        let priority = match component_reader.attributes.get("priority") {
+            Some(priority) => priority.parse().unwrap_or_else(|err| {
+                log::warn!(
+                    "invalid priority {:?} in {:?}, using 0: {}",
+                    priority,
+                    path,
+                    err
+                );
+                0
+            }),
+            None => 0,
+        };
+        let mut results: Vec<_> = (&mut component_reader)
+            .enumerate()
+            .par_bridge()
+            .filter_map(|(i, mut e)| {
+                if &*e.name == "component" {
+                    let merge_kind_opt =
+                        e.attributes.get("merge").and_then(|x| MergeKind::parse(x));
+                    // Merge components usually only have the fields they change
+                    let missing_name = merge_kind_opt.is_some() && e.get_child("name").is_none();
+                    if missing_name {
+                        let mut name = xmltree::Element::new("name");
+                        name.children
+                            .push(xmltree::XMLNode::Text(String::from("-")));
+                        e.children.push(xmltree::XMLNode::Element(name));
+                    }
+                    match Component::try_from(&e) {
+                        Ok(component) => {
+                            if merge_kind_opt.is_none()
+                                && AppKind::from_component_kind(&component.kind).is_none()
+                            {
+                                // Skip generic components and other kinds that are not shown
+                                return None;
                             }
-                            Err(err) => {
-                                log::error!(
-                                    "failed to parse {:?} in {:?}: {}",
-                                    e.get_child("id")
-                                        .and_then(|x| appstream::AppId::try_from(x).ok()),
-                                    path,
-                                    err
-                                );
+
+                            let id = AppId::new(&component.id.0);
+                            let monthly_downloads = stats::monthly_downloads(&id).unwrap_or(0);
+                            let mut info = AppInfo::new(
+                                &self.source_id,
+                                &self.source_name,
+                                origin_opt.as_deref(),
+                                component,
+                                &self.locale,
+                                monthly_downloads,
+                            );
+                            info.localize_descriptions(&e, &self.locale);
+                            info.keywords = app_info::keywords_from_element(&e, &self.locale);
+                            info.provides = AppProvide::from_element(&e);
+                            if missing_name {
+                                info.name.clear();
                             }
+                            return Some((i, id, merge_kind_opt, Arc::new(info)));
+                        }
+                        Err(err) => {
+                            log::error!(
+                                "failed to parse {:?} in {:?}: {}",
+                                e.get_child("id")
+                                    .and_then(|x| appstream::AppId::try_from(x).ok()),
+                                path,
+                                err
+                            );
                         }
                     }
                 }
                 None
             })
             .collect();
+        if let Some(err) = component_reader.take_error() {
+            return Err(err.into());
+        }
+        // Restore document order so duplicates within a file resolve deterministically
+        results.sort_by_key(|x| x.0);
+        let mut catalog = Catalog {
+            priority,
+            ..Default::default()
+        };
+        for (_i, id, merge_kind_opt, info) in results {
+            match merge_kind_opt {
+                Some(merge_kind) => catalog.merges.push((id, merge_kind, info)),
+                None => catalog.infos.push((id, info)),
+            }
+        }
         let duration = start.elapsed();
         log::info!(
-            "loaded {} items from {:?} in {:?}",
-            infos.len(),
+            "loaded {} items and {} merges from {:?} in {:?}",
+            catalog.infos.len(),
+            catalog.merges.len(),
             path,
             duration
         );
-        Ok(infos)
+        Ok(catalog)
     }
 
     fn parse_yaml<P: AsRef<Path>, R: Read>(
         &self,
         path: P,
         reader: R,
-    ) -> Result<Vec<(AppId, Arc<AppInfo>)>, Box<dyn Error>> {
+    ) -> Result<Catalog, Box<dyn Error>> {
         let start = Instant::now();
         let path = path.as_ref();
         let mut origin_opt = None;
         let mut media_base_url_opt = None;
-        let mut infos = Vec::new();
+        let mut catalog = Catalog::default();
         //TODO: par_iter?
         for (doc_i, doc) in serde_yaml::Deserializer::from_reader(reader).enumerate() {
-            let value = match serde_yaml::Value::deserialize(doc) {
+            let mut value = match serde_yaml::Value::deserialize(doc) {
                 Ok(ok) => ok,
                 Err(err) => {
                     log::error!("failed to parse document {} in {:?}: {}", doc_i, path, err);
@@ -632,12 +1332,27 @@ impl AppstreamCache {
             if doc_i == 0 {
                 origin_opt = value["Origin"].as_str().map(|x| x.to_string());
                 media_base_url_opt = value["MediaBaseUrl"].as_str().map(|x| x.to_string());
+                catalog.priority = value["Priority"]
+                    .as_i64()
+                    .and_then(|x| x.try_into().ok())
+                    .unwrap_or(0);
             } else {
+                let merge_kind_opt = value["Merge"].as_str().and_then(MergeKind::parse);
+                // Merge components usually only have the fields they change
+                let missing_name = merge_kind_opt.is_some() && value.get("Name").is_none();
+                if missing_name {
+                    if let Some(mapping) = value.as_mapping_mut() {
+                        let mut name = serde_yaml::Mapping::new();
+                        name.insert("C".into(), "-".into());
+                        mapping.insert("Name".into(), name.into());
+                    }
+                }
                 match Component::deserialize(&value) {
                     Ok(mut component) => {
-                        if component.kind != ComponentKind::DesktopApplication {
-                            // Skip anything that is not a desktop application
-                            //TODO: should we allow more components?
+                        if merge_kind_opt.is_none()
+                            && AppKind::from_component_kind(&component.kind).is_none()
+                        {
+                            // Skip generic components and other kinds that are not shown
                             continue;
                         }
 
@@ -685,15 +1400,68 @@ impl AppstreamCache {
                                             );
                                         }
                                     },
-                                    Some("remote") => {
-                                        // For now we just ignore remote icons
-                                        log::debug!(
-                                            "ignoring remote icons {:?} for {:?} in {:?}",
-                                            icon,
-                                            component.id,
-                                            path
-                                        );
-                                    }
+                                    Some("remote") => match icon.as_sequence() {
+                                        Some(sequence) => {
+                                            for remote in sequence {
+                                                let url_str = match remote["url"].as_str() {
+                                                    Some(some) => some,
+                                                    None => {
+                                                        log::warn!(
+                                                        "unsupported remote icon {:?} for {:?} in {:?}",
+                                                        remote,
+                                                        component.id,
+                                                        path
+                                                    );
+                                                        continue;
+                                                    }
+                                                };
+                                                // Relative URLs are below the media base URL
+                                                let url_res = match &media_base_url_opt {
+                                                    Some(media_base_url)
+                                                        if Url::parse(url_str).is_err() =>
+                                                    {
+                                                        Url::parse(&format!(
+                                                            "{media_base_url}/{url_str}"
+                                                        ))
+                                                    }
+                                                    _ => Url::parse(url_str),
+                                                };
+                                                match url_res {
+                                                    Ok(url) => {
+                                                        component.icons.push(Icon::Remote {
+                                                            url,
+                                                            width: remote["width"]
+                                                                .as_u64()
+                                                                .and_then(|x| x.try_into().ok()),
+                                                            height: remote["height"]
+                                                                .as_u64()
+                                                                .and_then(|x| x.try_into().ok()),
+                                                            scale: remote["scale"]
+                                                                .as_u64()
+                                                                .and_then(|x| x.try_into().ok()),
+                                                        });
+                                                    }
+                                                    Err(err) => {
+                                                        log::warn!(
+                                                        "failed to parse remote icon {:?} for {:?} in {:?}: {}",
+                                                        url_str,
+                                                        component.id,
+                                                        path,
+                                                        err
+                                                    );
+                                                    }
+                                                }
+                                            }
+                                        }
+                                        None => {
+                                            log::warn!(
+                                                "unsupported remote icons {:?} for {:?} in {:?}",
+                                                icon,
+                                                component.id,
+                                                path
+                                            );
+                                        }
+                                    },
                                     Some("stock") => match icon.as_str() {
                                         Some(stock) => {
                                             component.icons.push(Icon::Stock(stock.to_string()));
@@ -881,17 +1649,39 @@ impl AppstreamCache {
 
                         let id = AppId::new(&component.id.0);
                         let monthly_downloads = stats::monthly_downloads(&id).unwrap_or(0);
-                        infos.push((
-                            id,
-                            Arc::new(AppInfo::new(
-                                &self.source_id,
-                                &self.source_name,
-                                origin_opt.as_deref(),
-                                component,
-                                &self.locale,
-                                monthly_downloads,
-                            )),
-                        ));
+                        let mut info = AppInfo::new(
+                            &self.source_id,
+                            &self.source_name,
+                            origin_opt.as_deref(),
+                            component,
+                            &self.locale,
+                            monthly_downloads,
+                        );
+                        //TODO: move to appstream crate
+                        if let Some(content_rating) =
+                            ContentRating::from_yaml(&value["ContentRating"])
+                        {
+                            info.content_rating_opt = Some(content_rating);
+                        }
+                        info.keywords =
+                            app_info::keywords_from_yaml(&value["Keywords"], &self.locale);
+                        info.provides = AppProvide::from_yaml(&value["Provides"]);
+                        if info.urls.is_empty() {
+                            info.urls = AppUrl::from_yaml(&value["Url"]);
+                        }
+                        if info.project_license_opt.is_none() {
+                            info.project_license_opt =
+                                value["ProjectLicense"].as_str().map(|x| x.to_string());
+                        }
+                        if missing_name {
+                            info.name.clear();
+                        }
+                        match merge_kind_opt {
+                            Some(merge_kind) => {
+                                catalog.merges.push((id, merge_kind, Arc::new(info)))
+                            }
+                            None => catalog.infos.push((id, Arc::new(info))),
+                        }
                     }
                     Err(err) => {
                         log::error!("failed to parse {:?} in {:?}: {}", value["ID"], path, err);
@@ -901,11 +1691,276 @@ impl AppstreamCache {
         }
         let duration = start.elapsed();
         log::info!(
-            "loaded {} items from {:?} in {:?}",
-            infos.len(),
+            "loaded {} items and {} merges from {:?} in {:?}",
+            catalog.infos.len(),
+            catalog.merges.len(),
             path,
             duration
         );
-        Ok(infos)
+        Ok(catalog)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, process};
+
+    /// Empty directory for the appstream files and caches of a test
+    fn test_dir(name: &str) -> PathBuf {
+        let dir =
+            env::temp_dir().join(format!("cosmic-store-appstream-{}-{}", process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn write_catalog(path: &Path, attributes: &str, components: &[&str]) {
+        let mut xml = format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<components version=\"0.16\" origin=\"test\"{}>\n",
+            attributes
+        );
+        for component in components {
+            xml.push_str(component);
+            xml.push('\n');
+        }
+        xml.push_str("</components>\n");
+        fs::write(path, xml).unwrap();
+    }
+
+    fn component(id: &str, summary: &str) -> String {
+        format!(
+            "<component type=\"desktop-application\"><id>{}</id><name>{}</name><summary>{}</summary></component>",
+            id, id, summary
+        )
+    }
+
+    fn new_cache(dir: &Path, file_names: &[&str]) -> AppstreamCache {
+        let mut cache = AppstreamCache::new(
+            "test".to_string(),
+            "Test".to_string(),
+            file_names.iter().map(|x| dir.join(x)).collect(),
+            Vec::new(),
+            "en",
+        );
+        cache.cache_root_opt = Some(dir.join("cache"));
+        cache
+    }
+
+    fn summary<'a>(cache: &'a AppstreamCache, id: &str) -> Option<&'a str> {
+        cache
+            .infos
+            .get(&AppId::new(id))
+            .map(|info| info.summary.as_str())
+    }
+
+    #[test]
+    fn priority_overrides_path_order() {
+        let dir = test_dir("priority");
+        write_catalog(
+            &dir.join("a.xml"),
+            " priority=\"10\"",
+            &[&component("org.example.App", "High priority")],
+        );
+        write_catalog(
+            &dir.join("b.xml"),
+            "",
+            &[
+                &component("org.example.App", "Low priority"),
+                &component("org.example.Other", "First path"),
+            ],
+        );
+        write_catalog(
+            &dir.join("c.xml"),
+            "",
+            &[&component("org.example.Other", "Last path")],
+        );
+        let mut cache = new_cache(&dir, &["c.xml", "b.xml", "a.xml"]);
+        cache.load_original();
+        assert_eq!(summary(&cache, "org.example.App"), Some("High priority"));
+        // Equal priorities are applied by path
+        assert_eq!(summary(&cache, "org.example.Other"), Some("Last path"));
+    }
+
+    #[test]
+    fn merge_components() {
+        let dir = test_dir("merge");
+        // Merges are applied after all regular components, even from files sorted before them
+        write_catalog(
+            &dir.join("a-merges.xml"),
+            "",
+            &[
+                "<component merge=\"append\"><id>org.example.App</id><summary>Ignored</summary><developer_name>Appended</developer_name><keywords><keyword>appended</keyword></keywords></component>",
+                "<component merge=\"replace\"><id>org.example.Other</id><summary>Replaced</summary><keywords><keyword>replaced</keyword></keywords></component>",
+                "<component merge=\"remove-component\"><id>org.example.Removed</id></component>",
+                "<component merge=\"append\"><id>org.example.Missing</id><summary>Missing</summary></component>",
+            ],
+        );
+        write_catalog(
+            &dir.join("b.xml"),
+            "",
+            &[
+                "<component type=\"desktop-application\"><id>org.example.App</id><name>App</name><summary>Original</summary><keywords><keyword>original</keyword></keywords></component>",
+                "<component type=\"desktop-application\"><id>org.example.Other</id><name>Other</name><summary>Original</summary><developer_name>Kept</developer_name><keywords><keyword>original</keyword></keywords></component>",
+                &component("org.example.Removed", "Removed"),
+            ],
+        );
+        let mut cache = new_cache(&dir, &["a-merges.xml", "b.xml"]);
+        cache.load_original();
+
+        // Append only sets empty fields and extends lists
+        let info = &cache.infos[&AppId::new("org.example.App")];
+        assert_eq!(info.name, "App");
+        assert_eq!(info.summary, "Original");
+        assert_eq!(info.developer_name, "Appended");
+        assert_eq!(info.keywords, ["original", "appended"]);
+
+        // Replace overwrites the fields it sets, and keeps the rest
+        let info = &cache.infos[&AppId::new("org.example.Other")];
+        assert_eq!(info.name, "Other");
+        assert_eq!(info.summary, "Replaced");
+        assert_eq!(info.developer_name, "Kept");
+        assert_eq!(info.keywords, ["replaced"]);
+
+        assert_eq!(summary(&cache, "org.example.Removed"), None);
+        assert_eq!(summary(&cache, "org.example.Missing"), None);
+        assert_eq!(cache.infos.len(), 2);
+    }
+
+    #[test]
+    fn incremental_parses_changed_files() {
+        let dir = test_dir("incremental");
+        let path_a = dir.join("a.xml");
+        let path_b = dir.join("b.xml");
+        write_catalog(&path_a, "", &[&component("org.example.App", "Cached A")]);
+        write_catalog(&path_b, "", &[&component("org.example.Other", "Before")]);
+        let mut cache = new_cache(&dir, &["a.xml", "b.xml"]);
+        cache.load_incremental("test");
+        assert_eq!(summary(&cache, "org.example.App"), Some("Cached A"));
+        assert_eq!(summary(&cache, "org.example.Other"), Some("Before"));
+
+        // Edit a.xml keeping its size and modified time, so its tag matches and the catalog
+        // cache is used instead of parsing it again
+        let modified = fs::metadata(&path_a).unwrap().modified().unwrap();
+        write_catalog(&path_a, "", &[&component("org.example.App", "Edited A")]);
+        fs::File::options()
+            .write(true)
+            .open(&path_a)
+            .unwrap()
+            .set_modified(modified)
+            .unwrap();
+        write_catalog(
+            &path_b,
+            "",
+            &[&component("org.example.Other", "After change")],
+        );
+
+        let mut cache = new_cache(&dir, &["a.xml", "b.xml"]);
+        cache.load_incremental("test");
+        assert_eq!(summary(&cache, "org.example.App"), Some("Cached A"));
+        assert_eq!(summary(&cache, "org.example.Other"), Some("After change"));
+    }
+
+    #[test]
+    fn corrupt_cache_is_quarantined() {
+        let dir = test_dir("corrupt");
+        write_catalog(
+            &dir.join("a.xml"),
+            "",
+            &[&component("org.example.App", "Summary")],
+        );
+        let mut cache = new_cache(&dir, &["a.xml"]);
+        let cache_path = cache
+            .cache_dir("test")
+            .unwrap()
+            .join(AppstreamCache::cache_filename());
+        let quarantine_path = cache_path.with_extension(QUARANTINE_EXTENSION);
+        fs::create_dir_all(cache_path.parent().unwrap()).unwrap();
+
+        let mut truncated = CACHE_MAGIC.to_vec();
+        truncated.extend_from_slice(&CACHE_VERSION.to_le_bytes());
+        truncated.extend_from_slice(&[0; 3]);
+        for data in [&b"CS"[..], &b"XXXX\0\0\0\0"[..], &truncated[..]] {
+            fs::write(&cache_path, data).unwrap();
+            match cache.load_cache("test") {
+                Err(AppstreamCacheError::Corrupt(path, _)) => assert_eq!(path, cache_path),
+                res => panic!("unexpected result for {:?}: {:?}", data, res),
+            }
+            assert!(!cache_path.exists());
+            assert!(quarantine_path.is_file());
+        }
+
+        // Other versions are rebuilt without quarantining
+        fs::remove_file(&quarantine_path).unwrap();
+        let mut other_version = CACHE_MAGIC.to_vec();
+        other_version.extend_from_slice(&(CACHE_VERSION + 1).to_le_bytes());
+        fs::write(&cache_path, &other_version).unwrap();
+        match cache.load_cache("test") {
+            Err(AppstreamCacheError::Version(_, version)) => {
+                assert_eq!(version, CACHE_VERSION + 1)
+            }
+            res => panic!("unexpected result: {:?}", res),
+        }
+        assert!(cache_path.is_file());
+        assert!(!quarantine_path.exists());
+
+        // The cache is rebuilt and used by the next load
+        cache.reload();
+        assert!(matches!(cache.status, AppstreamCacheStatus::Miss(_)));
+        let mut cache = new_cache(&dir, &["a.xml"]);
+        cache.reload();
+        assert_eq!(cache.status, AppstreamCacheStatus::Hit);
+        assert_eq!(summary(&cache, "org.example.App"), Some("Summary"));
+    }
+
+    #[test]
+    fn yaml_remote_icons() {
+        let dir = test_dir("yaml-remote-icons");
+        fs::write(
+            dir.join("a.yml"),
+            concat!(
+                "---\n",
+                "File: DEP-11\n",
+                "Version: '0.16'\n",
+                "Origin: test\n",
+                "MediaBaseUrl: https://media.example.org\n",
+                "---\n",
+                "Type: desktop-application\n",
+                "ID: org.example.App\n",
+                "Name:\n",
+                "  C: App\n",
+                "Summary:\n",
+                "  C: Summary\n",
+                "Icon:\n",
+                "  remote:\n",
+                "  - url: org/example/App/64x64.png\n",
+                "    width: 64\n",
+                "    height: 64\n",
+                "  - url: https://example.org/icon-128.png\n",
+                "    width: 128\n",
+                "    height: 128\n",
+                "    scale: 2\n",
+            ),
+        )
+        .unwrap();
+        let mut cache = new_cache(&dir, &["a.yml"]);
+        cache.load_original();
+        assert_eq!(
+            cache.infos[&AppId::new("org.example.App")].icons,
+            [
+                AppIcon::Remote(
+                    "https://media.example.org/org/example/App/64x64.png".to_string(),
+                    Some(64),
+                    Some(64),
+                    None
+                ),
+                AppIcon::Remote(
+                    "https://example.org/icon-128.png".to_string(),
+                    Some(128),
+                    Some(128),
+                    Some(2)
+                ),
+            ]
+        );
     }
 }
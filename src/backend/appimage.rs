@@ -0,0 +1,1212 @@
+use appstream::{xmltree, Component};
+use cosmic::widget;
+use sha1::{Digest, Sha1};
+use std::{
+    collections::HashMap,
+    error::Error,
+    fmt::Write as _,
+    fs,
+    io::{self, Read, Seek, SeekFrom, Write},
+    os::unix::{ffi::OsStrExt, fs::PermissionsExt},
+    path::{Path, PathBuf},
+    process,
+    sync::{Arc, Mutex},
+    time::SystemTime,
+};
+
+use super::{Backend, BackendError, Capabilities, Package};
+use crate::{app_info, AppIcon, AppId, AppInfo, AppKind, AppstreamCache, OperationKind, RichText};
+
+const SOURCE_ID: &'static str = "appimage";
+//TODO: translate
+const SOURCE_NAME: &'static str = "AppImage";
+
+/// Files extracted from the squashfs image to read metadata
+const METADATA_PATTERNS: &'static [&'static str] = &[
+    "*.desktop",
+    ".DirIcon",
+    "*.png",
+    "*.svg",
+    "usr/share/metainfo",
+    "usr/share/appdata",
+];
+
+fn is_appimage(path: &Path) -> bool {
+    path.is_file()
+        && path
+            .extension()
+            .and_then(|x| x.to_str())
+            .map_or(false, |x| x.eq_ignore_ascii_case("appimage"))
+}
+
+fn read_at(file: &mut fs::File, offset: u64, size: u64) -> io::Result<Vec<u8>> {
+    // Sections used here are small, this protects against corrupt headers
+    if size > 16 * 1024 * 1024 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("refusing to read {} bytes", size),
+        ));
+    }
+    let mut data = vec![0; size as usize];
+    file.seek(SeekFrom::Start(offset))?;
+    file.read_exact(&mut data)?;
+    Ok(data)
+}
+
+struct Endian {
+    little: bool,
+}
+
+impl Endian {
+    fn u16(&self, data: &[u8], offset: usize) -> u16 {
+        let bytes = data[offset..offset + 2].try_into().unwrap();
+        if self.little {
+            u16::from_le_bytes(bytes)
+        } else {
+            u16::from_be_bytes(bytes)
+        }
+    }
+
+    fn u32(&self, data: &[u8], offset: usize) -> u32 {
+        let bytes = data[offset..offset + 4].try_into().unwrap();
+        if self.little {
+            u32::from_le_bytes(bytes)
+        } else {
+            u32::from_be_bytes(bytes)
+        }
+    }
+
+    fn u64(&self, data: &[u8], offset: usize) -> u64 {
+        let bytes = data[offset..offset + 8].try_into().unwrap();
+        if self.little {
+            u64::from_le_bytes(bytes)
+        } else {
+            u64::from_be_bytes(bytes)
+        }
+    }
+}
+
+struct ElfInfo {
+    /// Offset of the squashfs image, which starts after the ELF runtime
+    squashfs_offset: u64,
+    /// Contents of the .upd_info section
+    update_info: Option<String>,
+}
+
+// https://github.com/AppImage/AppImageSpec/blob/master/draft.md#type-2-image-format
+fn read_elf(path: &Path) -> Result<ElfInfo, Box<dyn Error>> {
+    let mut file = fs::File::open(path)?;
+    let mut header = [0; 64];
+    file.read_exact(&mut header)?;
+    if &header[..4] != b"\x7fELF" {
+        return Err(format!("{:?} is not an ELF file", path).into());
+    }
+    let is_64 = match header[4] {
+        1 => false,
+        2 => true,
+        class => return Err(format!("{:?} has unsupported ELF class {}", path, class).into()),
+    };
+    let endian = match header[5] {
+        1 => Endian { little: true },
+        2 => Endian { little: false },
+        data => return Err(format!("{:?} has unsupported ELF data {}", path, data).into()),
+    };
+
+    let (shoff, shentsize, shnum, shstrndx) = if is_64 {
+        (
+            endian.u64(&header, 0x28),
+            endian.u16(&header, 0x3A) as usize,
+            endian.u16(&header, 0x3C) as usize,
+            endian.u16(&header, 0x3E) as usize,
+        )
+    } else {
+        (
+            endian.u32(&header, 0x20) as u64,
+            endian.u16(&header, 0x2E) as usize,
+            endian.u16(&header, 0x30) as usize,
+            endian.u16(&header, 0x32) as usize,
+        )
+    };
+    let squashfs_offset = shoff + (shentsize * shnum) as u64;
+
+    if shentsize < if is_64 { 40 } else { 24 } || shstrndx >= shnum {
+        return Err(format!("{:?} has invalid ELF section headers", path).into());
+    }
+    let sections = read_at(&mut file, shoff, (shentsize * shnum) as u64)?;
+    // Returns name offset, data offset, and data size of a section
+    let section = |i: usize| -> (usize, u64, u64) {
+        let data = &sections[i * shentsize..(i + 1) * shentsize];
+        if is_64 {
+            (
+                endian.u32(data, 0) as usize,
+                endian.u64(data, 24),
+                endian.u64(data, 32),
+            )
+        } else {
+            (
+                endian.u32(data, 0) as usize,
+                endian.u32(data, 16) as u64,
+                endian.u32(data, 20) as u64,
+            )
+        }
+    };
+
+    let (_, strtab_offset, strtab_size) = section(shstrndx);
+    let strtab = read_at(&mut file, strtab_offset, strtab_size)?;
+    let mut update_info = None;
+    for i in 0..shnum {
+        let (name_offset, offset, size) = section(i);
+        let Some(name) = strtab.get(name_offset..) else {
+            continue;
+        };
+        let name_end = name.iter().position(|x| *x == 0).unwrap_or(name.len());
+        if &name[..name_end] == b".upd_info" {
+            let data = read_at(&mut file, offset, size)?;
+            let data_end = data.iter().position(|x| *x == 0).unwrap_or(data.len());
+            let info = String::from_utf8_lossy(&data[..data_end])
+                .trim()
+                .to_string();
+            if !info.is_empty() {
+                update_info = Some(info);
+            }
+            break;
+        }
+    }
+
+    Ok(ElfInfo {
+        squashfs_offset,
+        update_info,
+    })
+}
+
+/// Read the width and height from the header of a PNG file
+fn png_size(path: &Path) -> Option<(u32, u32)> {
+    let mut header = [0; 24];
+    fs::File::open(path).ok()?.read_exact(&mut header).ok()?;
+    if &header[..8] != b"\x89PNG\r\n\x1a\n" {
+        return None;
+    }
+    Some((
+        u32::from_be_bytes(header[16..20].try_into().ok()?),
+        u32::from_be_bytes(header[20..24].try_into().ok()?),
+    ))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    let mut hex = String::with_capacity(bytes.len() * 2);
+    for byte in bytes {
+        let _ = write!(hex, "{:02x}", byte);
+    }
+    hex
+}
+
+fn sha1_file(path: &Path) -> io::Result<String> {
+    let mut file = fs::File::open(path)?;
+    let mut hasher = Sha1::new();
+    io::copy(&mut file, &mut hasher)?;
+    Ok(hex(&hasher.finalize()))
+}
+
+/// Modification time and size of a file, used to detect changes
+fn file_tag(path: &Path) -> Result<String, Box<dyn Error>> {
+    let metadata = fs::metadata(path)?;
+    let modified = metadata
+        .modified()?
+        .duration_since(SystemTime::UNIX_EPOCH)?
+        .as_secs();
+    Ok(format!("{} {}", modified, metadata.len()))
+}
+
+/// Find the first file in a directory with the specified extension
+fn find_extension(dir: &Path, extension: &str) -> Option<PathBuf> {
+    let mut paths: Vec<_> = fs::read_dir(dir)
+        .ok()?
+        .filter_map(|entry_res| Some(entry_res.ok()?.path()))
+        .filter(|path| path.is_file() && path.extension().map_or(false, |x| x == extension))
+        .collect();
+    // Sort for stable results
+    paths.sort();
+    paths.into_iter().next()
+}
+
+/// Escape a value of type string in a desktop entry
+// https://specifications.freedesktop.org/desktop-entry-spec/latest/value-types.html
+fn desktop_string_escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\t' => escaped.push_str("\\t"),
+            '\r' => escaped.push_str("\\r"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Quote a path for the Exec key of a desktop entry. Reserved characters are escaped for the
+/// quoting rules, and then the result is escaped again as a string value.
+// https://specifications.freedesktop.org/desktop-entry-spec/latest/exec-variables.html
+fn desktop_exec_quote(path: &Path) -> String {
+    let mut quoted = String::from("\"");
+    for c in path.to_string_lossy().chars() {
+        match c {
+            '"' | '`' | '$' | '\\' => {
+                quoted.push('\\');
+                quoted.push(c);
+            }
+            // Field codes start with a percent sign
+            '%' => quoted.push_str("%%"),
+            _ => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    desktop_string_escape(&quoted)
+}
+
+/// Find the arguments after the program in an Exec value, following the string escape rules and
+/// then the quoting rules. Returns None if a quoted argument is not terminated.
+fn desktop_exec_args(value: &str) -> Option<&str> {
+    // Decode string escapes first, keeping the offset in the value after each character
+    let mut chars = Vec::with_capacity(value.len());
+    let mut char_indices = value.char_indices().peekable();
+    while let Some((i, c)) = char_indices.next() {
+        let end = i + c.len_utf8();
+        if c != '\\' {
+            chars.push((c, end));
+            continue;
+        }
+        match char_indices.peek().copied() {
+            Some((j, next)) if matches!(next, 's' | 'n' | 't' | 'r' | '\\') => {
+                char_indices.next();
+                let decoded = match next {
+                    's' => ' ',
+                    'n' => '\n',
+                    't' => '\t',
+                    'r' => '\r',
+                    _ => '\\',
+                };
+                chars.push((decoded, j + next.len_utf8()));
+            }
+            _ => chars.push((c, end)),
+        }
+    }
+
+    let mut quoted = false;
+    let mut escaped = false;
+    for (c, end) in chars {
+        if quoted {
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == '"' {
+                quoted = false;
+            }
+        } else if c == '"' {
+            quoted = true;
+        } else if c == ' ' {
+            return Some(value[end..].trim_start());
+        }
+    }
+    if quoted {
+        None
+    } else {
+        Some("")
+    }
+}
+
+/// Rewrite an embedded desktop entry to launch the AppImage at the specified path
+fn rewrite_desktop_entry(contents: &str, image: &Path, icon_name: &str) -> String {
+    let exec = desktop_exec_quote(image);
+    let image_value = desktop_string_escape(&image.to_string_lossy());
+    let mut s = String::new();
+    let mut in_entry = false;
+    for line in contents.lines() {
+        let trimmed = line.trim();
+        if trimmed.starts_with('[') {
+            let _ = writeln!(s, "{}", line);
+            in_entry = trimmed == "[Desktop Entry]" || trimmed.starts_with("[Desktop Action ");
+            if trimmed == "[Desktop Entry]" {
+                let _ = writeln!(s, "TryExec={}", image_value);
+                let _ = writeln!(s, "X-AppImage-Path={}", image_value);
+            }
+            continue;
+        }
+        if in_entry {
+            if let Some(value) = trimmed.strip_prefix("Exec=") {
+                // Replace the program, keeping its arguments
+                match desktop_exec_args(value) {
+                    Some("") => {
+                        let _ = writeln!(s, "Exec={}", exec);
+                    }
+                    Some(args) => {
+                        let _ = writeln!(s, "Exec={} {}", exec, args);
+                    }
+                    None => {
+                        log::warn!("invalid Exec value {:?} in desktop entry", value);
+                        let _ = writeln!(s, "Exec={}", exec);
+                    }
+                }
+                continue;
+            }
+            if trimmed.starts_with("Icon=") {
+                let _ = writeln!(s, "Icon={}", icon_name);
+                continue;
+            }
+            if trimmed.starts_with("TryExec=") || trimmed.starts_with("X-AppImage-Path=") {
+                continue;
+            }
+        }
+        let _ = writeln!(s, "{}", line);
+    }
+    s
+}
+
+fn http_client() -> reqwest::Result<reqwest::blocking::Client> {
+    // GitHub requires a user agent
+    reqwest::blocking::Client::builder()
+        .user_agent("cosmic-store")
+        .build()
+}
+
+/// Resolve the zsync URL from AppImage update information
+// https://github.com/AppImage/AppImageSpec/blob/master/draft.md#update-information
+fn zsync_url(update_info: &str) -> Result<Option<String>, Box<dyn Error>> {
+    let parts: Vec<&str> = update_info.split('|').collect();
+    match parts.as_slice() {
+        ["zsync", url] => Ok(Some(url.to_string())),
+        ["gh-releases-zsync", user, repo, tag, pattern] => {
+            let api_url = if *tag == "latest" {
+                format!("https://api.github.com/repos/{user}/{repo}/releases/latest")
+            } else {
+                format!("https://api.github.com/repos/{user}/{repo}/releases/tags/{tag}")
+            };
+            let release: serde_json::Value = http_client()?
+                .get(&api_url)
+                .send()?
+                .error_for_status()?
+                .json()?;
+            let pattern_regex = regex::Regex::new(&format!(
+                "^{}$",
+                regex::escape(pattern).replace("\\*", ".*")
+            ))?;
+            if let Some(assets) = release["assets"].as_array() {
+                for asset in assets {
+                    let name = asset["name"].as_str().unwrap_or_default();
+                    if pattern_regex.is_match(name) {
+                        if let Some(url) = asset["browser_download_url"].as_str() {
+                            return Ok(Some(url.to_string()));
+                        }
+                    }
+                }
+            }
+            Err(format!(
+                "no release asset matching {:?} found at {}",
+                pattern, api_url
+            )
+            .into())
+        }
+        _ => {
+            log::info!("unsupported update information {:?}", update_info);
+            Ok(None)
+        }
+    }
+}
+
+/// Read the header of a zsync control file
+fn zsync_header(url: &str) -> Result<HashMap<String, String>, Box<dyn Error>> {
+    let data = http_client()?
+        .get(url)
+        .send()?
+        .error_for_status()?
+        .bytes()?;
+    let mut header = HashMap::new();
+    // The header ends at the first empty line, followed by binary checksums
+    for line in data.split(|x| *x == b'\n') {
+        if line.is_empty() {
+            break;
+        }
+        let line = String::from_utf8_lossy(line);
+        if let Some((key, value)) = line.split_once(':') {
+            header.insert(key.trim().to_string(), value.trim().to_string());
+        }
+    }
+    Ok(header)
+}
+
+/// Copy from a reader to a file while reporting progress
+fn copy_progress<R: Read>(
+    mut reader: R,
+    total_opt: Option<u64>,
+    path: &Path,
+    f: &mut Box<dyn FnMut(f32) + 'static>,
+) -> io::Result<()> {
+    let mut file = fs::File::create(path)?;
+    let mut buf = vec![0; 1024 * 1024];
+    let mut copied = 0;
+    loop {
+        let count = reader.read(&mut buf)?;
+        if count == 0 {
+            break;
+        }
+        file.write_all(&buf[..count])?;
+        copied += count as u64;
+        if let Some(total) = total_opt.filter(|x| *x > 0) {
+            f((copied as f32) * 100.0 / (total as f32));
+        }
+    }
+    file.sync_all()?;
+    fs::set_permissions(path, fs::Permissions::from_mode(0o755))
+}
+
+/// Copy to a partial file next to the destination and rename it into place, so a failed
+/// copy never leaves a truncated image behind
+fn replace_progress<R: Read>(
+    reader: R,
+    total_opt: Option<u64>,
+    path: &Path,
+    f: &mut Box<dyn FnMut(f32) + 'static>,
+) -> io::Result<()> {
+    let partial = path.with_extension("AppImage.part");
+    let res =
+        copy_progress(reader, total_opt, &partial, f).and_then(|()| fs::rename(&partial, path));
+    if res.is_err() {
+        if let Err(err) = fs::remove_file(&partial) {
+            log::warn!("failed to remove {:?}: {}", partial, err);
+        }
+    }
+    res
+}
+
+#[derive(Clone, Debug)]
+struct AppimageMetadata {
+    id: AppId,
+    info: Arc<AppInfo>,
+    version: String,
+    desktop_path_opt: Option<PathBuf>,
+    icon_path_opt: Option<PathBuf>,
+    update_info: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct Appimage {
+    dir: PathBuf,
+    locale: String,
+    appstream_caches: Vec<AppstreamCache>,
+    // AppImages loaded from files outside of the managed directory, by ID
+    file_images: Mutex<HashMap<AppId, PathBuf>>,
+    // Results of update checks by image path, with the tag of the image when checked
+    update_checks: Mutex<HashMap<PathBuf, (String, Option<String>)>>,
+}
+
+impl Appimage {
+    pub fn new(dir: PathBuf, locale: &str) -> Result<Self, Box<dyn Error>> {
+        Ok(Self {
+            dir,
+            locale: locale.to_string(),
+            appstream_caches: vec![AppstreamCache::new(
+                SOURCE_ID.to_string(),
+                SOURCE_NAME.to_string(),
+                Vec::new(),
+                Vec::new(),
+                locale,
+            )],
+            file_images: Mutex::new(HashMap::new()),
+            update_checks: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Directory where metadata extracted from an AppImage is stored
+    fn metadata_dir(&self, image: &Path) -> Result<PathBuf, Box<dyn Error>> {
+        let cache_dir = dirs::cache_dir().ok_or("failed to find cache directory")?;
+        // Images with the same file name in different directories must not share metadata
+        let canonical = fs::canonicalize(image)?;
+        let mut hasher = Sha1::new();
+        hasher.update(canonical.as_os_str().as_bytes());
+        Ok(cache_dir
+            .join("cosmic-store")
+            .join("appimage")
+            .join(hex(&hasher.finalize())))
+    }
+
+    fn desktop_id(id: &AppId) -> String {
+        format!("appimage-{}.desktop", id.normalized())
+    }
+
+    fn icon_name(id: &AppId) -> String {
+        format!("appimage-{}", id.normalized())
+    }
+
+    /// Extract metadata from an AppImage if it has changed, and then load it
+    fn metadata(&self, image: &Path) -> Result<AppimageMetadata, Box<dyn Error>> {
+        let dir = self.metadata_dir(image)?;
+        let tag = file_tag(image)?;
+        let tag_path = dir.with_extension("tag");
+
+        let elf = read_elf(image)?;
+        if fs::read_to_string(&tag_path).ok().as_deref() != Some(tag.as_str()) {
+            if dir.exists() {
+                fs::remove_dir_all(&dir)?;
+            }
+            fs::create_dir_all(&dir)?;
+            // Extracting with unsquashfs avoids running the AppImage to read its metadata
+            let status = process::Command::new("unsquashfs")
+                .arg("-no-progress")
+                .arg("-offset")
+                .arg(elf.squashfs_offset.to_string())
+                .arg("-force")
+                .arg("-dest")
+                .arg(&dir)
+                .arg(image)
+                .args(METADATA_PATTERNS)
+                .stdout(process::Stdio::null())
+                .status()?;
+            if !status.success() {
+                return Err(format!("failed to extract {:?}: {}", image, status).into());
+            }
+            fs::write(&tag_path, &tag)?;
+        }
+
+        self.load_metadata(image, &dir, elf.update_info)
+    }
+
+    fn load_metadata(
+        &self,
+        image: &Path,
+        dir: &Path,
+        update_info: Option<String>,
+    ) -> Result<AppimageMetadata, Box<dyn Error>> {
+        let desktop_path_opt = find_extension(dir, "desktop");
+        let desktop_entry_opt = match &desktop_path_opt {
+            Some(desktop_path) => Some(freedesktop_entry_parser::parse_entry(desktop_path)?),
+            None => None,
+        };
+        let desktop_attr = |key: &str| -> Option<String> {
+            desktop_entry_opt
+                .as_ref()?
+                .section("Desktop Entry")
+                .attr(key)
+                .map(|x| x.to_string())
+        };
+
+        let mut icon_path_opt = None;
+        if let Some(icon) = desktop_attr("Icon") {
+            for extension in ["svg", "png"] {
+                let icon_path = dir.join(format!("{}.{}", icon, extension));
+                if icon_path.is_file() {
+                    icon_path_opt = Some(icon_path);
+                    break;
+                }
+            }
+        }
+        if icon_path_opt.is_none() && dir.join(".DirIcon").is_file() {
+            icon_path_opt = Some(dir.join(".DirIcon"));
+        }
+
+        let mut metainfo_opt = None;
+        for metainfo_dir in ["usr/share/metainfo", "usr/share/appdata"] {
+            if let Some(path) = find_extension(&dir.join(metainfo_dir), "xml") {
+                metainfo_opt = Some(path);
+                break;
+            }
+        }
+
+        let file_stem = image
+            .file_stem()
+            .and_then(|x| x.to_str())
+            .unwrap_or_default()
+            .to_string();
+        let mut id_opt = None;
+        let mut info = match metainfo_opt {
+            Some(metainfo) => {
+                let element = xmltree::Element::parse(fs::File::open(&metainfo)?)?;
+                let component = Component::try_from(&element)?;
+                id_opt = Some(AppId::new(&component.id.0));
+                let mut info =
+                    AppInfo::new(SOURCE_ID, SOURCE_NAME, None, component, &self.locale, 0);
+                info.keywords = app_info::keywords_from_element(&element, &self.locale);
+                info
+            }
+            None => {
+                //TODO: use localized desktop entry values
+                AppInfo {
+                    source_id: SOURCE_ID.to_string(),
+                    source_name: SOURCE_NAME.to_string(),
+                    origin_opt: None,
+                    kind: AppKind::DesktopApplication,
+                    name: desktop_attr("Name").unwrap_or_else(|| file_stem.clone()),
+                    summary: desktop_attr("Comment").unwrap_or_default(),
+                    developer_name: String::new(),
+                    description: RichText::default(),
+                    pkgnames: Vec::new(),
+                    categories: desktop_attr("Categories")
+                        .unwrap_or_default()
+                        .split(';')
+                        .filter(|x| !x.is_empty())
+                        .map(|x| x.to_string())
+                        .collect(),
+                    keywords: desktop_attr("Keywords")
+                        .unwrap_or_default()
+                        .split(';')
+                        .filter(|x| !x.is_empty())
+                        .map(|x| x.to_string())
+                        .collect(),
+                    desktop_ids: Vec::new(),
+                    flatpak_refs: Vec::new(),
+                    extends: Vec::new(),
+                    provides: Vec::new(),
+                    icons: Vec::new(),
+                    releases: Vec::new(),
+                    screenshots: Vec::new(),
+                    content_rating_opt: None,
+                    project_license_opt: None,
+                    urls: Vec::new(),
+                    monthly_downloads: 0,
+                }
+            }
+        };
+
+        // Fall back to the desktop entry name if there was no metainfo
+        let id = id_opt.unwrap_or_else(|| {
+            match desktop_path_opt
+                .as_ref()
+                .and_then(|x| x.file_stem())
+                .and_then(|x| x.to_str())
+            {
+                Some(desktop_stem) => AppId::new(desktop_stem),
+                None => AppId::new(&file_stem),
+            }
+        });
+
+        // The integrated desktop entry and icon replace the ones from metainfo
+        info.desktop_ids = vec![Self::desktop_id(&id)];
+        info.icons = match &icon_path_opt {
+            Some(icon_path) => {
+                let (width, height) = png_size(icon_path).unzip();
+                vec![AppIcon::Local(
+                    icon_path.to_string_lossy().to_string(),
+                    width,
+                    height,
+                    None,
+                )]
+            }
+            None => Vec::new(),
+        };
+
+        let version = desktop_attr("X-AppImage-Version")
+            .or_else(|| info.releases.first().map(|x| x.version.clone()))
+            .unwrap_or_default();
+
+        Ok(AppimageMetadata {
+            id,
+            info: Arc::new(info),
+            version,
+            desktop_path_opt,
+            icon_path_opt,
+            update_info,
+        })
+    }
+
+    /// List AppImages in the managed directory
+    fn scan(&self) -> Vec<(PathBuf, AppimageMetadata)> {
+        let mut images = Vec::new();
+        let read_dir = match fs::read_dir(&self.dir) {
+            Ok(ok) => ok,
+            Err(err) => {
+                if err.kind() != io::ErrorKind::NotFound {
+                    log::warn!("failed to read AppImage directory {:?}: {}", self.dir, err);
+                }
+                return images;
+            }
+        };
+        for entry_res in read_dir {
+            let path = match entry_res {
+                Ok(entry) => entry.path(),
+                Err(err) => {
+                    log::warn!(
+                        "failed to read entry in AppImage directory {:?}: {}",
+                        self.dir,
+                        err
+                    );
+                    continue;
+                }
+            };
+            if !is_appimage(&path) {
+                continue;
+            }
+            match self.metadata(&path) {
+                Ok(metadata) => images.push((path, metadata)),
+                Err(err) => {
+                    log::warn!("failed to load AppImage {:?}: {}", path, err);
+                }
+            }
+        }
+        images.sort_by(|a, b| a.0.cmp(&b.0));
+        images
+    }
+
+    fn find_image(&self, id: &AppId) -> Option<(PathBuf, AppimageMetadata)> {
+        self.scan()
+            .into_iter()
+            .find(|(_, metadata)| &metadata.id == id)
+    }
+
+    fn icon_dir(metadata: &AppimageMetadata) -> Option<PathBuf> {
+        let icon_path = metadata.icon_path_opt.as_ref()?;
+        let hicolor = dirs::data_dir()?.join("icons").join("hicolor");
+        if icon_path.extension().map_or(false, |x| x == "svg") {
+            Some(hicolor.join("scalable").join("apps"))
+        } else {
+            let (width, height) = png_size(icon_path).unwrap_or((256, 256));
+            Some(hicolor.join(format!("{}x{}", width, height)).join("apps"))
+        }
+    }
+
+    /// Create a desktop entry and icon for an AppImage
+    fn integrate(&self, image: &Path, metadata: &AppimageMetadata) -> Result<(), Box<dyn Error>> {
+        let data_dir = dirs::data_dir().ok_or("failed to find data directory")?;
+        let icon_name = Self::icon_name(&metadata.id);
+
+        if let (Some(icon_path), Some(icon_dir)) =
+            (&metadata.icon_path_opt, Self::icon_dir(metadata))
+        {
+            let extension = if icon_path.extension().map_or(false, |x| x == "svg") {
+                "svg"
+            } else {
+                "png"
+            };
+            fs::create_dir_all(&icon_dir)?;
+            fs::copy(
+                icon_path,
+                icon_dir.join(format!("{}.{}", icon_name, extension)),
+            )?;
+        }
+
+        let Some(desktop_path) = &metadata.desktop_path_opt else {
+            log::warn!("AppImage {:?} has no desktop entry to integrate", image);
+            return Ok(());
+        };
+        let contents = fs::read_to_string(desktop_path)?;
+        let applications_dir = data_dir.join("applications");
+        fs::create_dir_all(&applications_dir)?;
+        fs::write(
+            applications_dir.join(Self::desktop_id(&metadata.id)),
+            rewrite_desktop_entry(&contents, image, &icon_name),
+        )?;
+        log::info!("integrated AppImage {:?}", image);
+        Ok(())
+    }
+
+    /// Remove the desktop entry and icons of an AppImage
+    fn disintegrate(&self, metadata: &AppimageMetadata) -> Result<(), Box<dyn Error>> {
+        let data_dir = dirs::data_dir().ok_or("failed to find data directory")?;
+        let desktop_path = data_dir
+            .join("applications")
+            .join(Self::desktop_id(&metadata.id));
+        if desktop_path.is_file() {
+            fs::remove_file(&desktop_path)?;
+        }
+
+        // Icons may have been installed with any size
+        let icon_name = Self::icon_name(&metadata.id);
+        let hicolor = data_dir.join("icons").join("hicolor");
+        if let Ok(read_dir) = fs::read_dir(&hicolor) {
+            for entry in read_dir.filter_map(|x| x.ok()) {
+                for extension in ["png", "svg"] {
+                    let icon_path = entry
+                        .path()
+                        .join("apps")
+                        .join(format!("{}.{}", icon_name, extension));
+                    if icon_path.is_file() {
+                        fs::remove_file(&icon_path)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn to_package(metadata: AppimageMetadata) -> Package {
+        Package {
+            icon: match &metadata.icon_path_opt {
+                Some(icon_path) => widget::icon::from_path(icon_path.clone()),
+                None => widget::icon::from_name("package-x-generic")
+                    .size(128)
+                    .handle(),
+            },
+            id: metadata.id,
+            info: metadata.info,
+            version: metadata.version,
+            extra: HashMap::new(),
+        }
+    }
+
+    /// Checksum of an AppImage, cached until the image changes
+    fn image_sha1(&self, image: &Path) -> Result<String, Box<dyn Error>> {
+        let tag = file_tag(image)?;
+        let sha1_path = self.metadata_dir(image)?.with_extension("sha1");
+        if let Ok(cached) = fs::read_to_string(&sha1_path) {
+            if let Some((cached_tag, sha1)) = cached.rsplit_once(' ') {
+                if cached_tag == tag {
+                    return Ok(sha1.to_string());
+                }
+            }
+        }
+        let sha1 = sha1_file(image)?;
+        if let Err(err) = fs::write(&sha1_path, format!("{} {}", tag, sha1)) {
+            log::warn!("failed to cache checksum of {:?}: {}", image, err);
+        }
+        Ok(sha1)
+    }
+
+    /// Find the download URL of an update, if one is available
+    fn update_url(
+        &self,
+        image: &Path,
+        metadata: &AppimageMetadata,
+    ) -> Result<Option<String>, Box<dyn Error>> {
+        let Some(update_info) = &metadata.update_info else {
+            return Ok(None);
+        };
+        let Some(zsync_url) = zsync_url(update_info)? else {
+            return Ok(None);
+        };
+        let header = zsync_header(&zsync_url)?;
+        let sha1 = header
+            .get("SHA-1")
+            .ok_or_else(|| format!("zsync file {} missing SHA-1", zsync_url))?;
+        if sha1.eq_ignore_ascii_case(&self.image_sha1(image)?) {
+            return Ok(None);
+        }
+        let url = header
+            .get("URL")
+            .ok_or_else(|| format!("zsync file {} missing URL", zsync_url))?;
+        // The URL may be relative to the zsync file
+        Ok(Some(
+            reqwest::Url::parse(&zsync_url)?.join(url)?.to_string(),
+        ))
+    }
+
+    fn operation_inner(
+        &self,
+        kind: OperationKind,
+        package_id: &AppId,
+        _info: &AppInfo,
+        mut f: Box<dyn FnMut(f32) + 'static>,
+    ) -> Result<(), Box<dyn Error>> {
+        match kind {
+            OperationKind::Install => {
+                let source = self
+                    .file_images
+                    .lock()
+                    .unwrap()
+                    .get(package_id)
+                    .cloned()
+                    .ok_or_else(|| format!("no AppImage file found for {:?}", package_id))?;
+                let file_name = source.file_name().ok_or("AppImage has no file name")?;
+                fs::create_dir_all(&self.dir)?;
+                let image = self.dir.join(file_name);
+                // Copying an image onto itself would truncate it
+                let in_place = match (fs::canonicalize(&source), fs::canonicalize(&image)) {
+                    (Ok(source), Ok(image)) => source == image,
+                    _ => false,
+                };
+                if in_place {
+                    log::info!("AppImage {:?} is already in {:?}", source, self.dir);
+                    fs::set_permissions(&image, fs::Permissions::from_mode(0o755))?;
+                } else {
+                    log::info!("installing AppImage {:?} to {:?}", source, image);
+                    let total = fs::metadata(&source)?.len();
+                    replace_progress(fs::File::open(&source)?, Some(total), &image, &mut f)?;
+                }
+                let metadata = self.metadata(&image)?;
+                self.integrate(&image, &metadata)?;
+                Ok(())
+            }
+            OperationKind::Uninstall => {
+                let (image, metadata) = self
+                    .find_image(package_id)
+                    .ok_or_else(|| format!("AppImage {:?} not found", package_id))?;
+                log::info!("uninstalling AppImage {:?}", image);
+                self.disintegrate(&metadata)?;
+                // The metadata directory is found from the canonical path of the image
+                let metadata_dir = self.metadata_dir(&image)?;
+                fs::remove_file(&image)?;
+                if metadata_dir.exists() {
+                    fs::remove_dir_all(&metadata_dir)?;
+                }
+                for extension in ["tag", "sha1"] {
+                    let path = metadata_dir.with_extension(extension);
+                    if path.exists() {
+                        fs::remove_file(&path)?;
+                    }
+                }
+                self.update_checks.lock().unwrap().remove(&image);
+                Ok(())
+            }
+            OperationKind::Update => {
+                let (image, metadata) = self
+                    .find_image(package_id)
+                    .ok_or_else(|| format!("AppImage {:?} not found", package_id))?;
+                let url = self
+                    .update_url(&image, &metadata)?
+                    .ok_or_else(|| format!("no update found for AppImage {:?}", image))?;
+                log::info!("updating AppImage {:?} from {}", image, url);
+                //TODO: use zsync to only download changed blocks
+                let response = http_client()?.get(&url).send()?.error_for_status()?;
+                let total_opt = response.content_length();
+                replace_progress(response, total_opt, &image, &mut f)?;
+                // Icons and desktop entries may have changed
+                self.disintegrate(&metadata)?;
+                let metadata = self.metadata(&image)?;
+                self.integrate(&image, &metadata)?;
+                Ok(())
+            }
+            OperationKind::Rollback => Err("AppImages do not keep previous versions".into()),
+        }
+    }
+}
+
+impl Backend for Appimage {
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            file_install: true,
+            ..Default::default()
+        }
+    }
+
+    fn load_caches(&mut self, refresh: bool) -> Result<(), Box<dyn Error>> {
+        if refresh {
+            // Check for new releases again
+            self.update_checks.lock().unwrap().clear();
+        }
+        let images = self.scan();
+        self.appstream_caches[0].infos.clear();
+        self.appstream_caches[0].pkgnames.clear();
+        for (_image, metadata) in images {
+            self.appstream_caches[0]
+                .infos
+                .insert(metadata.id, metadata.info);
+        }
+        Ok(())
+    }
+
+    fn info_caches(&self) -> &[AppstreamCache] {
+        &self.appstream_caches
+    }
+
+    fn installed(&self) -> Result<Vec<Package>, Box<dyn Error>> {
+        Ok(self
+            .scan()
+            .into_iter()
+            .map(|(_, metadata)| Self::to_package(metadata))
+            .collect())
+    }
+
+    fn updates(&self) -> Result<Vec<Package>, Box<dyn Error>> {
+        let mut packages = Vec::new();
+        for (image, metadata) in self.scan() {
+            // Results are reused until the image changes or updates are refreshed
+            let tag = match file_tag(&image) {
+                Ok(ok) => ok,
+                Err(err) => {
+                    log::warn!("failed to read AppImage {:?}: {}", image, err);
+                    continue;
+                }
+            };
+            let cached_opt = match self.update_checks.lock().unwrap().get(&image) {
+                Some((cached_tag, url_opt)) if cached_tag == &tag => Some(url_opt.clone()),
+                _ => None,
+            };
+            let url_opt = match cached_opt {
+                Some(url_opt) => url_opt,
+                None => match self.update_url(&image, &metadata) {
+                    Ok(url_opt) => {
+                        self.update_checks
+                            .lock()
+                            .unwrap()
+                            .insert(image.clone(), (tag, url_opt.clone()));
+                        url_opt
+                    }
+                    Err(err) => {
+                        log::warn!("failed to check updates for AppImage {:?}: {}", image, err);
+                        continue;
+                    }
+                },
+            };
+            if url_opt.is_some() {
+                packages.push(Self::to_package(metadata));
+            }
+        }
+        Ok(packages)
+    }
+
+    fn file_packages(&self, path: &str) -> Result<Vec<Package>, Box<dyn Error>> {
+        let image = Path::new(path);
+        if !is_appimage(image) {
+            return Ok(Vec::new());
+        }
+        let metadata = self.metadata(image)?;
+        self.file_images
+            .lock()
+            .unwrap()
+            .insert(metadata.id.clone(), image.to_path_buf());
+        Ok(vec![Self::to_package(metadata)])
+    }
+
+    fn operation(
+        &self,
+        kind: OperationKind,
+        package_id: &AppId,
+        info: &AppInfo,
+        f: Box<dyn FnMut(f32) + 'static>,
+    ) -> Result<(), BackendError> {
+        self.operation_inner(kind, package_id, info, f)
+            .map_err(BackendError::from)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, process};
+
+    fn temp_path(name: &str) -> PathBuf {
+        env::temp_dir().join(format!("cosmic-store-appimage-{}-{}", process::id(), name))
+    }
+
+    #[test]
+    fn exec_quoting() {
+        assert_eq!(
+            desktop_exec_quote(Path::new("/home/user/Apps/My App.AppImage")),
+            r#""/home/user/Apps/My App.AppImage""#
+        );
+        // Reserved characters are escaped for quoting, and backslashes again as a string
+        assert_eq!(
+            desktop_exec_quote(Path::new(r#"/a"b`c$d\e 100%.AppImage"#)),
+            r#""/a\\"b\\`c\\$d\\\\e 100%%.AppImage""#
+        );
+    }
+
+    #[test]
+    fn exec_args() {
+        assert_eq!(desktop_exec_args("app"), Some(""));
+        assert_eq!(desktop_exec_args("app %U"), Some("%U"));
+        assert_eq!(
+            desktop_exec_args(r#""/opt/My App/app"  --flag %F"#),
+            Some("--flag %F")
+        );
+        assert_eq!(desktop_exec_args(r#""/opt/a \\"b\\"/app" %u"#), Some("%u"));
+        assert_eq!(desktop_exec_args(r#""/opt/My App/app %U"#), None);
+    }
+
+    #[test]
+    fn desktop_entry_rewrite() {
+        let contents = r#"[Desktop Entry]
+Name=My App
+Exec="/opt/My App/app" --flag %U
+TryExec=app
+Icon=app
+
+[Desktop Action new-window]
+Name=New Window
+Exec=app --new-window
+
+[X-Other]
+Exec=untouched
+"#;
+        assert_eq!(
+            rewrite_desktop_entry(
+                contents,
+                Path::new("/home/user/Apps/My App.AppImage"),
+                "appimage-my-app"
+            ),
+            r#"[Desktop Entry]
+TryExec=/home/user/Apps/My App.AppImage
+X-AppImage-Path=/home/user/Apps/My App.AppImage
+Name=My App
+Exec="/home/user/Apps/My App.AppImage" --flag %U
+Icon=appimage-my-app
+
+[Desktop Action new-window]
+Name=New Window
+Exec="/home/user/Apps/My App.AppImage" --new-window
+
+[X-Other]
+Exec=untouched
+"#
+        );
+    }
+
+    #[test]
+    fn zsync_update_info() {
+        assert_eq!(
+            zsync_url("zsync|https://example.org/app.AppImage.zsync").unwrap(),
+            Some("https://example.org/app.AppImage.zsync".to_string())
+        );
+        assert_eq!(zsync_url("bintray-zsync|a|b|c|d").unwrap(), None);
+        assert_eq!(zsync_url("").unwrap(), None);
+    }
+
+    #[test]
+    fn elf_sections() {
+        let update_info = b"zsync|https://example.org/app.zsync\0\0\0\0";
+        let strtab = b"\0.upd_info\0.shstrtab\0";
+        let strtab_offset = 64;
+        let update_info_offset = strtab_offset + strtab.len();
+        let shoff = 256;
+
+        let mut data = vec![0; shoff];
+        data[..4].copy_from_slice(b"\x7fELF");
+        data[4] = 2;
+        data[5] = 1;
+        data[0x28..0x30].copy_from_slice(&(shoff as u64).to_le_bytes());
+        data[0x3A..0x3C].copy_from_slice(&64u16.to_le_bytes());
+        data[0x3C..0x3E].copy_from_slice(&3u16.to_le_bytes());
+        data[0x3E..0x40].copy_from_slice(&2u16.to_le_bytes());
+        data[strtab_offset..update_info_offset].copy_from_slice(strtab);
+        data[update_info_offset..update_info_offset + update_info.len()]
+            .copy_from_slice(update_info);
+        for (name, offset, size) in [
+            (0u32, 0usize, 0usize),
+            (1, update_info_offset, update_info.len()),
+            (11, strtab_offset, strtab.len()),
+        ] {
+            let mut section = [0; 64];
+            section[..4].copy_from_slice(&name.to_le_bytes());
+            section[24..32].copy_from_slice(&(offset as u64).to_le_bytes());
+            section[32..40].copy_from_slice(&(size as u64).to_le_bytes());
+            data.extend_from_slice(&section);
+        }
+
+        let path = temp_path("elf");
+        fs::write(&path, &data).unwrap();
+        let elf = read_elf(&path).unwrap();
+        assert_eq!(elf.squashfs_offset, (shoff + 3 * 64) as u64);
+        assert_eq!(
+            elf.update_info.as_deref(),
+            Some("zsync|https://example.org/app.zsync")
+        );
+
+        fs::write(&path, b"#!/bin/sh\n").unwrap();
+        assert!(read_elf(&path).is_err());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn png_header_size() {
+        let mut data = b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR".to_vec();
+        data.extend_from_slice(&48u32.to_be_bytes());
+        data.extend_from_slice(&32u32.to_be_bytes());
+        let path = temp_path("png");
+        fs::write(&path, &data).unwrap();
+        assert_eq!(png_size(&path), Some((48, 32)));
+
+        fs::write(&path, b"<svg/>").unwrap();
+        assert_eq!(png_size(&path), None);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(png_size(&path), None);
+    }
+}
@@ -0,0 +1,181 @@
+use std::{error::Error, fmt, io};
+
+use crate::fl;
+
+/// Categorized error from a backend operation, with the underlying detail
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum BackendError {
+    NotFound(String),
+    AuthDenied(String),
+    Network(String),
+    NoSpace(String),
+    Conflict(String),
+    Cancelled(String),
+    Unavailable(String),
+    Other(String),
+}
+
+impl BackendError {
+    pub fn detail(&self) -> &str {
+        match self {
+            Self::NotFound(detail)
+            | Self::AuthDenied(detail)
+            | Self::Network(detail)
+            | Self::NoSpace(detail)
+            | Self::Conflict(detail)
+            | Self::Cancelled(detail)
+            | Self::Unavailable(detail)
+            | Self::Other(detail) => detail,
+        }
+    }
+
+    /// True if trying the operation again may succeed without the user changing anything
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Self::Network(_) | Self::Conflict(_) | Self::Unavailable(_) | Self::Other(_) => true,
+            Self::NotFound(_) | Self::AuthDenied(_) | Self::NoSpace(_) | Self::Cancelled(_) => {
+                false
+            }
+        }
+    }
+
+    /// Translated explanation of what went wrong
+    pub fn explanation(&self) -> String {
+        match self {
+            Self::NotFound(_) => fl!("error-not-found"),
+            Self::AuthDenied(_) => fl!("error-auth-denied"),
+            Self::Network(_) => fl!("error-network"),
+            Self::NoSpace(_) => fl!("error-no-space"),
+            Self::Conflict(_) => fl!("error-conflict"),
+            Self::Cancelled(_) => fl!("error-cancelled"),
+            Self::Unavailable(_) => fl!("error-unavailable"),
+            Self::Other(_) => fl!("error-other"),
+        }
+    }
+
+    /// Translated suggestion for how to resolve the error
+    pub fn remedy(&self) -> String {
+        match self {
+            Self::NotFound(_) => fl!("error-not-found-remedy"),
+            Self::AuthDenied(_) => fl!("error-auth-denied-remedy"),
+            Self::Network(_) => fl!("error-network-remedy"),
+            Self::NoSpace(_) => fl!("error-no-space-remedy"),
+            Self::Conflict(_) => fl!("error-conflict-remedy"),
+            Self::Cancelled(_) => fl!("error-cancelled-remedy"),
+            Self::Unavailable(_) => fl!("error-unavailable-remedy"),
+            Self::Other(_) => fl!("error-other-remedy"),
+        }
+    }
+
+    fn from_io(err: &io::Error) -> Self {
+        let detail = err.to_string();
+        //TODO: use io::ErrorKind::StorageFull when rust-version allows
+        if err.raw_os_error() == Some(libc::ENOSPC) {
+            return Self::NoSpace(detail);
+        }
+        match err.kind() {
+            io::ErrorKind::NotFound => Self::NotFound(detail),
+            io::ErrorKind::PermissionDenied => Self::AuthDenied(detail),
+            io::ErrorKind::ConnectionRefused
+            | io::ErrorKind::ConnectionReset
+            | io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::NotConnected
+            | io::ErrorKind::TimedOut => Self::Network(detail),
+            io::ErrorKind::AlreadyExists => Self::Conflict(detail),
+            io::ErrorKind::Interrupted => Self::Cancelled(detail),
+            _ => Self::Other(detail),
+        }
+    }
+
+    fn from_reqwest(err: &reqwest::Error) -> Self {
+        let detail = err.to_string();
+        match err.status().map(|x| x.as_u16()) {
+            Some(401 | 403) => Self::AuthDenied(detail),
+            Some(404 | 410) => Self::NotFound(detail),
+            _ => Self::Network(detail),
+        }
+    }
+}
+
+impl fmt::Display for BackendError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.detail())
+    }
+}
+
+impl Error for BackendError {}
+
+impl From<Box<dyn Error>> for BackendError {
+    fn from(err: Box<dyn Error>) -> Self {
+        let err = match err.downcast::<Self>() {
+            Ok(backend_err) => return *backend_err,
+            Err(err) => err,
+        };
+        if let Some(io_err) = err.downcast_ref::<io::Error>() {
+            return Self::from_io(io_err);
+        }
+        if let Some(reqwest_err) = err.downcast_ref::<reqwest::Error>() {
+            return Self::from_reqwest(reqwest_err);
+        }
+        Self::Other(err.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn from_io(err: io::Error) -> BackendError {
+        BackendError::from(Box::new(err) as Box<dyn Error>)
+    }
+
+    #[test]
+    fn io_errors() {
+        assert_eq!(
+            from_io(io::Error::from_raw_os_error(libc::ENOSPC)),
+            BackendError::NoSpace(io::Error::from_raw_os_error(libc::ENOSPC).to_string())
+        );
+        for (kind, expected) in [
+            (
+                io::ErrorKind::NotFound,
+                BackendError::NotFound as fn(String) -> _,
+            ),
+            (io::ErrorKind::PermissionDenied, BackendError::AuthDenied),
+            (io::ErrorKind::ConnectionRefused, BackendError::Network),
+            (io::ErrorKind::TimedOut, BackendError::Network),
+            (io::ErrorKind::AlreadyExists, BackendError::Conflict),
+            (io::ErrorKind::Interrupted, BackendError::Cancelled),
+            (io::ErrorKind::InvalidData, BackendError::Other),
+        ] {
+            let err = io::Error::new(kind, "detail");
+            assert_eq!(from_io(err), expected("detail".to_string()), "{:?}", kind);
+        }
+    }
+
+    #[test]
+    fn downcast() {
+        // Categorized errors pass through boxing unchanged
+        let err: Box<dyn Error> = Box::new(BackendError::AuthDenied("denied".to_string()));
+        assert_eq!(
+            BackendError::from(err),
+            BackendError::AuthDenied("denied".to_string())
+        );
+
+        // Errors without a category keep their message
+        let err: Box<dyn Error> = "failed".into();
+        assert_eq!(
+            BackendError::from(err),
+            BackendError::Other("failed".to_string())
+        );
+    }
+
+    #[test]
+    fn retryable() {
+        assert!(BackendError::Network(String::new()).is_retryable());
+        assert!(BackendError::Conflict(String::new()).is_retryable());
+        assert!(BackendError::Other(String::new()).is_retryable());
+        assert!(!BackendError::AuthDenied(String::new()).is_retryable());
+        assert!(!BackendError::NoSpace(String::new()).is_retryable());
+        assert!(!BackendError::NotFound(String::new()).is_retryable());
+    }
+}
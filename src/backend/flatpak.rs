@@ -1,19 +1,109 @@
 use cosmic::widget;
-use libflatpak::{gio::Cancellable, prelude::*, Installation, Ref, Transaction};
+use libflatpak::{
+    gio::{self, Cancellable},
+    glib,
+    prelude::*,
+    Installation, Ref, Transaction,
+};
 use std::{
     cell::Cell,
     collections::HashMap,
     error::Error,
     fmt::Write,
+    path::PathBuf,
     sync::{Arc, Mutex},
 };
 
-use super::{Backend, Package};
-use crate::{AppId, AppInfo, AppstreamCache, OperationKind};
+use super::{Backend, BackendError, Capabilities, Package, Repo};
+use crate::{AppId, AppInfo, AppKind, AppstreamCache, OperationKind, RichText};
+
+/// Categorize errors from libflatpak and gio
+fn flatpak_error(err: Box<dyn Error>) -> BackendError {
+    let Some(glib_err) = err.downcast_ref::<glib::Error>() else {
+        return BackendError::from(err);
+    };
+    let detail = glib_err.message().to_string();
+    if let Some(kind) = glib_err.kind::<libflatpak::Error>() {
+        return match kind {
+            libflatpak::Error::NotInstalled
+            | libflatpak::Error::RemoteNotFound
+            | libflatpak::Error::RuntimeNotFound
+            | libflatpak::Error::RefNotFound => BackendError::NotFound(detail),
+            libflatpak::Error::PermissionDenied
+            | libflatpak::Error::AuthenticationFailed
+            | libflatpak::Error::NotAuthorized
+            | libflatpak::Error::WrongUser => BackendError::AuthDenied(detail),
+            libflatpak::Error::OutOfSpace => BackendError::NoSpace(detail),
+            libflatpak::Error::AlreadyInstalled
+            | libflatpak::Error::DifferentRemote
+            | libflatpak::Error::Downgrade
+            | libflatpak::Error::RemoteUsed
+            | libflatpak::Error::RuntimeUsed => BackendError::Conflict(detail),
+            libflatpak::Error::Aborted => BackendError::Cancelled(detail),
+            libflatpak::Error::NeedNewFlatpak => BackendError::Unavailable(detail),
+            _ => BackendError::Other(detail),
+        };
+    }
+    if let Some(kind) = glib_err.kind::<gio::IOErrorEnum>() {
+        return match kind {
+            gio::IOErrorEnum::NotFound => BackendError::NotFound(detail),
+            gio::IOErrorEnum::PermissionDenied => BackendError::AuthDenied(detail),
+            gio::IOErrorEnum::NoSpace => BackendError::NoSpace(detail),
+            gio::IOErrorEnum::Exists => BackendError::Conflict(detail),
+            gio::IOErrorEnum::Cancelled => BackendError::Cancelled(detail),
+            gio::IOErrorEnum::HostNotFound
+            | gio::IOErrorEnum::HostUnreachable
+            | gio::IOErrorEnum::NetworkUnreachable
+            | gio::IOErrorEnum::ConnectionRefused
+            | gio::IOErrorEnum::TimedOut => BackendError::Network(detail),
+            _ => BackendError::Other(detail),
+        };
+    }
+    BackendError::Other(detail)
+}
+
+/// Readable permissions from the metadata of an installed flatpak
+// https://docs.flatpak.org/en/latest/flatpak-command-reference.html#flatpak-metadata
+fn metadata_permissions(metadata: &str) -> Vec<String> {
+    let mut permissions = Vec::new();
+    let mut section = "";
+    for line in metadata.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Some(name) = line.strip_prefix('[').and_then(|x| x.strip_suffix(']')) {
+            section = name;
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        let (key, value) = (key.trim(), value.trim());
+        match section {
+            "Context" => {
+                for item in value.split(';').filter(|x| !x.is_empty()) {
+                    permissions.push(format!("{}: {}", key, item));
+                }
+            }
+            "Session Bus Policy" => {
+                permissions.push(format!("session-bus: {} ({})", key, value));
+            }
+            "System Bus Policy" => {
+                permissions.push(format!("system-bus: {} ({})", key, value));
+            }
+            _ => {}
+        }
+    }
+    permissions
+}
 
 #[derive(Debug)]
 pub struct Flatpak {
     appstream_caches: Vec<AppstreamCache>,
+    installation_path_opt: Option<PathBuf>,
+    // Cancellables of operations in progress by app, so they can be cancelled
+    cancellables: Mutex<HashMap<AppId, Cancellable>>,
 }
 
 impl Flatpak {
@@ -31,6 +121,11 @@ impl Flatpak {
                 }
             };
 
+            if remote.is_disabled() {
+                log::info!("skipping disabled remote {}", source_id);
+                continue;
+            }
+
             let appstream_dir = match remote.appstream_dir(None).and_then(|x| x.path()) {
                 Some(some) => some,
                 None => {
@@ -75,7 +170,11 @@ impl Flatpak {
         }
 
         // We don't store the installation because it is not Send
-        Ok(Self { appstream_caches })
+        Ok(Self {
+            appstream_caches,
+            installation_path_opt: inst.path().and_then(|x| x.path()),
+            cancellables: Mutex::new(HashMap::new()),
+        })
     }
 
     fn ref_to_package<R: InstalledRefExt + RefExt>(&self, r: &R) -> Option<Package> {
@@ -161,17 +260,24 @@ impl Flatpak {
                     source_id: appstream_cache.source_id.clone(),
                     source_name: appstream_cache.source_name.clone(),
                     origin_opt: None,
+                    kind: AppKind::Generic,
                     name,
                     summary,
                     developer_name: String::new(),
-                    description,
+                    description: RichText::from_plain(&description),
                     pkgnames: Vec::new(),
                     categories: Vec::new(),
+                    keywords: Vec::new(),
                     desktop_ids: Vec::new(),
                     flatpak_refs,
+                    extends: Vec::new(),
+                    provides: Vec::new(),
                     icons: Vec::new(),
                     releases: Vec::new(),
                     screenshots: Vec::new(),
+                    content_rating_opt: None,
+                    project_license_opt: None,
+                    urls: Vec::new(),
                     monthly_downloads: 0,
                 }),
                 version: String::new(),
@@ -181,56 +287,14 @@ impl Flatpak {
 
         packages
     }
-}
 
-impl Backend for Flatpak {
-    fn load_caches(&mut self, refresh: bool) -> Result<(), Box<dyn Error>> {
-        if refresh {
-            //TODO: should we support system installations?
-            let inst = Installation::new_user(Cancellable::NONE)?;
-            for remote in inst.list_remotes(Cancellable::NONE)? {
-                let Some(remote_name) = remote.name() else {
-                    continue;
-                };
-                inst.update_remote_sync(&remote_name, Cancellable::NONE)?;
-            }
-        }
-
-        for appstream_cache in self.appstream_caches.iter_mut() {
-            appstream_cache.reload();
-        }
-        Ok(())
-    }
-
-    fn info_caches(&self) -> &[AppstreamCache] {
-        &self.appstream_caches
-    }
-
-    fn installed(&self) -> Result<Vec<Package>, Box<dyn Error>> {
-        //TODO: should we support system installations?
-        let inst = Installation::new_user(Cancellable::NONE)?;
-        let packages = self.refs_to_packages(inst.list_installed_refs(Cancellable::NONE)?);
-        Ok(packages)
-    }
-
-    fn updates(&self) -> Result<Vec<Package>, Box<dyn Error>> {
-        //TODO: should we support system installations?
-        let inst = Installation::new_user(Cancellable::NONE)?;
-        let packages =
-            self.refs_to_packages(inst.list_installed_refs_for_update(Cancellable::NONE)?);
-        Ok(packages)
-    }
-
-    fn file_packages(&self, path: &str) -> Result<Vec<Package>, Box<dyn Error>> {
-        Err("flatpak backend does not support loading details from a file".into())
-    }
-
-    fn operation(
+    fn operation_inner(
         &self,
         kind: OperationKind,
         id: &AppId,
         info: &AppInfo,
         callback: Box<dyn FnMut(f32) + 'static>,
+        cancellable: &Cancellable,
     ) -> Result<(), Box<dyn Error>> {
         let callback = Arc::new(Mutex::new(callback));
         //TODO: should we support system installations?
@@ -303,7 +367,7 @@ impl Backend for Flatpak {
 
                         log::info!("installing flatpak {} from remote {}", r_str, remote_name);
                         tx.add_install(&remote_name, &r_str, &[])?;
-                        tx.run(Cancellable::NONE)?;
+                        tx.run(Some(cancellable))?;
                         return Ok(());
                     }
                 }
@@ -335,7 +399,7 @@ impl Backend for Flatpak {
                     log::info!("uninstalling flatpak {}", r_str);
                     tx.add_uninstall(&r_str)?;
                 }
-                tx.run(Cancellable::NONE)?;
+                tx.run(Some(cancellable))?;
                 return Ok(());
             }
             OperationKind::Update => {
@@ -365,10 +429,207 @@ impl Backend for Flatpak {
                     log::info!("updating flatpak {}", r_str);
                     tx.add_update(&r_str, &[], None)?;
                 }
-                tx.run(Cancellable::NONE)?;
+                tx.run(Some(cancellable))?;
                 return Ok(());
             }
+            OperationKind::Rollback => {
+                return Err("flatpak backend does not support rollback".into());
+            }
         }
         Err(format!("package {id:?} not found").into())
     }
 }
+
+impl Backend for Flatpak {
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            cancel: true,
+            repo_management: true,
+            permissions: true,
+            ..Default::default()
+        }
+    }
+
+    fn load_caches(&mut self, refresh: bool) -> Result<(), Box<dyn Error>> {
+        if refresh {
+            //TODO: should we support system installations?
+            let inst = Installation::new_user(Cancellable::NONE)?;
+            for remote in inst.list_remotes(Cancellable::NONE)? {
+                let Some(remote_name) = remote.name() else {
+                    continue;
+                };
+                inst.update_remote_sync(&remote_name, Cancellable::NONE)?;
+            }
+        }
+
+        for appstream_cache in self.appstream_caches.iter_mut() {
+            appstream_cache.reload();
+        }
+        Ok(())
+    }
+
+    fn info_caches(&self) -> &[AppstreamCache] {
+        &self.appstream_caches
+    }
+
+    fn watch_paths(&self) -> Vec<PathBuf> {
+        let mut watch_paths = Vec::new();
+        for appstream_cache in self.appstream_caches.iter() {
+            watch_paths.extend(appstream_cache.watch_paths());
+        }
+        // Remotes are stored in the repo config, and their appstream data in the appstream
+        // directory, so adding or removing remotes changes these
+        if let Some(installation_path) = &self.installation_path_opt {
+            for dir in [
+                installation_path.join("repo"),
+                installation_path.join("appstream"),
+            ] {
+                if dir.is_dir() {
+                    watch_paths.push(dir);
+                }
+            }
+        }
+        watch_paths.sort();
+        watch_paths.dedup();
+        watch_paths
+    }
+
+    fn installed(&self) -> Result<Vec<Package>, Box<dyn Error>> {
+        //TODO: should we support system installations?
+        let inst = Installation::new_user(Cancellable::NONE)?;
+        let packages = self.refs_to_packages(inst.list_installed_refs(Cancellable::NONE)?);
+        Ok(packages)
+    }
+
+    fn updates(&self) -> Result<Vec<Package>, Box<dyn Error>> {
+        //TODO: should we support system installations?
+        let inst = Installation::new_user(Cancellable::NONE)?;
+        let packages =
+            self.refs_to_packages(inst.list_installed_refs_for_update(Cancellable::NONE)?);
+        Ok(packages)
+    }
+
+    fn operation(
+        &self,
+        kind: OperationKind,
+        id: &AppId,
+        info: &AppInfo,
+        callback: Box<dyn FnMut(f32) + 'static>,
+    ) -> Result<(), BackendError> {
+        let cancellable = Cancellable::new();
+        self.cancellables
+            .lock()
+            .unwrap()
+            .insert(id.clone(), cancellable.clone());
+        let res = self.operation_inner(kind, id, info, callback, &cancellable);
+        self.cancellables.lock().unwrap().remove(id);
+        res.map_err(flatpak_error)
+    }
+
+    fn cancel(&self, id: &AppId) -> Result<(), Box<dyn Error>> {
+        match self.cancellables.lock().unwrap().get(id) {
+            Some(cancellable) => {
+                log::info!("cancelling flatpak operation for {:?}", id);
+                cancellable.cancel();
+                Ok(())
+            }
+            None => Err(format!("no operation in progress for {:?}", id).into()),
+        }
+    }
+
+    fn repos(&self) -> Result<Vec<Repo>, Box<dyn Error>> {
+        //TODO: should we support system installations?
+        let inst = Installation::new_user(Cancellable::NONE)?;
+        let mut repos = Vec::new();
+        for remote in inst.list_remotes(Cancellable::NONE)? {
+            let Some(remote_name) = remote.name() else {
+                continue;
+            };
+            repos.push(Repo {
+                id: remote_name.to_string(),
+                name: remote
+                    .title()
+                    .map_or_else(|| remote_name.to_string(), |x| x.to_string()),
+                enabled: !remote.is_disabled(),
+            });
+        }
+        Ok(repos)
+    }
+
+    fn set_repo_enabled(&self, id: &str, enabled: bool) -> Result<(), Box<dyn Error>> {
+        //TODO: should we support system installations?
+        let inst = Installation::new_user(Cancellable::NONE)?;
+        let remote = inst.remote_by_name(id, Cancellable::NONE)?;
+        log::info!(
+            "{} flatpak remote {}",
+            if enabled { "enabling" } else { "disabling" },
+            id
+        );
+        remote.set_disabled(!enabled);
+        inst.modify_remote(&remote, Cancellable::NONE)?;
+        Ok(())
+    }
+
+    fn permissions(&self, id: &AppId, info: &AppInfo) -> Result<Vec<String>, Box<dyn Error>> {
+        //TODO: should we support system installations?
+        let inst = Installation::new_user(Cancellable::NONE)?;
+        for r_str in info.flatpak_refs.iter() {
+            let r = match Ref::parse(r_str) {
+                Ok(ok) => ok,
+                Err(err) => {
+                    log::warn!("failed to parse flatpak ref {:?}: {}", r_str, err);
+                    continue;
+                }
+            };
+            let installed_ref = match inst.installed_ref(
+                r.kind(),
+                &r.name().unwrap_or_default(),
+                r.arch().as_deref(),
+                r.branch().as_deref(),
+                Cancellable::NONE,
+            ) {
+                Ok(ok) => ok,
+                Err(err) => {
+                    log::info!("failed to find {:?} installed locally: {}", id, err);
+                    continue;
+                }
+            };
+            let metadata = installed_ref.load_metadata(Cancellable::NONE)?;
+            return Ok(metadata_permissions(std::str::from_utf8(&metadata)?));
+        }
+        Err(format!("package {id:?} not installed").into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::metadata_permissions;
+
+    #[test]
+    fn permissions_from_metadata() {
+        let metadata = "[Application]
+name=org.example.App
+runtime=org.freedesktop.Platform/x86_64/23.08
+
+[Context]
+shared=network;ipc;
+sockets=x11;wayland;
+filesystems=xdg-download:ro;
+
+[Session Bus Policy]
+org.freedesktop.Notifications=talk
+";
+        assert_eq!(
+            metadata_permissions(metadata),
+            vec![
+                "shared: network",
+                "shared: ipc",
+                "sockets: x11",
+                "sockets: wayland",
+                "filesystems: xdg-download:ro",
+                "session-bus: org.freedesktop.Notifications (talk)",
+            ]
+        );
+        assert!(metadata_permissions("[Application]\nname=org.example.App\n").is_empty());
+    }
+}
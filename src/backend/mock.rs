@@ -0,0 +1,676 @@
+use serde::Deserialize;
+use std::{
+    collections::{HashMap, HashSet},
+    env,
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    thread,
+    time::Duration,
+};
+
+use super::{Backend, BackendError, Capabilities, Package, Repo};
+use crate::{AppId, AppInfo, AppstreamCache, OperationKind};
+
+const SOURCE_ID: &'static str = "mock";
+//TODO: translate
+const SOURCE_NAME: &'static str = "Mock";
+
+/// Fixtures built into the binary, used unless COSMIC_STORE_MOCK_DIR is set
+const FIXTURES: &'static [(&'static str, &'static str)] = &[
+    (
+        "appstream.xml",
+        include_str!("../../res/mock/appstream.xml"),
+    ),
+    ("mock.json", include_str!("../../res/mock/mock.json")),
+];
+
+/// Write the built in fixtures to a directory, as appstream caches are loaded from files
+fn write_fixtures(dir: &Path) -> Result<(), Box<dyn Error>> {
+    fs::create_dir_all(dir)?;
+    for (file_name, data) in FIXTURES {
+        fs::write(dir.join(file_name), data)?;
+    }
+    Ok(())
+}
+
+#[derive(Clone, Debug, Deserialize)]
+struct MockPackage {
+    id: String,
+    version: String,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum MockOperationKind {
+    Install,
+    Uninstall,
+    Update,
+    Rollback,
+}
+
+impl From<OperationKind> for MockOperationKind {
+    fn from(kind: OperationKind) -> Self {
+        match kind {
+            OperationKind::Install => Self::Install,
+            OperationKind::Uninstall => Self::Uninstall,
+            OperationKind::Update => Self::Update,
+            OperationKind::Rollback => Self::Rollback,
+        }
+    }
+}
+
+/// Category of a scripted failure
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+enum MockErrorKind {
+    NotFound,
+    AuthDenied,
+    Network,
+    NoSpace,
+    Conflict,
+    Cancelled,
+    Unavailable,
+    #[default]
+    Other,
+}
+
+impl MockErrorKind {
+    fn error(self, detail: String) -> BackendError {
+        match self {
+            Self::NotFound => BackendError::NotFound(detail),
+            Self::AuthDenied => BackendError::AuthDenied(detail),
+            Self::Network => BackendError::Network(detail),
+            Self::NoSpace => BackendError::NoSpace(detail),
+            Self::Conflict => BackendError::Conflict(detail),
+            Self::Cancelled => BackendError::Cancelled(detail),
+            Self::Unavailable => BackendError::Unavailable(detail),
+            Self::Other => BackendError::Other(detail),
+        }
+    }
+}
+
+/// Scripted behavior of an operation
+#[derive(Clone, Debug, Deserialize)]
+struct MockOperation {
+    kind: Option<MockOperationKind>,
+    id: Option<String>,
+    /// Progress values reported in order
+    #[serde(default = "MockOperation::default_progress")]
+    progress: Vec<f32>,
+    /// Delay before each progress value
+    #[serde(default = "MockOperation::default_delay_ms")]
+    delay_ms: u64,
+    /// Fail with this error after reporting progress
+    error: Option<String>,
+    /// Category of the error
+    #[serde(default)]
+    error_kind: MockErrorKind,
+}
+
+impl MockOperation {
+    fn default_progress() -> Vec<f32> {
+        vec![0.0, 25.0, 50.0, 75.0, 100.0]
+    }
+
+    fn default_delay_ms() -> u64 {
+        100
+    }
+
+    fn matches(&self, kind: MockOperationKind, id: &AppId) -> bool {
+        self.kind.map_or(true, |x| x == kind) && self.id.as_ref().map_or(true, |x| x == id.raw())
+    }
+}
+
+impl Default for MockOperation {
+    fn default() -> Self {
+        Self {
+            kind: None,
+            id: None,
+            progress: Self::default_progress(),
+            delay_ms: Self::default_delay_ms(),
+            error: None,
+            error_kind: MockErrorKind::default(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+struct MockFixture {
+    capabilities: Capabilities,
+    installed: Vec<MockPackage>,
+    updates: Vec<MockPackage>,
+    /// Versions that rollback reverts to
+    previous: Vec<MockPackage>,
+    operations: Vec<MockOperation>,
+    repos: Vec<Repo>,
+    /// Permissions by package ID
+    permissions: HashMap<String, Vec<String>>,
+}
+
+#[derive(Debug, Default)]
+struct MockState {
+    // Installed package versions by ID
+    installed: HashMap<AppId, String>,
+    // Available update versions by ID
+    updates: HashMap<AppId, String>,
+    // Previously installed versions by ID
+    previous: HashMap<AppId, String>,
+    // Operations that were cancelled while in progress
+    cancelled: HashSet<AppId>,
+    repos: Vec<Repo>,
+}
+
+/// Backend that loads packages from fixture files, for testing and demos without a package manager
+#[derive(Debug)]
+pub struct Mock {
+    capabilities: Capabilities,
+    appstream_caches: Vec<AppstreamCache>,
+    operations: Vec<MockOperation>,
+    permissions: HashMap<String, Vec<String>>,
+    state: Mutex<MockState>,
+}
+
+impl Mock {
+    pub fn new(locale: &str) -> Result<Self, Box<dyn Error>> {
+        let dir = match env::var_os("COSMIC_STORE_MOCK_DIR") {
+            Some(dir) => PathBuf::from(dir),
+            None => {
+                let dir = dirs::cache_dir()
+                    .ok_or("failed to find cache directory")?
+                    .join("cosmic-store")
+                    .join("mock");
+                write_fixtures(&dir)?;
+                dir
+            }
+        };
+        Self::with_dir(&dir, locale)
+    }
+
+    /// Load fixtures from a directory containing appstream files and mock.json
+    pub fn with_dir(dir: &Path, locale: &str) -> Result<Self, Box<dyn Error>> {
+        let mut paths = Vec::new();
+        let mut icons_paths = Vec::new();
+        for entry_res in fs::read_dir(dir)? {
+            let path = entry_res?.path();
+            let Some(file_name) = path.file_name().and_then(|x| x.to_str()) else {
+                continue;
+            };
+            if file_name == "icons" && path.is_dir() {
+                match path.into_os_string().into_string() {
+                    Ok(ok) => icons_paths.push(ok),
+                    Err(os_string) => {
+                        log::error!("failed to convert {:?} to string", os_string)
+                    }
+                }
+            } else if [".xml", ".xml.gz", ".yml", ".yml.gz"]
+                .iter()
+                .any(|x| file_name.ends_with(x))
+            {
+                paths.push(path);
+            }
+        }
+        // Sort for deterministic loading
+        paths.sort();
+
+        let fixture_path = dir.join("mock.json");
+        let fixture: MockFixture = if fixture_path.is_file() {
+            serde_json::from_slice(&fs::read(&fixture_path)?)
+                .map_err(|err| format!("failed to parse {:?}: {}", fixture_path, err))?
+        } else {
+            log::warn!("mock fixture {:?} not found", fixture_path);
+            MockFixture::default()
+        };
+
+        let mut state = MockState {
+            repos: fixture.repos,
+            ..Default::default()
+        };
+        for package in fixture.installed {
+            state
+                .installed
+                .insert(AppId::new(&package.id), package.version);
+        }
+        for package in fixture.updates {
+            state
+                .updates
+                .insert(AppId::new(&package.id), package.version);
+        }
+        for package in fixture.previous {
+            state
+                .previous
+                .insert(AppId::new(&package.id), package.version);
+        }
+
+        Ok(Self {
+            capabilities: fixture.capabilities,
+            appstream_caches: vec![AppstreamCache::new(
+                SOURCE_ID.to_string(),
+                SOURCE_NAME.to_string(),
+                paths,
+                icons_paths,
+                locale,
+            )],
+            operations: fixture.operations,
+            permissions: fixture.permissions,
+            state: Mutex::new(state),
+        })
+    }
+
+    fn packages(&self, versions: &HashMap<AppId, String>) -> Vec<Package> {
+        let appstream_cache = &self.appstream_caches[0];
+        let mut packages = Vec::with_capacity(versions.len());
+        for (id, version) in versions.iter() {
+            let Some(info) = appstream_cache.infos.get(id) else {
+                log::warn!("mock package {:?} not found in fixtures", id);
+                continue;
+            };
+            packages.push(Package {
+                id: id.clone(),
+                icon: appstream_cache.icon(info),
+                info: info.clone(),
+                version: version.clone(),
+                extra: HashMap::new(),
+            });
+        }
+        // Sort for deterministic results
+        packages.sort_by(|a, b| a.id.raw().cmp(b.id.raw()));
+        packages
+    }
+}
+
+impl Backend for Mock {
+    fn capabilities(&self) -> Capabilities {
+        self.capabilities
+    }
+
+    fn load_caches(&mut self, _refresh: bool) -> Result<(), Box<dyn Error>> {
+        // Always parse fixtures so edits are picked up without stale caches
+        for appstream_cache in self.appstream_caches.iter_mut() {
+            appstream_cache.load_original();
+        }
+        Ok(())
+    }
+
+    fn info_caches(&self) -> &[AppstreamCache] {
+        &self.appstream_caches
+    }
+
+    fn installed(&self) -> Result<Vec<Package>, Box<dyn Error>> {
+        let state = self.state.lock().unwrap();
+        Ok(self.packages(&state.installed))
+    }
+
+    fn updates(&self) -> Result<Vec<Package>, Box<dyn Error>> {
+        let state = self.state.lock().unwrap();
+        Ok(self.packages(&state.updates))
+    }
+
+    fn file_packages(&self, path: &str) -> Result<Vec<Package>, Box<dyn Error>> {
+        // Files are matched to fixtures by name, for example org.example.Chess.mock
+        let id = match Path::new(path).file_stem().and_then(|x| x.to_str()) {
+            Some(file_stem) => AppId::new(file_stem),
+            None => return Ok(Vec::new()),
+        };
+        let appstream_cache = &self.appstream_caches[0];
+        let Some(info) = appstream_cache.infos.get(&id) else {
+            return Ok(Vec::new());
+        };
+        Ok(vec![Package {
+            id,
+            icon: appstream_cache.icon(info),
+            info: info.clone(),
+            version: info
+                .releases
+                .first()
+                .map(|x| x.version.clone())
+                .unwrap_or_default(),
+            extra: HashMap::new(),
+        }])
+    }
+
+    fn operation(
+        &self,
+        kind: OperationKind,
+        package_id: &AppId,
+        info: &AppInfo,
+        mut f: Box<dyn FnMut(f32) + 'static>,
+    ) -> Result<(), BackendError> {
+        let mock_kind = MockOperationKind::from(kind);
+        let operation = self
+            .operations
+            .iter()
+            .find(|x| x.matches(mock_kind, package_id))
+            .cloned()
+            .unwrap_or_default();
+        log::info!("mock {:?} of {:?}: {:?}", kind, package_id, operation);
+
+        self.state.lock().unwrap().cancelled.remove(package_id);
+        for progress in operation.progress.iter() {
+            thread::sleep(Duration::from_millis(operation.delay_ms));
+            if self.state.lock().unwrap().cancelled.remove(package_id) {
+                return Err(BackendError::Cancelled(format!(
+                    "mock {:?} of {:?} was cancelled",
+                    kind, package_id
+                )));
+            }
+            f(*progress);
+        }
+
+        if let Some(error) = operation.error {
+            return Err(operation.error_kind.error(error));
+        }
+
+        let mut state = self.state.lock().unwrap();
+        match kind {
+            OperationKind::Install => {
+                let version = info
+                    .releases
+                    .first()
+                    .map(|x| x.version.clone())
+                    .unwrap_or_default();
+                state.installed.insert(package_id.clone(), version);
+            }
+            OperationKind::Uninstall => {
+                if state.installed.remove(package_id).is_none() {
+                    return Err(BackendError::NotFound(format!(
+                        "package {:?} is not installed",
+                        package_id
+                    )));
+                }
+                state.updates.remove(package_id);
+            }
+            OperationKind::Update => match state.updates.remove(package_id) {
+                Some(version) => {
+                    if let Some(previous) = state.installed.insert(package_id.clone(), version) {
+                        state.previous.insert(package_id.clone(), previous);
+                    }
+                }
+                None => {
+                    return Err(BackendError::NotFound(format!(
+                        "package {:?} has no update",
+                        package_id
+                    )));
+                }
+            },
+            OperationKind::Rollback => match state.previous.remove(package_id) {
+                Some(version) => {
+                    state.installed.insert(package_id.clone(), version);
+                }
+                None => {
+                    return Err(BackendError::NotFound(format!(
+                        "package {:?} has no previous version",
+                        package_id
+                    )));
+                }
+            },
+        }
+        Ok(())
+    }
+
+    fn cancel(&self, package_id: &AppId) -> Result<(), Box<dyn Error>> {
+        log::info!("mock cancel of {:?}", package_id);
+        self.state
+            .lock()
+            .unwrap()
+            .cancelled
+            .insert(package_id.clone());
+        Ok(())
+    }
+
+    fn simulate(
+        &self,
+        kind: OperationKind,
+        package_id: &AppId,
+        info: &AppInfo,
+    ) -> Result<Vec<String>, Box<dyn Error>> {
+        let mut names = info.pkgnames.clone();
+        if kind == OperationKind::Uninstall {
+            // Installed add-ons are removed with the app they extend
+            let state = self.state.lock().unwrap();
+            for (addon_id, addon_info) in self.appstream_caches[0].infos.iter() {
+                if state.installed.contains_key(addon_id)
+                    && addon_info
+                        .extends
+                        .iter()
+                        .any(|x| AppId::new(x) == *package_id)
+                {
+                    names.extend(addon_info.pkgnames.iter().cloned());
+                }
+            }
+        }
+        names.sort();
+        Ok(names)
+    }
+
+    fn offline_update(&self) -> Result<(), Box<dyn Error>> {
+        let state = self.state.lock().unwrap();
+        log::info!(
+            "mock offline update of {:?} prepared",
+            state.updates.keys().collect::<Vec<_>>()
+        );
+        Ok(())
+    }
+
+    fn repos(&self) -> Result<Vec<Repo>, Box<dyn Error>> {
+        Ok(self.state.lock().unwrap().repos.clone())
+    }
+
+    fn set_repo_enabled(&self, id: &str, enabled: bool) -> Result<(), Box<dyn Error>> {
+        let mut state = self.state.lock().unwrap();
+        let repo = state
+            .repos
+            .iter_mut()
+            .find(|x| x.id == id)
+            .ok_or_else(|| format!("mock repo {:?} not found", id))?;
+        repo.enabled = enabled;
+        Ok(())
+    }
+
+    fn permissions(
+        &self,
+        package_id: &AppId,
+        _info: &AppInfo,
+    ) -> Result<Vec<String>, Box<dyn Error>> {
+        Ok(self
+            .permissions
+            .get(package_id.raw())
+            .cloned()
+            .unwrap_or_default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{process, sync::Arc};
+
+    fn mock(name: &str) -> Mock {
+        let dir = env::temp_dir().join(format!("cosmic-store-mock-{}-{}", process::id(), name));
+        write_fixtures(&dir).unwrap();
+        let mut mock = Mock::with_dir(&dir, "en").unwrap();
+        mock.load_caches(false).unwrap();
+        // Keep scripted progress, but without waiting
+        for operation in mock.operations.iter_mut() {
+            operation.delay_ms = 0;
+        }
+        mock
+    }
+
+    fn info(mock: &Mock, id: &str) -> Arc<AppInfo> {
+        mock.info_caches()[0].infos[&AppId::new(id)].clone()
+    }
+
+    fn versions(packages: Vec<Package>) -> Vec<(String, String)> {
+        packages
+            .into_iter()
+            .map(|x| (x.id.raw().to_string(), x.version))
+            .collect()
+    }
+
+    /// Run an operation, returning the reported progress and the result
+    fn operation(
+        mock: &Mock,
+        kind: OperationKind,
+        id: &str,
+    ) -> (Vec<f32>, Result<(), BackendError>) {
+        let progress = Arc::new(Mutex::new(Vec::new()));
+        let callback_progress = progress.clone();
+        let res = mock.operation(
+            kind,
+            &AppId::new(id),
+            &info(mock, id),
+            Box::new(move |x| callback_progress.lock().unwrap().push(x)),
+        );
+        let progress = progress.lock().unwrap().clone();
+        (progress, res)
+    }
+
+    #[test]
+    fn fixtures() {
+        let mock = mock("fixtures");
+        assert_eq!(
+            mock.capabilities(),
+            Capabilities {
+                file_install: true,
+                cancel: true,
+                dry_run: true,
+                offline_update: true,
+                repo_management: true,
+                permissions: true,
+                rollback: true,
+            }
+        );
+        assert_eq!(
+            versions(mock.installed().unwrap()),
+            [
+                ("org.example.Terminal".to_string(), "3.2.1".to_string()),
+                ("org.example.TextEditor".to_string(), "1.0.0".to_string()),
+                (
+                    "org.example.TextEditor.SpellCheck".to_string(),
+                    "1.0.0".to_string()
+                ),
+            ]
+        );
+        assert_eq!(
+            versions(mock.updates().unwrap()),
+            [("org.example.TextEditor".to_string(), "1.1.0".to_string())]
+        );
+    }
+
+    #[test]
+    fn scripted_install() {
+        let mock = mock("install");
+        let (progress, res) = operation(&mock, OperationKind::Install, "org.example.Chess");
+        assert_eq!(progress, [10.0, 40.0, 40.0, 70.0, 100.0]);
+        assert_eq!(res, Ok(()));
+        assert!(versions(mock.installed().unwrap())
+            .contains(&("org.example.Chess".to_string(), "0.9.0".to_string())));
+
+        let (progress, res) = operation(&mock, OperationKind::Install, "org.example.Music");
+        assert_eq!(progress, [20.0, 50.0]);
+        assert_eq!(
+            res,
+            Err(BackendError::Network(
+                "simulated network failure".to_string()
+            ))
+        );
+        assert!(!versions(mock.installed().unwrap())
+            .iter()
+            .any(|(id, _)| id == "org.example.Music"));
+    }
+
+    #[test]
+    fn scripted_uninstall_and_update() {
+        let mock = mock("uninstall");
+        let (progress, res) = operation(&mock, OperationKind::Uninstall, "org.example.Terminal");
+        assert_eq!(progress, MockOperation::default_progress());
+        assert_eq!(res, Ok(()));
+        let (_progress, res) = operation(&mock, OperationKind::Uninstall, "org.example.Terminal");
+        assert!(matches!(res, Err(BackendError::NotFound(_))), "{:?}", res);
+
+        let (_progress, res) = operation(&mock, OperationKind::Update, "org.example.TextEditor");
+        assert_eq!(res, Ok(()));
+        assert!(versions(mock.installed().unwrap())
+            .contains(&("org.example.TextEditor".to_string(), "1.1.0".to_string())));
+        assert!(mock.updates().unwrap().is_empty());
+
+        // Rolling back reverts the update, but only once
+        let (_progress, res) = operation(&mock, OperationKind::Rollback, "org.example.TextEditor");
+        assert_eq!(res, Ok(()));
+        assert!(versions(mock.installed().unwrap())
+            .contains(&("org.example.TextEditor".to_string(), "1.0.0".to_string())));
+        let (_progress, res) = operation(&mock, OperationKind::Rollback, "org.example.TextEditor");
+        assert!(matches!(res, Err(BackendError::NotFound(_))), "{:?}", res);
+    }
+
+    #[test]
+    fn cancel_operation() {
+        let mock = mock("cancel");
+        let id = AppId::new("org.example.Chess");
+        // Stale cancellations do not affect later operations
+        mock.cancel(&id).unwrap();
+        let (progress, res) = operation(&mock, OperationKind::Install, "org.example.Chess");
+        assert_eq!(progress.len(), 5);
+        assert_eq!(res, Ok(()));
+
+        // Cancel from the progress callback, as it runs between steps
+        let mock = Arc::new(mock);
+        let callback_mock = mock.clone();
+        let callback_id = id.clone();
+        let res = mock.operation(
+            OperationKind::Uninstall,
+            &id,
+            &info(&mock, "org.example.Chess"),
+            Box::new(move |_| callback_mock.cancel(&callback_id).unwrap()),
+        );
+        assert!(matches!(res, Err(BackendError::Cancelled(_))), "{:?}", res);
+        assert!(versions(mock.installed().unwrap())
+            .contains(&("org.example.Chess".to_string(), "0.9.0".to_string())));
+    }
+
+    #[test]
+    fn simulate_repos_and_permissions() {
+        let mock = mock("simulate");
+        let id = AppId::new("org.example.TextEditor");
+        let info = info(&mock, "org.example.TextEditor");
+        assert_eq!(
+            mock.simulate(OperationKind::Install, &id, &info).unwrap(),
+            ["example-text-editor"]
+        );
+        assert_eq!(
+            mock.simulate(OperationKind::Uninstall, &id, &info).unwrap(),
+            ["example-text-editor", "example-text-editor-spell-check"]
+        );
+
+        assert_eq!(
+            mock.repos().unwrap(),
+            [
+                Repo {
+                    id: "mock".to_string(),
+                    name: "Mock".to_string(),
+                    enabled: true,
+                },
+                Repo {
+                    id: "mock-testing".to_string(),
+                    name: "Mock Testing".to_string(),
+                    enabled: false,
+                },
+            ]
+        );
+        mock.set_repo_enabled("mock-testing", true).unwrap();
+        assert!(mock.repos().unwrap().iter().all(|x| x.enabled));
+        assert!(mock.set_repo_enabled("missing", true).is_err());
+
+        assert_eq!(
+            mock.permissions(&id, &info).unwrap(),
+            ["filesystems: home", "shared: network"]
+        );
+        assert!(mock
+            .permissions(&AppId::new("org.example.Chess"), &info)
+            .unwrap()
+            .is_empty());
+    }
+}
@@ -1,21 +1,36 @@
 use cosmic::widget;
 use rayon::prelude::*;
+#[cfg(feature = "mock")]
+use serde::Deserialize;
 use std::{
     collections::{BTreeMap, HashMap},
     error::Error,
     fmt,
+    path::PathBuf,
     sync::Arc,
-    time::Instant,
+    time::{Duration, Instant},
 };
 
-use crate::{AppId, AppInfo, AppstreamCache, OperationKind};
+use crate::{AppId, AppInfo, AppstreamCache, Config, OperationKind};
+
+pub use self::error::BackendError;
+mod error;
+
+#[cfg(feature = "appimage")]
+mod appimage;
 
 #[cfg(feature = "flatpak")]
 mod flatpak;
 
+#[cfg(feature = "mock")]
+mod mock;
+
 #[cfg(feature = "packagekit")]
 mod packagekit;
 
+#[cfg(feature = "snap")]
+mod snap;
+
 #[derive(Clone, Debug)]
 pub struct Package {
     pub id: AppId,
@@ -25,79 +40,285 @@ pub struct Package {
     pub extra: HashMap<String, String>,
 }
 
+/// Optional features implemented by a backend
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "mock", derive(Deserialize), serde(default))]
+pub struct Capabilities {
+    /// Loading and installing packages from local files
+    pub file_install: bool,
+    /// Cancelling operations in progress
+    pub cancel: bool,
+    /// Simulating operations without making changes
+    pub dry_run: bool,
+    /// Applying updates on the next boot
+    pub offline_update: bool,
+    /// Enabling and disabling package sources
+    pub repo_management: bool,
+    /// Viewing app permissions
+    pub permissions: bool,
+    /// Reverting to a previously installed version
+    pub rollback: bool,
+}
+
+/// Package source of a backend, like a flatpak remote
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "mock", derive(Deserialize))]
+pub struct Repo {
+    pub id: String,
+    pub name: String,
+    pub enabled: bool,
+}
+
 pub trait Backend: fmt::Debug + Send + Sync {
+    fn capabilities(&self) -> Capabilities;
     fn load_caches(&mut self, refresh: bool) -> Result<(), Box<dyn Error>>;
     fn info_caches(&self) -> &[AppstreamCache];
+    /// Paths that are watched for changes, which require reloading caches
+    fn watch_paths(&self) -> Vec<PathBuf> {
+        let mut watch_paths = Vec::new();
+        for appstream_cache in self.info_caches() {
+            for path in appstream_cache.watch_paths() {
+                if !watch_paths.contains(&path) {
+                    watch_paths.push(path);
+                }
+            }
+        }
+        watch_paths
+    }
     fn installed(&self) -> Result<Vec<Package>, Box<dyn Error>>;
     fn updates(&self) -> Result<Vec<Package>, Box<dyn Error>>;
-    fn file_packages(&self, path: &str) -> Result<Vec<Package>, Box<dyn Error>>;
+    /// Only called if capabilities include file_install
+    fn file_packages(&self, _path: &str) -> Result<Vec<Package>, Box<dyn Error>> {
+        Err("backend does not support loading details from a file".into())
+    }
+    /// Rollback operations are only requested if capabilities include rollback
     fn operation(
         &self,
         kind: OperationKind,
         package_id: &AppId,
         info: &AppInfo,
         f: Box<dyn FnMut(f32) + 'static>,
-    ) -> Result<(), Box<dyn Error>>;
+    ) -> Result<(), BackendError>;
+    /// Only called if capabilities include cancel. The cancelled operation fails with
+    /// BackendError::Cancelled.
+    fn cancel(&self, _package_id: &AppId) -> Result<(), Box<dyn Error>> {
+        Err("backend does not support cancelling operations".into())
+    }
+    /// Only called if capabilities include dry_run. Names of the packages an operation would
+    /// change, without changing them.
+    fn simulate(
+        &self,
+        _kind: OperationKind,
+        _package_id: &AppId,
+        _info: &AppInfo,
+    ) -> Result<Vec<String>, Box<dyn Error>> {
+        Err("backend does not support simulating operations".into())
+    }
+    /// Only called if capabilities include offline_update. Downloads all updates and applies
+    /// them on the next boot.
+    fn offline_update(&self) -> Result<(), Box<dyn Error>> {
+        Err("backend does not support offline updates".into())
+    }
+    /// Only called if capabilities include repo_management
+    fn repos(&self) -> Result<Vec<Repo>, Box<dyn Error>> {
+        Err("backend does not support managing sources".into())
+    }
+    /// Only called if capabilities include repo_management
+    fn set_repo_enabled(&self, _id: &str, _enabled: bool) -> Result<(), Box<dyn Error>> {
+        Err("backend does not support managing sources".into())
+    }
+    /// Only called if capabilities include permissions. Readable descriptions of the
+    /// permissions of an installed package.
+    fn permissions(
+        &self,
+        _package_id: &AppId,
+        _info: &AppInfo,
+    ) -> Result<Vec<String>, Box<dyn Error>> {
+        Err("backend does not support viewing permissions".into())
+    }
 }
 
 // BTreeMap for stable sort order
 pub type Backends = BTreeMap<&'static str, Arc<dyn Backend>>;
 
-pub fn backends(locale: &str, refresh: bool) -> Backends {
+/// Names of all backends enabled at compile time
+pub fn backend_names() -> Vec<&'static str> {
+    let mut names = Vec::new();
+    #[cfg(feature = "appimage")]
+    names.push("appimage");
+    #[cfg(feature = "flatpak")]
+    names.push("flatpak");
+    #[cfg(feature = "mock")]
+    names.push("mock");
+    #[cfg(feature = "packagekit")]
+    names.push("packagekit");
+    #[cfg(feature = "snap")]
+    names.push("snap");
+    names
+}
+
+/// Name of backend as displayed to the user
+pub fn backend_title(backend_name: &str) -> &str {
+    match backend_name {
+        "appimage" => "AppImage",
+        "flatpak" => "Flatpak",
+        "mock" => "Mock",
+        "packagekit" => "PackageKit",
+        "snap" => "Snap",
+        _ => backend_name,
+    }
+}
+
+/// Result of initializing and loading the caches of a backend
+#[derive(Clone, Debug)]
+pub struct BackendStatus {
+    pub init_duration: Duration,
+    pub init_error_opt: Option<String>,
+    pub load_duration_opt: Option<Duration>,
+    pub load_error_opt: Option<String>,
+}
+
+impl BackendStatus {
+    fn new(init_duration: Duration, init_error_opt: Option<String>) -> Self {
+        Self {
+            init_duration,
+            init_error_opt,
+            load_duration_opt: None,
+            load_error_opt: None,
+        }
+    }
+}
+
+pub type BackendStatuses = BTreeMap<&'static str, BackendStatus>;
+
+pub fn backends(config: &Config, locale: &str, refresh: bool) -> (Backends, BackendStatuses) {
     let mut backends = Backends::new();
+    let mut statuses = BackendStatuses::new();
+
+    #[cfg(feature = "appimage")]
+    if config.backend_enabled("appimage") {
+        let start = Instant::now();
+        match appimage::Appimage::new(config.appimage_dir(), locale) {
+            Ok(backend) => {
+                backends.insert("appimage", Arc::new(backend));
+                let duration = start.elapsed();
+                log::info!("initialized appimage backend in {:?}", duration);
+                statuses.insert("appimage", BackendStatus::new(duration, None));
+            }
+            Err(err) => {
+                log::error!("failed to load appimage backend: {}", err);
+                statuses.insert(
+                    "appimage",
+                    BackendStatus::new(start.elapsed(), Some(err.to_string())),
+                );
+            }
+        }
+    }
 
     #[cfg(feature = "flatpak")]
-    {
+    if config.backend_enabled("flatpak") {
         let start = Instant::now();
         match flatpak::Flatpak::new(locale) {
             Ok(backend) => {
                 backends.insert("flatpak", Arc::new(backend));
                 let duration = start.elapsed();
                 log::info!("initialized flatpak backend in {:?}", duration);
+                statuses.insert("flatpak", BackendStatus::new(duration, None));
             }
             Err(err) => {
                 log::error!("failed to load flatpak backend: {}", err);
+                statuses.insert(
+                    "flatpak",
+                    BackendStatus::new(start.elapsed(), Some(err.to_string())),
+                );
+            }
+        }
+    }
+
+    #[cfg(feature = "mock")]
+    if config.backend_enabled("mock") {
+        let start = Instant::now();
+        match mock::Mock::new(locale) {
+            Ok(backend) => {
+                backends.insert("mock", Arc::new(backend));
+                let duration = start.elapsed();
+                log::info!("initialized mock backend in {:?}", duration);
+                statuses.insert("mock", BackendStatus::new(duration, None));
+            }
+            Err(err) => {
+                log::error!("failed to load mock backend: {}", err);
+                statuses.insert(
+                    "mock",
+                    BackendStatus::new(start.elapsed(), Some(err.to_string())),
+                );
             }
         }
     }
 
     #[cfg(feature = "packagekit")]
-    {
+    if config.backend_enabled("packagekit") {
         let start = Instant::now();
         match packagekit::Packagekit::new(locale) {
             Ok(backend) => {
                 backends.insert("packagekit", Arc::new(backend));
                 let duration = start.elapsed();
                 log::info!("initialized packagekit backend in {:?}", duration);
+                statuses.insert("packagekit", BackendStatus::new(duration, None));
             }
             Err(err) => {
                 log::error!("failed to load packagekit backend: {}", err);
+                statuses.insert(
+                    "packagekit",
+                    BackendStatus::new(start.elapsed(), Some(err.to_string())),
+                );
             }
         }
     }
 
-    backends.par_iter_mut().for_each(|(backend_name, backend)| {
+    #[cfg(feature = "snap")]
+    if config.backend_enabled("snap") {
         let start = Instant::now();
-        match Arc::get_mut(backend).unwrap().load_caches(refresh) {
-            Ok(()) => {
+        match snap::Snap::new(locale) {
+            Ok(backend) => {
+                backends.insert("snap", Arc::new(backend));
                 let duration = start.elapsed();
-                log::info!("loaded {} backend caches in {:?}", backend_name, duration);
+                log::info!("initialized snap backend in {:?}", duration);
+                statuses.insert("snap", BackendStatus::new(duration, None));
             }
             Err(err) => {
-                log::error!("failed to load {} backend caches: {}", backend_name, err);
+                log::error!("failed to load snap backend: {}", err);
+                statuses.insert(
+                    "snap",
+                    BackendStatus::new(start.elapsed(), Some(err.to_string())),
+                );
             }
         }
-    });
+    }
 
-    //TODO: Workaround for xml-rs memory leak when loading appstream data
-    {
-        let start = Instant::now();
-        unsafe {
-            libc::malloc_trim(0);
+    let load_results: Vec<_> = backends
+        .par_iter_mut()
+        .map(|(backend_name, backend)| {
+            let start = Instant::now();
+            let error_opt = match Arc::get_mut(backend).unwrap().load_caches(refresh) {
+                Ok(()) => {
+                    let duration = start.elapsed();
+                    log::info!("loaded {} backend caches in {:?}", backend_name, duration);
+                    None
+                }
+                Err(err) => {
+                    log::error!("failed to load {} backend caches: {}", backend_name, err);
+                    Some(err.to_string())
+                }
+            };
+            (*backend_name, start.elapsed(), error_opt)
+        })
+        .collect();
+    for (backend_name, duration, error_opt) in load_results {
+        if let Some(status) = statuses.get_mut(backend_name) {
+            status.load_duration_opt = Some(duration);
+            status.load_error_opt = error_opt;
         }
-        let duration = start.elapsed();
-        log::info!("trimmed allocations in {:?}", duration);
     }
 
-    backends
+    (backends, statuses)
 }
@@ -4,10 +4,15 @@ use packagekit_zbus::{
     PackageKit::PackageKitProxyBlocking,
     Transaction::TransactionProxyBlocking,
 };
-use std::{collections::HashMap, error::Error, fmt::Write, sync::Arc};
+use std::{
+    collections::HashMap,
+    error::Error,
+    fmt::Write,
+    sync::{Arc, Mutex},
+};
 
-use super::{Backend, Package};
-use crate::{AppId, AppInfo, AppstreamCache, OperationKind};
+use super::{Backend, BackendError, Capabilities, Package};
+use crate::{AppId, AppInfo, AppKind, AppstreamCache, OperationKind, RichText};
 
 struct TransactionDetails {
     //TODO: more fields: https://www.freedesktop.org/software/PackageKit/gtk-doc/Transaction.html#Transaction::Details
@@ -29,6 +34,31 @@ struct TransactionProgress {
     percentage: u32,
 }
 
+/// Categorize PackageKit error codes
+// https://www.freedesktop.org/software/PackageKit/gtk-doc/PackageKit-Enumerations.html#PkErrorEnum
+fn packagekit_error(code: u32, details: String) -> BackendError {
+    let detail = format!("{details} (code {code})");
+    match code {
+        // package-id-invalid, package-not-installed, package-not-found, group-not-found,
+        // repo-not-found, file-not-found, update-not-found
+        6 | 7 | 8 | 11 | 19 | 42 | 49 => BackendError::NotFound(detail),
+        // not-authorized
+        48 => BackendError::AuthDenied(detail),
+        // no-network, package-download-failed, repo-not-available, no-more-mirrors-to-try
+        2 | 10 | 37 | 43 => BackendError::Network(detail),
+        // no-space-on-device
+        46 => BackendError::NoSpace(detail),
+        // package-already-installed, dep-resolution-failed, cannot-remove-system-package,
+        // cannot-get-lock, file-conflicts, package-conflicts, all-packages-already-installed
+        9 | 13 | 20 | 26 | 35 | 36 | 41 => BackendError::Conflict(detail),
+        // transaction-cancelled, cancelled-priority
+        17 | 65 => BackendError::Cancelled(detail),
+        // not-supported, failed-initialization
+        3 | 22 => BackendError::Unavailable(detail),
+        _ => BackendError::Other(detail),
+    }
+}
+
 fn transaction_handle(
     tx: TransactionProxyBlocking,
     mut on_progress: impl FnMut(TransactionProgress),
@@ -69,7 +99,7 @@ fn transaction_handle(
                 "ErrorCode" => {
                     // https://www.freedesktop.org/software/PackageKit/gtk-doc/Transaction.html#Transaction::ErrorCode
                     let (code, details) = signal.body::<(u32, String)>()?;
-                    return Err(format!("{details} (code {code})").into());
+                    return Err(Box::new(packagekit_error(code, details)));
                 }
                 "ItemProgress" => {
                     // https://www.freedesktop.org/software/PackageKit/gtk-doc/Transaction.html#Transaction::ItemProgress
@@ -117,6 +147,8 @@ enum FilterKind {
 enum TransactionFlag {
     None = 1 << 0,
     OnlyTrusted = 1 << 1,
+    Simulate = 1 << 2,
+    OnlyDownload = 1 << 3,
     AllowReinstall = 1 << 4,
     AllowDowngrade = 1 << 6,
 }
@@ -125,6 +157,8 @@ enum TransactionFlag {
 pub struct Packagekit {
     connection: Connection,
     appstream_caches: Vec<AppstreamCache>,
+    // Paths of transactions in progress by package, so they can be cancelled
+    transactions: Mutex<HashMap<AppId, String>>,
 }
 
 impl Packagekit {
@@ -141,6 +175,7 @@ impl Packagekit {
                 source_name.to_string(),
                 locale,
             )],
+            transactions: Mutex::new(HashMap::new()),
         })
     }
 
@@ -192,17 +227,24 @@ impl Packagekit {
                     source_id: appstream_cache.source_id.clone(),
                     source_name: appstream_cache.source_name.clone(),
                     origin_opt: None,
+                    kind: AppKind::Generic,
                     name: package_name.to_string(),
                     summary: tx_detail.summary.clone(),
                     developer_name: String::new(),
-                    description: tx_detail.description.clone(),
+                    description: RichText::from_plain(&tx_detail.description),
                     pkgnames: vec![package_name.to_string()],
                     categories: Vec::new(),
+                    keywords: Vec::new(),
                     desktop_ids: Vec::new(),
                     flatpak_refs: Vec::new(),
+                    extends: Vec::new(),
+                    provides: Vec::new(),
                     icons: Vec::new(),
                     releases: Vec::new(),
                     screenshots: Vec::new(),
+                    content_rating_opt: None,
+                    project_license_opt: None,
+                    urls: Vec::new(),
                     monthly_downloads: 0,
                 }),
                 version: version_opt.unwrap_or("").to_string(),
@@ -276,17 +318,24 @@ impl Packagekit {
                     source_id: appstream_cache.source_id.clone(),
                     source_name: appstream_cache.source_name.clone(),
                     origin_opt: None,
+                    kind: AppKind::Generic,
                     name,
                     summary,
                     developer_name: String::new(),
-                    description,
+                    description: RichText::from_plain(&description),
                     pkgnames,
                     categories: Vec::new(),
+                    keywords: Vec::new(),
                     desktop_ids: Vec::new(),
                     flatpak_refs: Vec::new(),
+                    extends: Vec::new(),
+                    provides: Vec::new(),
                     icons: Vec::new(),
                     releases: Vec::new(),
                     screenshots: Vec::new(),
+                    content_rating_opt: None,
+                    project_license_opt: None,
+                    urls: Vec::new(),
                     monthly_downloads: 0,
                 }),
                 version: String::new(),
@@ -295,9 +344,100 @@ impl Packagekit {
         }
         Ok(packages)
     }
+
+    /// Resolve the IDs of the packages of an app that an operation applies to
+    fn resolve(
+        &self,
+        kind: OperationKind,
+        package_id: &AppId,
+        info: &AppInfo,
+    ) -> Result<Vec<String>, Box<dyn Error>> {
+        let mut package_names = Vec::with_capacity(info.pkgnames.len());
+        for pkgname in &info.pkgnames {
+            package_names.push(pkgname.as_str());
+        }
+        if package_names.is_empty() {
+            return Err(format!("{:?} missing package name", package_id).into());
+        }
+        let filter = match kind {
+            OperationKind::Install | OperationKind::Update => {
+                FilterKind::NotInstalled as u64
+                    | FilterKind::Newest as u64
+                    | FilterKind::Arch as u64
+            }
+            OperationKind::Uninstall => FilterKind::Installed as u64,
+            OperationKind::Rollback => return Err("PackageKit does not support rollback".into()),
+        };
+        let tx = self.transaction()?;
+        log::info!("resolve packages for {:?}", package_names);
+        tx.resolve(filter, &package_names)?;
+        let (_tx_details, tx_packages) = transaction_handle(tx, |_| {})?;
+        Ok(tx_packages
+            .into_iter()
+            .map(|tx_package| tx_package.package_id)
+            .collect())
+    }
+
+    fn operation_inner(
+        &self,
+        kind: OperationKind,
+        package_id: &AppId,
+        info: &AppInfo,
+        mut f: Box<dyn FnMut(f32) + 'static>,
+    ) -> Result<(), Box<dyn Error>> {
+        let resolved = self.resolve(kind, package_id, info)?;
+        let package_ids: Vec<&str> = resolved.iter().map(|x| x.as_str()).collect();
+        let tx = self.transaction()?;
+        tx.set_hints(&["interactive=true"])?;
+        match kind {
+            OperationKind::Install => {
+                log::info!("installing packages {:?}", package_ids);
+                //TODO: transaction flags
+                tx.install_packages(TransactionFlag::OnlyTrusted as u64, &package_ids)?;
+            }
+            OperationKind::Uninstall => {
+                log::info!("uninstalling packages {:?}", package_ids);
+                //TODO: transaction flags?
+                tx.remove_packages(0, &package_ids, true, true)?;
+            }
+            OperationKind::Update => {
+                log::info!("updating packages {:?}", package_ids);
+                //TODO: transaction flags?
+                tx.update_packages(TransactionFlag::OnlyTrusted as u64, &package_ids)?;
+            }
+            OperationKind::Rollback => return Err("PackageKit does not support rollback".into()),
+        }
+        self.transactions
+            .lock()
+            .unwrap()
+            .insert(package_id.clone(), tx.inner().path().to_string());
+        let res = transaction_handle(tx, |progress| {
+            log::info!(
+                "{} {} {}%",
+                progress.package_id,
+                progress.status,
+                progress.percentage
+            );
+            //TODO: show progress as total of all items
+            f(progress.percentage as f32);
+        });
+        self.transactions.lock().unwrap().remove(package_id);
+        res?;
+        Ok(())
+    }
 }
 
 impl Backend for Packagekit {
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            file_install: true,
+            cancel: true,
+            dry_run: true,
+            offline_update: true,
+            ..Default::default()
+        }
+    }
+
     fn load_caches(&mut self, refresh: bool) -> Result<(), Box<dyn Error>> {
         if refresh {
             let tx = self.transaction()?;
@@ -340,62 +480,93 @@ impl Backend for Packagekit {
         kind: OperationKind,
         package_id: &AppId,
         info: &AppInfo,
-        mut f: Box<dyn FnMut(f32) + 'static>,
-    ) -> Result<(), Box<dyn Error>> {
-        let mut package_names = Vec::with_capacity(info.pkgnames.len());
-        for pkgname in &info.pkgnames {
-            package_names.push(pkgname.as_str());
-        }
-        if package_names.is_empty() {
-            return Err(format!("{:?} missing package name", package_id).into());
-        }
-        let (_tx_details, tx_packages) = {
-            let tx = self.transaction()?;
-            log::info!("resolve packages for {:?}", package_names);
-            let filter = match kind {
-                OperationKind::Install | OperationKind::Update => {
-                    FilterKind::NotInstalled as u64
-                        | FilterKind::Newest as u64
-                        | FilterKind::Arch as u64
-                }
-                OperationKind::Uninstall => FilterKind::Installed as u64,
-            };
-            tx.resolve(filter, &package_names)?;
-            transaction_handle(tx, |_| {})?
-        };
-        let mut package_ids = Vec::with_capacity(package_names.len());
-        for tx_package in tx_packages.iter() {
-            package_ids.push(tx_package.package_id.as_str());
-        }
+        f: Box<dyn FnMut(f32) + 'static>,
+    ) -> Result<(), BackendError> {
+        self.operation_inner(kind, package_id, info, f)
+            .map_err(BackendError::from)
+    }
+
+    fn cancel(&self, package_id: &AppId) -> Result<(), Box<dyn Error>> {
+        let tx_path = self
+            .transactions
+            .lock()
+            .unwrap()
+            .get(package_id)
+            .cloned()
+            .ok_or_else(|| format!("no transaction in progress for {:?}", package_id))?;
+        log::info!("cancelling transaction {} for {:?}", tx_path, package_id);
+        let tx = TransactionProxyBlocking::builder(&self.connection)
+            .destination("org.freedesktop.PackageKit")?
+            .path(tx_path)?
+            .build()?;
+        tx.cancel()?;
+        Ok(())
+    }
+
+    fn simulate(
+        &self,
+        kind: OperationKind,
+        package_id: &AppId,
+        info: &AppInfo,
+    ) -> Result<Vec<String>, Box<dyn Error>> {
+        let resolved = self.resolve(kind, package_id, info)?;
+        let package_ids: Vec<&str> = resolved.iter().map(|x| x.as_str()).collect();
         let tx = self.transaction()?;
-        tx.set_hints(&["interactive=true"])?;
+        let simulate = TransactionFlag::Simulate as u64;
         match kind {
             OperationKind::Install => {
-                log::info!("installing packages {:?}", package_ids);
-                //TODO: transaction flags
-                tx.install_packages(TransactionFlag::OnlyTrusted as u64, &package_ids)?;
-            }
-            OperationKind::Uninstall => {
-                log::info!("uninstalling packages {:?}", package_ids);
-                //TODO: transaction flags?
-                tx.remove_packages(0, &package_ids, true, true)?;
+                tx.install_packages(simulate | TransactionFlag::OnlyTrusted as u64, &package_ids)?
             }
+            OperationKind::Uninstall => tx.remove_packages(simulate, &package_ids, true, true)?,
             OperationKind::Update => {
-                log::info!("updating packages {:?}", package_ids);
-                //TODO: transaction flags?
-                tx.update_packages(TransactionFlag::OnlyTrusted as u64, &package_ids)?;
+                tx.update_packages(simulate | TransactionFlag::OnlyTrusted as u64, &package_ids)?
             }
+            OperationKind::Rollback => return Err("PackageKit does not support rollback".into()),
         }
-        let _tx_packages = transaction_handle(tx, |progress| {
-            log::info!(
-                "{} {} {}%",
-                progress.package_id,
-                progress.status,
-                progress.percentage
-            );
-            //TODO: show progress as total of all items
-            f(progress.percentage as f32);
-        })?;
+        let (_tx_details, tx_packages) = transaction_handle(tx, |_| {})?;
+        let mut names: Vec<String> = Vec::with_capacity(tx_packages.len());
+        for tx_package in tx_packages {
+            let Some(name) = tx_package.package_id.split(';').next() else {
+                continue;
+            };
+            if !names.iter().any(|x| x == name) {
+                names.push(name.to_string());
+            }
+        }
+        Ok(names)
+    }
+
+    fn offline_update(&self) -> Result<(), Box<dyn Error>> {
+        let tx = self.transaction()?;
+        tx.get_updates(FilterKind::None as u64)?;
+        let (_tx_details, tx_packages) = transaction_handle(tx, |_| {})?;
+        let package_ids: Vec<&str> = tx_packages
+            .iter()
+            .map(|tx_package| tx_package.package_id.as_str())
+            .collect();
+        if package_ids.is_empty() {
+            log::info!("no updates to prepare for offline update");
+            return Ok(());
+        }
+
+        log::info!("downloading packages {:?} for offline update", package_ids);
+        let tx = self.transaction()?;
+        tx.set_hints(&["interactive=true"])?;
+        tx.update_packages(
+            TransactionFlag::OnlyTrusted as u64 | TransactionFlag::OnlyDownload as u64,
+            &package_ids,
+        )?;
+        transaction_handle(tx, |_| {})?;
+
+        // Prepared updates are installed after rebooting
+        // https://www.freedesktop.org/software/PackageKit/gtk-doc/Offline.html
+        self.connection.call_method(
+            Some("org.freedesktop.PackageKit"),
+            "/org/freedesktop/PackageKit",
+            Some("org.freedesktop.PackageKit.Offline"),
+            "Trigger",
+            &("reboot",),
+        )?;
         Ok(())
     }
 }
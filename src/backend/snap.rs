@@ -0,0 +1,1305 @@
+use cosmic::widget;
+use serde::Deserialize;
+use std::{
+    collections::HashMap,
+    env,
+    error::Error,
+    fmt::Write as _,
+    io::{Read, Write},
+    os::unix::net::UnixStream,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+use super::{Backend, BackendError, Capabilities, Package};
+use crate::{
+    app_info::{AppScreenshot, AppUrl, AppUrlKind},
+    AppIcon, AppId, AppInfo, AppKind, AppstreamCache, OperationKind, RichText,
+};
+
+/// Default location of the snapd REST API socket
+const SNAPD_SOCKET: &'static str = "/run/snapd.socket";
+
+/// Channel risk levels, used as the source IDs of the snap backend
+const RISKS: &'static [&'static str] = &["stable", "candidate", "beta", "edge"];
+
+/// How often the progress of a change is polled
+const CHANGE_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// How long a change may take before it is aborted
+const CHANGE_TIMEOUT: Duration = Duration::from_secs(60 * 60);
+
+// https://snapcraft.io/docs/snapd-api#heading--responses
+#[derive(Debug, Deserialize)]
+struct SnapdResponse {
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(rename = "status-code")]
+    status_code: u16,
+    #[serde(default)]
+    result: serde_json::Value,
+    change: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct SnapPublisher {
+    #[serde(default)]
+    display_name: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct SnapMedia {
+    #[serde(rename = "type")]
+    kind: String,
+    url: String,
+    width: Option<u32>,
+    height: Option<u32>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct SnapApp {
+    desktop_file: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SnapCategory {
+    name: String,
+}
+
+// https://snapcraft.io/docs/snapd-api#heading--snaps
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct SnapInfo {
+    name: String,
+    #[serde(default)]
+    title: String,
+    #[serde(default)]
+    summary: String,
+    #[serde(default)]
+    description: String,
+    #[serde(default)]
+    version: String,
+    #[serde(rename = "type", default)]
+    kind: String,
+    #[serde(default)]
+    publisher: SnapPublisher,
+    #[serde(default)]
+    media: Vec<SnapMedia>,
+    #[serde(default)]
+    apps: Vec<SnapApp>,
+    #[serde(default)]
+    categories: Vec<SnapCategory>,
+    #[serde(default)]
+    channels: HashMap<String, serde_json::Value>,
+    tracking_channel: Option<String>,
+    license: Option<String>,
+    website: Option<String>,
+    contact: Option<String>,
+}
+
+impl SnapInfo {
+    /// Risk level of the tracked channel, for installed snaps
+    fn tracking_risk(&self) -> &str {
+        // Channels are formatted as [track/]risk[/branch]
+        let channel = self.tracking_channel.as_deref().unwrap_or("stable");
+        channel
+            .split('/')
+            .find(|part| RISKS.contains(part))
+            .unwrap_or("stable")
+    }
+
+    /// Risk levels this snap has published revisions for, stable if unknown
+    fn risks(&self) -> Vec<&'static str> {
+        let mut risks = Vec::new();
+        for risk in RISKS {
+            if self
+                .channels
+                .keys()
+                .any(|channel| channel.split('/').any(|part| part == *risk))
+            {
+                risks.push(*risk);
+            }
+        }
+        if risks.is_empty() {
+            risks.push("stable");
+        }
+        risks
+    }
+
+    fn app_info(&self, risk: &str) -> AppInfo {
+        let mut icons = Vec::new();
+        let mut screenshots = Vec::new();
+        for media in self.media.iter() {
+            match media.kind.as_str() {
+                "icon" => icons.push(AppIcon::Remote(
+                    media.url.clone(),
+                    media.width,
+                    media.height,
+                    None,
+                )),
+                "screenshot" => screenshots.push(AppScreenshot {
+                    caption: String::new(),
+                    url: media.url.clone(),
+                }),
+                _ => {}
+            }
+        }
+        let desktop_ids = self
+            .apps
+            .iter()
+            .filter_map(|app| {
+                let desktop_file = app.desktop_file.as_ref()?;
+                Some(Path::new(desktop_file).file_name()?.to_str()?.to_string())
+            })
+            .collect();
+        let urls = [
+            (AppUrlKind::Homepage, &self.website),
+            (AppUrlKind::Contact, &self.contact),
+        ]
+        .into_iter()
+        .filter_map(|(kind, url_opt)| {
+            let url = url_opt.as_ref().filter(|x| !x.is_empty())?;
+            Some(AppUrl {
+                kind,
+                url: url.clone(),
+            })
+        })
+        .collect();
+        AppInfo {
+            source_id: risk.to_string(),
+            //TODO: translate
+            source_name: format!("Snap Store ({})", risk),
+            origin_opt: None,
+            kind: AppKind::DesktopApplication,
+            name: if self.title.is_empty() {
+                self.name.clone()
+            } else {
+                self.title.clone()
+            },
+            summary: self.summary.clone(),
+            developer_name: self.publisher.display_name.clone(),
+            description: RichText::from_plain(&self.description),
+            pkgnames: vec![self.name.clone()],
+            categories: self
+                .categories
+                .iter()
+                .map(|category| category.name.clone())
+                .collect(),
+            keywords: Vec::new(),
+            desktop_ids,
+            flatpak_refs: Vec::new(),
+            extends: Vec::new(),
+            provides: Vec::new(),
+            icons,
+            releases: Vec::new(),
+            screenshots,
+            content_rating_opt: None,
+            project_license_opt: self.license.clone().filter(|x| !x.is_empty()),
+            urls,
+            monthly_downloads: 0,
+        }
+    }
+}
+
+fn risk_cache<'a>(
+    appstream_caches: &'a mut [AppstreamCache],
+    risk: &str,
+) -> &'a mut AppstreamCache {
+    // Caches are created for every risk in Snap::new
+    appstream_caches
+        .iter_mut()
+        .find(|appstream_cache| appstream_cache.source_id == risk)
+        .unwrap()
+}
+
+/// Decode an HTTP/1.1 chunked transfer encoded body
+fn decode_chunked(mut data: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
+    let mut body = Vec::new();
+    loop {
+        let line_end = data
+            .windows(2)
+            .position(|x| x == b"\r\n")
+            .ok_or("missing chunk size")?;
+        let size_str = std::str::from_utf8(&data[..line_end])?;
+        // Chunk extensions are separated by a semicolon
+        let size_hex = size_str.split(';').next().unwrap_or_default().trim();
+        let size = usize::from_str_radix(size_hex, 16)?;
+        data = &data[line_end + 2..];
+        if size == 0 {
+            return Ok(body);
+        }
+        if data.len() < size {
+            return Err("truncated chunk".into());
+        }
+        body.extend_from_slice(&data[..size]);
+        data = data.get(size + 2..).ok_or("truncated chunk")?;
+    }
+}
+
+/// Categorize snapd error responses
+// https://snapcraft.io/docs/snapd-rest-api#heading--errors
+fn snapd_error(kind: &str, status_code: u16, detail: String) -> BackendError {
+    match kind {
+        "snap-not-found"
+        | "snap-not-installed"
+        | "snap-channel-not-available"
+        | "snap-revision-not-available"
+        | "option-not-found" => BackendError::NotFound(detail),
+        "login-required"
+        | "auth-cancelled"
+        | "two-factor-required"
+        | "two-factor-failed"
+        | "terms-not-accepted" => BackendError::AuthDenied(detail),
+        "network-timeout" | "dns-failure" => BackendError::Network(detail),
+        "insufficient-disk-space" => BackendError::NoSpace(detail),
+        "snap-already-installed"
+        | "snap-change-conflict"
+        | "snap-needs-classic"
+        | "snap-needs-devmode"
+        | "snap-not-classic" => BackendError::Conflict(detail),
+        "system-restart" | "daemon-restart" => BackendError::Unavailable(detail),
+        _ => match status_code {
+            401 | 403 => BackendError::AuthDenied(detail),
+            404 => BackendError::NotFound(detail),
+            409 => BackendError::Conflict(detail),
+            _ => BackendError::Other(detail),
+        },
+    }
+}
+
+#[derive(Debug)]
+pub struct Snap {
+    socket_path: PathBuf,
+    appstream_caches: Vec<AppstreamCache>,
+    // IDs of changes in progress by app, so they can be aborted
+    changes: Mutex<HashMap<AppId, String>>,
+    change_timeout: Duration,
+}
+
+impl Snap {
+    pub fn new(locale: &str) -> Result<Self, Box<dyn Error>> {
+        // Allow overriding the socket, so a fake snapd can be used for testing
+        match env::var_os("COSMIC_STORE_SNAPD_SOCKET") {
+            Some(socket_path) => Self::with_socket(socket_path, locale),
+            None => Self::with_socket(SNAPD_SOCKET, locale),
+        }
+    }
+
+    /// Connect to snapd on the specified socket
+    pub fn with_socket<P: AsRef<Path>>(
+        socket_path: P,
+        locale: &str,
+    ) -> Result<Self, Box<dyn Error>> {
+        let snap = Self {
+            socket_path: socket_path.as_ref().to_path_buf(),
+            appstream_caches: RISKS
+                .iter()
+                .map(|risk| {
+                    //TODO: translate
+                    AppstreamCache::new(
+                        risk.to_string(),
+                        format!("Snap Store ({})", risk),
+                        Vec::new(),
+                        Vec::new(),
+                        locale,
+                    )
+                })
+                .collect(),
+            changes: Mutex::new(HashMap::new()),
+            change_timeout: CHANGE_TIMEOUT,
+        };
+        // Make sure snapd is reachable before using this backend
+        snap.request("GET", "/v2/system-info", None)?;
+        Ok(snap)
+    }
+
+    fn request(
+        &self,
+        method: &str,
+        path: &str,
+        body_opt: Option<&serde_json::Value>,
+    ) -> Result<SnapdResponse, Box<dyn Error>> {
+        let mut stream = UnixStream::connect(&self.socket_path)?;
+        stream.set_read_timeout(Some(Duration::from_secs(60)))?;
+
+        let body = match body_opt {
+            Some(body) => serde_json::to_vec(body)?,
+            None => Vec::new(),
+        };
+        let mut request = format!("{method} {path} HTTP/1.1\r\n");
+        request.push_str("Host: localhost\r\n");
+        request.push_str("Connection: close\r\n");
+        // Allow snapd to show a polkit dialog for privileged actions
+        request.push_str("X-Allow-Interaction: true\r\n");
+        if body_opt.is_some() {
+            request.push_str("Content-Type: application/json\r\n");
+            write!(request, "Content-Length: {}\r\n", body.len())?;
+        }
+        request.push_str("\r\n");
+        stream.write_all(request.as_bytes())?;
+        stream.write_all(&body)?;
+
+        let mut data = Vec::new();
+        stream.read_to_end(&mut data)?;
+
+        let header_end = data
+            .windows(4)
+            .position(|x| x == b"\r\n\r\n")
+            .ok_or_else(|| format!("invalid response to {method} {path}: missing headers"))?;
+        let headers = std::str::from_utf8(&data[..header_end])?;
+        let mut chunked = false;
+        let mut content_length_opt = None;
+        for line in headers.lines().skip(1) {
+            if let Some((key, value)) = line.split_once(':') {
+                if key.trim().eq_ignore_ascii_case("transfer-encoding")
+                    && value.trim().eq_ignore_ascii_case("chunked")
+                {
+                    chunked = true;
+                } else if key.trim().eq_ignore_ascii_case("content-length") {
+                    content_length_opt = Some(value.trim().parse::<usize>()?);
+                }
+            }
+        }
+        let mut body = &data[header_end + 4..];
+        if let (false, Some(content_length)) = (chunked, content_length_opt) {
+            body = body
+                .get(..content_length)
+                .ok_or_else(|| format!("invalid response to {method} {path}: truncated body"))?;
+        }
+        let response: SnapdResponse = if chunked {
+            serde_json::from_slice(&decode_chunked(body)?)?
+        } else {
+            serde_json::from_slice(body)?
+        };
+
+        if response.kind == "error" {
+            let message = response.result["message"].as_str().unwrap_or_default();
+            let kind = response.result["kind"].as_str().unwrap_or_default();
+            let detail = format!(
+                "{method} {path} failed: {message} (kind {kind:?}, status {})",
+                response.status_code
+            );
+            return Err(Box::new(snapd_error(kind, response.status_code, detail)));
+        }
+
+        Ok(response)
+    }
+
+    fn request_snaps(&self, path: &str) -> Result<Vec<SnapInfo>, Box<dyn Error>> {
+        let response = self.request("GET", path, None)?;
+        Ok(serde_json::from_value(response.result)?)
+    }
+
+    fn find(&self, params: &[(&str, &str)]) -> Result<Vec<SnapInfo>, Box<dyn Error>> {
+        let url = reqwest::Url::parse_with_params("http://localhost/v2/find", params)?;
+        self.request_snaps(&format!(
+            "{}?{}",
+            url.path(),
+            url.query().unwrap_or_default()
+        ))
+    }
+
+    fn insert_snap(appstream_caches: &mut [AppstreamCache], snap: &SnapInfo, risk: &str) {
+        let appstream_cache = risk_cache(appstream_caches, risk);
+        let id = AppId::new(&snap.name);
+        appstream_cache
+            .pkgnames
+            .entry(snap.name.clone())
+            .or_default()
+            .insert(id.clone());
+        appstream_cache
+            .infos
+            .insert(id, Arc::new(snap.app_info(risk)));
+    }
+
+    fn snaps_to_packages(&self, snaps: Vec<SnapInfo>) -> Vec<Package> {
+        let mut packages = Vec::new();
+        let mut system_packages = Vec::new();
+        for snap in snaps {
+            if snap.kind != "app" {
+                system_packages.push((snap.name, snap.version));
+                continue;
+            }
+
+            let risk = snap.tracking_risk();
+            let id = AppId::new(&snap.name);
+            let info = match self
+                .appstream_caches
+                .iter()
+                .find(|appstream_cache| appstream_cache.source_id == risk)
+                .and_then(|appstream_cache| appstream_cache.infos.get(&id))
+            {
+                Some(some) => some.clone(),
+                None => Arc::new(snap.app_info(risk)),
+            };
+            let mut extra = HashMap::new();
+            if let Some(tracking_channel) = &snap.tracking_channel {
+                extra.insert("channel".to_string(), tracking_channel.clone());
+            }
+            packages.push(Package {
+                id,
+                //TODO: load icon of installed snap from /v2/icons
+                icon: widget::icon::from_name("package-x-generic")
+                    .size(128)
+                    .handle(),
+                info,
+                version: snap.version,
+                extra,
+            });
+        }
+
+        if !system_packages.is_empty() {
+            let appstream_cache = &self.appstream_caches[0];
+            let name = "System Packages".to_string();
+            let summary = format!(
+                "{} package{}",
+                system_packages.len(),
+                if system_packages.len() == 1 { "" } else { "s" }
+            );
+            let mut description = String::new();
+            let mut pkgnames = Vec::with_capacity(system_packages.len());
+            for (package_name, version) in system_packages {
+                let _ = writeln!(description, " * {}: {}", package_name, version);
+                pkgnames.push(package_name);
+            }
+            //TODO: translate
+            packages.push(Package {
+                id: AppId::system(),
+                icon: widget::icon::from_name("package-x-generic")
+                    .size(128)
+                    .handle(),
+                //TODO: fill in more AppInfo fields
+                info: Arc::new(AppInfo {
+                    source_id: appstream_cache.source_id.clone(),
+                    source_name: appstream_cache.source_name.clone(),
+                    origin_opt: None,
+                    kind: AppKind::Generic,
+                    name,
+                    summary,
+                    developer_name: String::new(),
+                    description: RichText::from_plain(&description),
+                    pkgnames,
+                    categories: Vec::new(),
+                    keywords: Vec::new(),
+                    desktop_ids: Vec::new(),
+                    flatpak_refs: Vec::new(),
+                    extends: Vec::new(),
+                    provides: Vec::new(),
+                    icons: Vec::new(),
+                    releases: Vec::new(),
+                    screenshots: Vec::new(),
+                    content_rating_opt: None,
+                    project_license_opt: None,
+                    urls: Vec::new(),
+                    monthly_downloads: 0,
+                }),
+                version: String::new(),
+                extra: HashMap::new(),
+            });
+        }
+
+        packages
+    }
+
+    fn load_listings(&self, appstream_caches: &mut [AppstreamCache]) -> Result<(), Box<dyn Error>> {
+        // Browse the store by section, as snapd does not allow listing all snaps
+        let sections: Vec<String> =
+            serde_json::from_value(self.request("GET", "/v2/sections", None)?.result)?;
+        for section in sections.iter() {
+            match self.find(&[("section", section.as_str())]) {
+                Ok(snaps) => {
+                    for snap in snaps.iter().filter(|snap| snap.kind == "app") {
+                        Self::insert_snap(appstream_caches, snap, "stable");
+                    }
+                }
+                Err(err) => {
+                    log::warn!("failed to find snaps in section {:?}: {}", section, err);
+                }
+            }
+        }
+
+        // Add all channels of installed snaps, so they can be switched
+        for installed in self.request_snaps("/v2/snaps")? {
+            if installed.kind != "app" {
+                continue;
+            }
+            match self.find(&[("name", installed.name.as_str())]) {
+                Ok(snaps) => {
+                    for snap in snaps.iter() {
+                        for risk in snap.risks() {
+                            Self::insert_snap(appstream_caches, snap, risk);
+                        }
+                    }
+                }
+                Err(err) => {
+                    log::warn!(
+                        "failed to find channels of snap {:?}: {}",
+                        installed.name,
+                        err
+                    );
+                    Self::insert_snap(appstream_caches, &installed, installed.tracking_risk());
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Poll a change until it is ready, reporting progress of its tasks
+    fn wait_change(
+        &self,
+        change_id: &str,
+        f: &mut Box<dyn FnMut(f32) + 'static>,
+    ) -> Result<(), Box<dyn Error>> {
+        let path = format!("/v2/changes/{}", change_id);
+        let deadline = Instant::now() + self.change_timeout;
+        loop {
+            let response = self.request("GET", &path, None)?;
+            let change = &response.result;
+
+            let mut done = 0.0;
+            let mut total = 0.0;
+            if let Some(tasks) = change["tasks"].as_array() {
+                for task in tasks {
+                    done += task["progress"]["done"].as_f64().unwrap_or(0.0);
+                    total += task["progress"]["total"].as_f64().unwrap_or(0.0);
+                }
+            }
+            if total > 0.0 {
+                f((done * 100.0 / total) as f32);
+            }
+
+            if change["ready"].as_bool().unwrap_or(false) {
+                return match change["status"].as_str() {
+                    Some("Done") => Ok(()),
+                    // Aborted changes are undone
+                    Some("Undone") => Err(Box::new(BackendError::Cancelled(format!(
+                        "change {} was aborted",
+                        change_id
+                    )))),
+                    status => Err(format!(
+                        "change {} finished with status {:?}: {}",
+                        change_id,
+                        status,
+                        change["err"].as_str().unwrap_or_default()
+                    )
+                    .into()),
+                };
+            }
+
+            if Instant::now() >= deadline {
+                // Abort the change so it does not continue in the background
+                if let Err(err) = self.request(
+                    "POST",
+                    &path,
+                    Some(&serde_json::json!({ "action": "abort" })),
+                ) {
+                    log::warn!("failed to abort change {}: {}", change_id, err);
+                }
+                return Err(format!(
+                    "change {} did not finish within {:?}",
+                    change_id, self.change_timeout
+                )
+                .into());
+            }
+
+            thread::sleep(CHANGE_POLL_INTERVAL);
+        }
+    }
+
+    fn operation_inner(
+        &self,
+        kind: OperationKind,
+        package_id: &AppId,
+        info: &AppInfo,
+        mut f: Box<dyn FnMut(f32) + 'static>,
+    ) -> Result<(), Box<dyn Error>> {
+        let Some(name) = info.pkgnames.first() else {
+            return Err(format!("{:?} missing snap name", package_id).into());
+        };
+        // The source ID is the channel risk level
+        let channel = info.source_id.as_str();
+        let path = format!("/v2/snaps/{}", name);
+        let body = match kind {
+            OperationKind::Install => {
+                // Only a missing snap means it is not installed, other errors are reported
+                let installed = match self.request("GET", &path, None) {
+                    Ok(_) => true,
+                    Err(err) => match err.downcast_ref::<BackendError>() {
+                        Some(BackendError::NotFound(_)) => false,
+                        _ => return Err(err),
+                    },
+                };
+                if installed {
+                    // Installing from another source switches the channel
+                    log::info!("switching snap {} to channel {}", name, channel);
+                    serde_json::json!({ "action": "refresh", "channel": channel })
+                } else {
+                    log::info!("installing snap {} from channel {}", name, channel);
+                    serde_json::json!({ "action": "install", "channel": channel })
+                }
+            }
+            OperationKind::Uninstall => {
+                log::info!("removing snap {}", name);
+                serde_json::json!({ "action": "remove" })
+            }
+            OperationKind::Update => {
+                log::info!("refreshing snap {} from channel {}", name, channel);
+                serde_json::json!({ "action": "refresh", "channel": channel })
+            }
+            OperationKind::Rollback => {
+                log::info!("reverting snap {}", name);
+                serde_json::json!({ "action": "revert" })
+            }
+        };
+        let response = self.request("POST", &path, Some(&body))?;
+        let Some(change_id) = response.change else {
+            return Err(format!("snapd did not return a change for {}", name).into());
+        };
+        self.changes
+            .lock()
+            .unwrap()
+            .insert(package_id.clone(), change_id.clone());
+        let res = self.wait_change(&change_id, &mut f);
+        self.changes.lock().unwrap().remove(package_id);
+        res
+    }
+}
+
+impl Backend for Snap {
+    fn capabilities(&self) -> Capabilities {
+        //TODO: support sideloading with /v2/snaps multipart upload
+        Capabilities {
+            cancel: true,
+            permissions: true,
+            rollback: true,
+            ..Default::default()
+        }
+    }
+
+    fn load_caches(&mut self, _refresh: bool) -> Result<(), Box<dyn Error>> {
+        // The snap store is queried live, so refreshing only reloads the listings
+        let mut appstream_caches = std::mem::take(&mut self.appstream_caches);
+        for appstream_cache in appstream_caches.iter_mut() {
+            appstream_cache.infos.clear();
+            appstream_cache.pkgnames.clear();
+        }
+        let res = self.load_listings(&mut appstream_caches);
+        self.appstream_caches = appstream_caches;
+        res
+    }
+
+    fn info_caches(&self) -> &[AppstreamCache] {
+        &self.appstream_caches
+    }
+
+    fn installed(&self) -> Result<Vec<Package>, Box<dyn Error>> {
+        let snaps = self.request_snaps("/v2/snaps")?;
+        Ok(self.snaps_to_packages(snaps))
+    }
+
+    fn updates(&self) -> Result<Vec<Package>, Box<dyn Error>> {
+        let snaps = self.find(&[("select", "refresh")])?;
+        Ok(self.snaps_to_packages(snaps))
+    }
+
+    fn operation(
+        &self,
+        kind: OperationKind,
+        package_id: &AppId,
+        info: &AppInfo,
+        f: Box<dyn FnMut(f32) + 'static>,
+    ) -> Result<(), BackendError> {
+        self.operation_inner(kind, package_id, info, f)
+            .map_err(BackendError::from)
+    }
+
+    fn cancel(&self, package_id: &AppId) -> Result<(), Box<dyn Error>> {
+        let change_id = self
+            .changes
+            .lock()
+            .unwrap()
+            .get(package_id)
+            .cloned()
+            .ok_or_else(|| format!("no change in progress for {:?}", package_id))?;
+        log::info!("aborting snap change {} for {:?}", change_id, package_id);
+        self.request(
+            "POST",
+            &format!("/v2/changes/{}", change_id),
+            Some(&serde_json::json!({ "action": "abort" })),
+        )?;
+        Ok(())
+    }
+
+    fn permissions(
+        &self,
+        package_id: &AppId,
+        info: &AppInfo,
+    ) -> Result<Vec<String>, Box<dyn Error>> {
+        let Some(name) = info.pkgnames.first() else {
+            return Err(format!("{:?} missing snap name", package_id).into());
+        };
+        // https://snapcraft.io/docs/snapd-api#heading--connections
+        let response = self.request("GET", &format!("/v2/connections?snap={}", name), None)?;
+        let mut permissions: Vec<String> = Vec::new();
+        if let Some(connections) = response.result["established"].as_array() {
+            for connection in connections {
+                // Only plugs of this snap grant it access to something
+                if connection["plug"]["snap"].as_str() != Some(name.as_str()) {
+                    continue;
+                }
+                let Some(interface) = connection["interface"].as_str() else {
+                    continue;
+                };
+                if !permissions.iter().any(|x| x == interface) {
+                    permissions.push(interface.to_string());
+                }
+            }
+        }
+        Ok(permissions)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        io::{BufRead, BufReader},
+        os::unix::net::UnixListener,
+        process,
+    };
+
+    /// Requests received by the fake snapd, as method, path, and body
+    type Requests = Arc<Mutex<Vec<(String, String, String)>>>;
+
+    /// How the fake snapd encodes response bodies
+    #[derive(Clone, Copy)]
+    enum Encoding {
+        ContentLength,
+        Chunked,
+    }
+
+    /// Serve snapd responses on a Unix socket, returning the socket path and received requests.
+    /// The handler returns the status code and JSON of the response for a method and path.
+    fn fake_snapd<F>(name: &str, encoding: Encoding, handler: F) -> (PathBuf, Requests)
+    where
+        F: Fn(&str, &str) -> (u16, serde_json::Value) + Send + 'static,
+    {
+        let socket_path = env::temp_dir().join(format!(
+            "cosmic-store-snapd-{}-{}.socket",
+            process::id(),
+            name
+        ));
+        let _ = std::fs::remove_file(&socket_path);
+        let listener = UnixListener::bind(&socket_path).unwrap();
+        let requests = Requests::default();
+        let thread_requests = requests.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+                let mut parts = request_line.split_whitespace();
+                let method = parts.next().unwrap().to_string();
+                let path = parts.next().unwrap().to_string();
+                let mut content_length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line == "\r\n" {
+                        break;
+                    }
+                    if let Some(value) = line.strip_prefix("Content-Length:") {
+                        content_length = value.trim().parse().unwrap();
+                    }
+                }
+                let mut body = vec![0; content_length];
+                reader.read_exact(&mut body).unwrap();
+                thread_requests.lock().unwrap().push((
+                    method.clone(),
+                    path.clone(),
+                    String::from_utf8(body).unwrap(),
+                ));
+
+                let (status_code, result) = handler(&method, &path);
+                let kind = if status_code >= 400 {
+                    "error"
+                } else if status_code == 202 {
+                    "async"
+                } else {
+                    "sync"
+                };
+                let mut json = serde_json::json!({
+                    "type": kind,
+                    "status-code": status_code,
+                    "result": result,
+                });
+                if kind == "async" {
+                    json["change"] = result["id"].clone();
+                }
+                let json = json.to_string();
+                let mut response = format!(
+                    "HTTP/1.1 {} Fake\r\nContent-Type: application/json\r\n",
+                    status_code
+                );
+                match encoding {
+                    Encoding::ContentLength => {
+                        response.push_str(&format!("Content-Length: {}\r\n\r\n", json.len()));
+                        response.push_str(&json);
+                    }
+                    Encoding::Chunked => {
+                        response.push_str("Transfer-Encoding: chunked\r\n\r\n");
+                        // Split into several chunks, with an extension on the first
+                        let (first, rest) = json.split_at(json.len() / 2);
+                        response.push_str(&format!("{:x};ext=1\r\n{}\r\n", first.len(), first));
+                        for chunk in rest.as_bytes().chunks(7) {
+                            let chunk = std::str::from_utf8(chunk).unwrap();
+                            response.push_str(&format!("{:x}\r\n{}\r\n", chunk.len(), chunk));
+                        }
+                        response.push_str("0\r\n\r\n");
+                    }
+                }
+                stream.write_all(response.as_bytes()).unwrap();
+            }
+        });
+        (socket_path, requests)
+    }
+
+    fn snap_json(name: &str, kind: &str) -> serde_json::Value {
+        serde_json::json!({
+            "name": name,
+            "title": name.to_uppercase(),
+            "summary": "Summary",
+            "version": "1.0",
+            "type": kind,
+            "publisher": { "display-name": "Publisher" },
+            "tracking-channel": "latest/beta",
+            "channels": { "latest/stable": {}, "latest/beta": {} },
+            "license": "MIT",
+        })
+    }
+
+    fn not_found() -> (u16, serde_json::Value) {
+        (
+            404,
+            serde_json::json!({ "kind": "snap-not-found", "message": "snap not found" }),
+        )
+    }
+
+    #[test]
+    fn content_length_responses() {
+        let (socket_path, requests) = fake_snapd(
+            "content-length",
+            Encoding::ContentLength,
+            |_method, path| match path {
+                "/v2/system-info" => (200, serde_json::json!({ "series": "16" })),
+                "/v2/snaps" => (
+                    200,
+                    serde_json::json!([snap_json("app", "app"), snap_json("core22", "base")]),
+                ),
+                _ => not_found(),
+            },
+        );
+        let snap = Snap::with_socket(&socket_path, "en").unwrap();
+
+        let packages = snap.installed().unwrap();
+        assert_eq!(packages.len(), 2);
+        assert_eq!(packages[0].id, AppId::new("app"));
+        assert_eq!(packages[0].info.source_id, "beta");
+        assert_eq!(packages[0].info.name, "APP");
+        assert_eq!(packages[0].extra["channel"], "latest/beta");
+        assert_eq!(packages[1].id, AppId::system());
+        assert_eq!(packages[1].info.pkgnames, ["core22"]);
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests[0].1, "/v2/system-info");
+        assert_eq!(requests[1].1, "/v2/snaps");
+    }
+
+    #[test]
+    fn chunked_responses() {
+        let (socket_path, _requests) =
+            fake_snapd("chunked", Encoding::Chunked, |_method, path| match path {
+                "/v2/system-info" => (200, serde_json::json!({})),
+                "/v2/sections" => (200, serde_json::json!(["games"])),
+                "/v2/find?section=games" => (200, serde_json::json!([snap_json("game", "app")])),
+                "/v2/snaps" => (200, serde_json::json!([snap_json("app", "app")])),
+                "/v2/find?name=app" => (200, serde_json::json!([snap_json("app", "app")])),
+                "/v2/find?select=refresh" => (200, serde_json::json!([snap_json("app", "app")])),
+                _ => not_found(),
+            });
+        let mut snap = Snap::with_socket(&socket_path, "en").unwrap();
+        snap.load_caches(false).unwrap();
+
+        let infos = |risk: &str| {
+            let appstream_cache = snap
+                .info_caches()
+                .iter()
+                .find(|x| x.source_id == risk)
+                .unwrap();
+            let mut names: Vec<_> = appstream_cache
+                .infos
+                .keys()
+                .map(|id| id.raw().to_string())
+                .collect();
+            names.sort();
+            names
+        };
+        assert_eq!(infos("stable"), ["app", "game"]);
+        assert_eq!(infos("beta"), ["app"]);
+        assert!(infos("edge").is_empty());
+
+        let updates = snap.updates().unwrap();
+        assert_eq!(updates.len(), 1);
+        assert_eq!(updates[0].version, "1.0");
+        assert_eq!(updates[0].info.project_license_opt.as_deref(), Some("MIT"));
+    }
+
+    #[test]
+    fn install_polls_change() {
+        let (socket_path, requests) =
+            fake_snapd("install", Encoding::ContentLength, |method, path| {
+                match (method, path) {
+                    ("GET", "/v2/system-info") => (200, serde_json::json!({})),
+                    ("POST", "/v2/snaps/app") => (202, serde_json::json!({ "id": "7" })),
+                    ("GET", "/v2/changes/7") => (
+                        200,
+                        serde_json::json!({
+                            "id": "7",
+                            "ready": true,
+                            "status": "Done",
+                            "tasks": [
+                                { "progress": { "done": 1, "total": 4 } },
+                                { "progress": { "done": 3, "total": 4 } },
+                            ],
+                        }),
+                    ),
+                    _ => not_found(),
+                }
+            });
+        let snap = Snap::with_socket(&socket_path, "en").unwrap();
+        let info: SnapInfo = serde_json::from_value(snap_json("app", "app")).unwrap();
+        let progress = Arc::new(Mutex::new(Vec::new()));
+        let callback_progress = progress.clone();
+        snap.operation(
+            OperationKind::Install,
+            &AppId::new("app"),
+            &info.app_info("edge"),
+            Box::new(move |x| callback_progress.lock().unwrap().push(x)),
+        )
+        .unwrap();
+        assert_eq!(*progress.lock().unwrap(), [50.0]);
+
+        let requests = requests.lock().unwrap();
+        let (_, _, body) = requests
+            .iter()
+            .find(|(method, _, _)| method == "POST")
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_str(body).unwrap();
+        assert_eq!(
+            body,
+            serde_json::json!({ "action": "install", "channel": "edge" })
+        );
+    }
+
+    #[test]
+    fn install_checks_installed() {
+        // Installed snaps switch channels
+        let (socket_path, requests) = fake_snapd(
+            "installed",
+            Encoding::ContentLength,
+            |method, path| match (method, path) {
+                ("GET", "/v2/system-info") => (200, serde_json::json!({})),
+                ("GET", "/v2/snaps/app") => (200, snap_json("app", "app")),
+                ("POST", "/v2/snaps/app") => (202, serde_json::json!({ "id": "3" })),
+                ("GET", "/v2/changes/3") => (
+                    200,
+                    serde_json::json!({ "id": "3", "ready": true, "status": "Done" }),
+                ),
+                _ => not_found(),
+            },
+        );
+        let snap = Snap::with_socket(&socket_path, "en").unwrap();
+        let info: SnapInfo = serde_json::from_value(snap_json("app", "app")).unwrap();
+        snap.operation(
+            OperationKind::Install,
+            &AppId::new("app"),
+            &info.app_info("candidate"),
+            Box::new(|_| {}),
+        )
+        .unwrap();
+        {
+            let requests = requests.lock().unwrap();
+            let (_, _, body) = requests
+                .iter()
+                .find(|(method, _, _)| method == "POST")
+                .unwrap();
+            let body: serde_json::Value = serde_json::from_str(body).unwrap();
+            assert_eq!(
+                body,
+                serde_json::json!({ "action": "refresh", "channel": "candidate" })
+            );
+        }
+
+        // Other errors are not mistaken for the snap not being installed
+        let (socket_path, requests) = fake_snapd(
+            "install-error",
+            Encoding::ContentLength,
+            |method, path| match (method, path) {
+                ("GET", "/v2/system-info") => (200, serde_json::json!({})),
+                ("GET", "/v2/snaps/app") => {
+                    (500, serde_json::json!({ "message": "internal error" }))
+                }
+                _ => (202, serde_json::json!({ "id": "4" })),
+            },
+        );
+        let snap = Snap::with_socket(&socket_path, "en").unwrap();
+        let err = snap
+            .operation(
+                OperationKind::Install,
+                &AppId::new("app"),
+                &info.app_info("stable"),
+                Box::new(|_| {}),
+            )
+            .unwrap_err();
+        assert!(matches!(err, BackendError::Other(_)), "{:?}", err);
+        assert!(!requests
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|(method, _, _)| method == "POST"));
+    }
+
+    #[test]
+    fn change_timeout() {
+        let (socket_path, requests) =
+            fake_snapd("timeout", Encoding::ContentLength, |method, path| {
+                match (method, path) {
+                    ("GET", "/v2/system-info") => (200, serde_json::json!({})),
+                    ("POST", "/v2/snaps/app") => (202, serde_json::json!({ "id": "5" })),
+                    ("GET", "/v2/changes/5") => (
+                        200,
+                        serde_json::json!({ "id": "5", "ready": false, "status": "Doing" }),
+                    ),
+                    ("POST", "/v2/changes/5") => (200, serde_json::json!({ "id": "5" })),
+                    _ => not_found(),
+                }
+            });
+        let mut snap = Snap::with_socket(&socket_path, "en").unwrap();
+        snap.change_timeout = Duration::from_millis(500);
+        let info: SnapInfo = serde_json::from_value(snap_json("app", "app")).unwrap();
+        let err = snap
+            .operation(
+                OperationKind::Uninstall,
+                &AppId::new("app"),
+                &info.app_info("stable"),
+                Box::new(|_| {}),
+            )
+            .unwrap_err();
+        assert!(err.detail().contains("did not finish"), "{:?}", err);
+
+        let requests = requests.lock().unwrap();
+        let (_, path, body) = requests.last().unwrap();
+        assert_eq!(path, "/v2/changes/5");
+        let body: serde_json::Value = serde_json::from_str(body).unwrap();
+        assert_eq!(body, serde_json::json!({ "action": "abort" }));
+    }
+
+    #[test]
+    fn cancel_aborts_change() {
+        let (socket_path, requests) =
+            fake_snapd("cancel", Encoding::ContentLength, |method, path| {
+                match (method, path) {
+                    ("GET", "/v2/system-info") => (200, serde_json::json!({})),
+                    ("POST", "/v2/snaps/app") => (202, serde_json::json!({ "id": "8" })),
+                    ("GET", "/v2/changes/8") => (
+                        200,
+                        serde_json::json!({ "id": "8", "ready": true, "status": "Undone" }),
+                    ),
+                    ("POST", "/v2/changes/8") => (200, serde_json::json!({ "id": "8" })),
+                    _ => not_found(),
+                }
+            });
+        let snap = Snap::with_socket(&socket_path, "en").unwrap();
+        let id = AppId::new("app");
+        assert!(snap.cancel(&id).is_err());
+
+        snap.changes
+            .lock()
+            .unwrap()
+            .insert(id.clone(), "8".to_string());
+        snap.cancel(&id).unwrap();
+        {
+            let requests = requests.lock().unwrap();
+            let (_, path, body) = requests.last().unwrap();
+            assert_eq!(path, "/v2/changes/8");
+            let body: serde_json::Value = serde_json::from_str(body).unwrap();
+            assert_eq!(body, serde_json::json!({ "action": "abort" }));
+        }
+
+        // The aborted change is undone
+        let info: SnapInfo = serde_json::from_value(snap_json("app", "app")).unwrap();
+        let err = snap
+            .operation(
+                OperationKind::Update,
+                &id,
+                &info.app_info("stable"),
+                Box::new(|_| {}),
+            )
+            .unwrap_err();
+        assert!(matches!(err, BackendError::Cancelled(_)), "{:?}", err);
+        assert!(snap.changes.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn rollback_and_permissions() {
+        let (socket_path, requests) = fake_snapd("rollback", Encoding::Chunked, |method, path| {
+            match (method, path) {
+                ("GET", "/v2/system-info") => (200, serde_json::json!({})),
+                ("POST", "/v2/snaps/app") => (202, serde_json::json!({ "id": "9" })),
+                ("GET", "/v2/changes/9") => (
+                    200,
+                    serde_json::json!({ "id": "9", "ready": true, "status": "Done" }),
+                ),
+                ("GET", "/v2/connections?snap=app") => (
+                    200,
+                    serde_json::json!({
+                        "established": [
+                            {
+                                "interface": "network",
+                                "plug": { "snap": "app", "plug": "network" },
+                                "slot": { "snap": "snapd", "slot": "network" },
+                            },
+                            {
+                                "interface": "home",
+                                "plug": { "snap": "app", "plug": "home" },
+                                "slot": { "snap": "snapd", "slot": "home" },
+                            },
+                            {
+                                "interface": "network",
+                                "plug": { "snap": "app", "plug": "network-2" },
+                                "slot": { "snap": "snapd", "slot": "network" },
+                            },
+                            {
+                                "interface": "content",
+                                "plug": { "snap": "other", "plug": "themes" },
+                                "slot": { "snap": "app", "slot": "themes" },
+                            },
+                        ],
+                    }),
+                ),
+                _ => not_found(),
+            }
+        });
+        let snap = Snap::with_socket(&socket_path, "en").unwrap();
+        let info: SnapInfo = serde_json::from_value(snap_json("app", "app")).unwrap();
+        let info = info.app_info("stable");
+        snap.operation(
+            OperationKind::Rollback,
+            &AppId::new("app"),
+            &info,
+            Box::new(|_| {}),
+        )
+        .unwrap();
+        {
+            let requests = requests.lock().unwrap();
+            let (_, _, body) = requests
+                .iter()
+                .find(|(method, _, _)| method == "POST")
+                .unwrap();
+            let body: serde_json::Value = serde_json::from_str(body).unwrap();
+            assert_eq!(body, serde_json::json!({ "action": "revert" }));
+        }
+
+        assert_eq!(
+            snap.permissions(&AppId::new("app"), &info).unwrap(),
+            ["network", "home"]
+        );
+    }
+
+    #[test]
+    fn error_responses() {
+        let (socket_path, _requests) =
+            fake_snapd("error", Encoding::Chunked, |method, path| {
+                match (method, path) {
+                    ("GET", "/v2/system-info") => (200, serde_json::json!({})),
+                    ("POST", "/v2/snaps/app") => (
+                        409,
+                        serde_json::json!({
+                            "kind": "snap-change-conflict",
+                            "message": "snap has changes in progress",
+                        }),
+                    ),
+                    _ => not_found(),
+                }
+            });
+        let snap = Snap::with_socket(&socket_path, "en").unwrap();
+        let info: SnapInfo = serde_json::from_value(snap_json("app", "app")).unwrap();
+        let err = snap
+            .operation(
+                OperationKind::Uninstall,
+                &AppId::new("app"),
+                &info.app_info("stable"),
+                Box::new(|_| {}),
+            )
+            .unwrap_err();
+        assert!(matches!(err, BackendError::Conflict(_)), "{:?}", err);
+        assert!(err.detail().contains("snap has changes in progress"));
+    }
+
+    #[test]
+    fn error_kinds() {
+        for (kind, status_code, expected) in [
+            ("snap-not-found", 404, BackendError::NotFound(String::new())),
+            (
+                "login-required",
+                401,
+                BackendError::AuthDenied(String::new()),
+            ),
+            ("network-timeout", 400, BackendError::Network(String::new())),
+            (
+                "insufficient-disk-space",
+                507,
+                BackendError::NoSpace(String::new()),
+            ),
+            (
+                "snap-needs-classic",
+                400,
+                BackendError::Conflict(String::new()),
+            ),
+            (
+                "daemon-restart",
+                200,
+                BackendError::Unavailable(String::new()),
+            ),
+            // Unknown kinds fall back to the status code
+            ("", 403, BackendError::AuthDenied(String::new())),
+            ("", 404, BackendError::NotFound(String::new())),
+            ("other", 409, BackendError::Conflict(String::new())),
+            ("", 500, BackendError::Other(String::new())),
+        ] {
+            assert_eq!(
+                snapd_error(kind, status_code, String::new()),
+                expected,
+                "{:?} {}",
+                kind,
+                status_code
+            );
+        }
+    }
+
+    #[test]
+    fn chunked_decoding() {
+        assert_eq!(
+            decode_chunked(b"3\r\nabc\r\n2;x=y\r\nde\r\n0\r\n\r\n").unwrap(),
+            b"abcde"
+        );
+        assert!(decode_chunked(b"5\r\nabc").is_err());
+        assert!(decode_chunked(b"abc").is_err());
+    }
+}
@@ -0,0 +1,231 @@
+use appstream::xmltree;
+use quick_xml::{
+    events::{BytesStart, Event},
+    Reader,
+};
+use std::{collections::HashMap, error::Error, io::BufRead, str};
+
+// Elements of description markup that are shown inline with text
+const INLINE_ELEMENTS: &[&str] = &["b", "code", "em", "i", "strong"];
+
+enum Step {
+    Child(xmltree::Element),
+    End,
+    Skip,
+    Text(String),
+}
+
+fn element_start(start: &BytesStart) -> Result<xmltree::Element, Box<dyn Error>> {
+    let mut element = xmltree::Element::new(str::from_utf8(start.local_name().as_ref())?);
+    for attr_res in start.attributes() {
+        let attr = attr_res?;
+        // Keys use the local name, like xmltree, so xml:lang is stored as lang
+        element.attributes.insert(
+            str::from_utf8(attr.key.local_name().as_ref())?.to_string(),
+            attr.unescape_value()?.into_owned(),
+        );
+    }
+    Ok(element)
+}
+
+fn is_inline(node_opt: Option<&xmltree::XMLNode>) -> bool {
+    match node_opt {
+        Some(xmltree::XMLNode::Element(element)) => {
+            INLINE_ELEMENTS.contains(&element.name.as_str())
+        }
+        Some(xmltree::XMLNode::Text(text)) => !text.trim().is_empty(),
+        _ => false,
+    }
+}
+
+/// Remove whitespace between elements like xmltree, except next to inline content where it
+/// separates words, as in `<p><em>a</em> <em>b</em></p>`
+fn remove_whitespace(element: &mut xmltree::Element) {
+    let keep: Vec<bool> = (0..element.children.len())
+        .map(|i| match &element.children[i] {
+            xmltree::XMLNode::Text(text) if text.trim().is_empty() => {
+                is_inline(i.checked_sub(1).and_then(|i| element.children.get(i)))
+                    || is_inline(element.children.get(i + 1))
+            }
+            _ => true,
+        })
+        .collect();
+    let mut keep = keep.into_iter();
+    element.children.retain(|_| keep.next().unwrap_or(true));
+}
+
+fn build<R: BufRead>(
+    reader: &mut Reader<R>,
+    mut element: xmltree::Element,
+    buf: &mut Vec<u8>,
+) -> Result<xmltree::Element, Box<dyn Error>> {
+    loop {
+        buf.clear();
+        // Events borrow the buffer, so they are converted before recursing
+        let step = match reader.read_event_into(buf)? {
+            Event::Start(start) => Step::Child(element_start(&start)?),
+            Event::End(_) => Step::End,
+            Event::Text(text) => Step::Text(text.unescape()?.into_owned()),
+            Event::CData(cdata) => Step::Text(String::from_utf8(cdata.into_inner().into_owned())?),
+            Event::Eof => {
+                return Err(format!("unexpected end of file in {:?} element", element.name).into())
+            }
+            _ => Step::Skip,
+        };
+        match step {
+            Step::Child(child) => {
+                let child = build(reader, child, buf)?;
+                element.children.push(xmltree::XMLNode::Element(child));
+            }
+            Step::End => {
+                remove_whitespace(&mut element);
+                return Ok(element);
+            }
+            Step::Skip => {}
+            Step::Text(text) => match element.children.last_mut() {
+                Some(xmltree::XMLNode::Text(last)) => last.push_str(&text),
+                _ => element.children.push(xmltree::XMLNode::Text(text)),
+            },
+        }
+    }
+}
+
+/// Reads elements of an appstream collection one at a time, instead of parsing the whole document
+pub struct ComponentReader<R: BufRead> {
+    reader: Reader<R>,
+    buf: Vec<u8>,
+    /// Attributes of the root collection element
+    pub attributes: HashMap<String, String>,
+    error_opt: Option<String>,
+}
+
+impl<R: BufRead> ComponentReader<R> {
+    pub fn new(reader: R) -> Result<Self, Box<dyn Error>> {
+        let mut reader = Reader::from_reader(reader);
+        reader.expand_empty_elements(true);
+        let mut buf = Vec::new();
+        let root = loop {
+            buf.clear();
+            match reader.read_event_into(&mut buf)? {
+                Event::Start(start) => break element_start(&start)?,
+                Event::Eof => return Err("missing collection element".into()),
+                _ => {}
+            }
+        };
+        if root.name != "components" {
+            return Err(format!("expected collection element, found {:?}", root.name).into());
+        }
+        Ok(Self {
+            reader,
+            buf,
+            attributes: root.attributes.into_iter().collect(),
+            error_opt: None,
+        })
+    }
+
+    /// Error that stopped iteration early, if any
+    pub fn take_error(&mut self) -> Option<String> {
+        self.error_opt.take()
+    }
+
+    fn next_element(&mut self) -> Result<Option<xmltree::Element>, Box<dyn Error>> {
+        loop {
+            self.buf.clear();
+            let step = match self.reader.read_event_into(&mut self.buf)? {
+                Event::Start(start) => Step::Child(element_start(&start)?),
+                Event::End(_) | Event::Eof => Step::End,
+                _ => Step::Skip,
+            };
+            match step {
+                Step::Child(child) => {
+                    return Ok(Some(build(&mut self.reader, child, &mut self.buf)?));
+                }
+                Step::End => return Ok(None),
+                Step::Skip | Step::Text(_) => {}
+            }
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for ComponentReader<R> {
+    type Item = xmltree::Element;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.error_opt.is_some() {
+            return None;
+        }
+        match self.next_element() {
+            Ok(element_opt) => element_opt,
+            Err(err) => {
+                self.error_opt = Some(err.to_string());
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn components(xml: &str) -> Vec<xmltree::Element> {
+        let mut component_reader = ComponentReader::new(xml.as_bytes()).unwrap();
+        let components = (&mut component_reader).collect();
+        assert_eq!(component_reader.take_error(), None);
+        components
+    }
+
+    fn texts(element: &xmltree::Element) -> Vec<&str> {
+        element
+            .children
+            .iter()
+            .filter_map(|child| match child {
+                xmltree::XMLNode::Text(text) => Some(text.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn whitespace() {
+        let components = components(concat!(
+            "<components version=\"0.16\">\n",
+            "  <component>\n",
+            "    <id>org.example.App</id>\n",
+            "    <description>\n",
+            "      <p><em>a</em> <em>b</em></p>\n",
+            "      <p>Text <code>c</code> </p>\n",
+            "      <ul>\n",
+            "        <li> </li>\n",
+            "      </ul>\n",
+            "    </description>\n",
+            "  </component>\n",
+            "</components>\n",
+        ));
+        assert_eq!(components.len(), 1);
+        let component = &components[0];
+        // Whitespace between elements without text is removed
+        assert!(texts(component).is_empty());
+        assert_eq!(
+            component.get_child("id").unwrap().get_text().unwrap(),
+            "org.example.App"
+        );
+        let description = component.get_child("description").unwrap();
+        assert!(texts(description).is_empty());
+        assert_eq!(description.children.len(), 3);
+        // Whitespace next to inline content is kept
+        let paragraphs: Vec<_> = description
+            .children
+            .iter()
+            .filter_map(|child| match child {
+                xmltree::XMLNode::Element(element) if element.name == "p" => Some(element),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(texts(paragraphs[0]), [" "]);
+        assert_eq!(texts(paragraphs[1]), ["Text ", " "]);
+        let list = description.get_child("ul").unwrap();
+        assert!(texts(list).is_empty());
+        assert!(list.get_child("li").unwrap().children.is_empty());
+    }
+}
@@ -5,9 +5,19 @@ use cosmic::{
     theme,
 };
 use serde::{Deserialize, Serialize};
+use std::{fs, path::PathBuf};
+
+use crate::{AppInfo, LicenseKind};
 
 pub const CONFIG_VERSION: u64 = 1;
 
+/// Ages that can be chosen as the parental controls limit
+pub const PARENTAL_AGES: &'static [u32] = &[3, 7, 12, 16, 18];
+
+/// System-wide parental controls, which override the user setting and cannot be changed in
+/// the app. Standard users cannot modify it when it is owned by root.
+pub const PARENTAL_POLICY_PATH: &'static str = "/etc/cosmic-store/parental-controls.conf";
+
 #[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
 pub enum AppTheme {
     Dark,
@@ -25,15 +35,261 @@ impl AppTheme {
     }
 }
 
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct ParentalControls {
+    /// Apps rated above this age cannot be installed
+    pub max_age_opt: Option<u32>,
+    /// Also hide apps rated above the age when browsing and searching
+    pub hide: bool,
+    /// Allow apps without a content rating when an age limit is set
+    #[serde(default)]
+    pub allow_unrated: bool,
+}
+
+impl ParentalControls {
+    /// Load the system policy, which has lines like `max-age = 12`, `hide = true`, and
+    /// `allow-unrated = false`
+    pub fn policy() -> Option<Self> {
+        match fs::read_to_string(PARENTAL_POLICY_PATH) {
+            Ok(data) => Some(Self::parse_policy(&data)),
+            Err(err) => {
+                if err.kind() != std::io::ErrorKind::NotFound {
+                    log::warn!("failed to read {:?}: {}", PARENTAL_POLICY_PATH, err);
+                }
+                None
+            }
+        }
+    }
+
+    fn parse_policy(data: &str) -> Self {
+        let mut policy = Self::default();
+        for line in data.lines().map(|x| x.trim()) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                log::warn!("invalid line in {:?}: {:?}", PARENTAL_POLICY_PATH, line);
+                continue;
+            };
+            match (key.trim(), value.trim()) {
+                ("max-age", "none") => policy.max_age_opt = None,
+                ("max-age", value) => match value.parse() {
+                    Ok(age) => policy.max_age_opt = Some(age),
+                    Err(err) => {
+                        log::warn!("invalid max-age in {:?}: {}", PARENTAL_POLICY_PATH, err)
+                    }
+                },
+                ("hide", value) => match value.parse() {
+                    Ok(hide) => policy.hide = hide,
+                    Err(err) => log::warn!("invalid hide in {:?}: {}", PARENTAL_POLICY_PATH, err),
+                },
+                ("allow-unrated", value) => match value.parse() {
+                    Ok(allow_unrated) => policy.allow_unrated = allow_unrated,
+                    Err(err) => {
+                        log::warn!(
+                            "invalid allow-unrated in {:?}: {}",
+                            PARENTAL_POLICY_PATH,
+                            err
+                        )
+                    }
+                },
+                (key, _) => log::warn!("unknown key in {:?}: {:?}", PARENTAL_POLICY_PATH, key),
+            }
+        }
+        policy
+    }
+
+    /// Check if an app may be installed
+    pub fn allows(&self, info: &AppInfo) -> bool {
+        match (self.max_age_opt, &info.content_rating_opt) {
+            (Some(max_age), Some(content_rating)) => content_rating.minimum_age() <= max_age,
+            (Some(_max_age), None) => self.allow_unrated,
+            (None, _) => true,
+        }
+    }
+
+    /// Check if an app is shown when browsing and searching
+    pub fn shows(&self, info: &AppInfo) -> bool {
+        !self.hide || self.allows(info)
+    }
+}
+
+/// Filters for apps that are not installed, applied when browsing and searching
+#[derive(Clone, Copy, Debug, Default)]
+pub struct AppFilter {
+    pub parental_controls: ParentalControls,
+    pub hide_proprietary: bool,
+}
+
+impl AppFilter {
+    /// Check if an app that is not installed is shown
+    pub fn shows(&self, info: &AppInfo) -> bool {
+        self.parental_controls.shows(info)
+            && !(self.hide_proprietary && info.license_kind() != LicenseKind::Free)
+    }
+}
+
 #[derive(Clone, CosmicConfigEntry, Debug, Deserialize, Eq, PartialEq, Serialize)]
 pub struct Config {
     pub app_theme: AppTheme,
+    /// Directory where AppImages are managed, defaults to ~/Applications
+    pub appimage_dir: Option<PathBuf>,
+    /// Backends that will not be loaded
+    pub disabled_backends: Vec<String>,
+    /// Preferred order of backends, backends not listed come after these. This overrides the
+    /// built-in source priorities when choosing which source of an app is shown first.
+    pub backend_order: Vec<String>,
+    /// Age restriction, unless overridden by the system policy
+    pub parental_controls: ParentalControls,
+    /// Hide apps that are not installed and are proprietary or have an unknown license
+    pub hide_proprietary: bool,
+}
+
+impl Config {
+    pub fn backend_enabled(&self, backend_name: &str) -> bool {
+        !self.disabled_backends.iter().any(|x| x == backend_name)
+    }
+
+    /// Position of backend in preferred order, lower is preferred
+    pub fn backend_rank(&self, backend_name: &str) -> usize {
+        self.backend_order
+            .iter()
+            .position(|x| x == backend_name)
+            .unwrap_or(self.backend_order.len())
+    }
+
+    pub fn appimage_dir(&self) -> PathBuf {
+        match &self.appimage_dir {
+            Some(dir) => dir.clone(),
+            None => dirs::home_dir().unwrap_or_default().join("Applications"),
+        }
+    }
 }
 
 impl Default for Config {
     fn default() -> Self {
         Self {
             app_theme: AppTheme::System,
+            appimage_dir: None,
+            disabled_backends: Vec::new(),
+            backend_order: Vec::new(),
+            parental_controls: ParentalControls::default(),
+            hide_proprietary: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{content_rating::ContentIntensity, ContentRating};
+
+    fn info(minimum_age_opt: Option<u32>) -> AppInfo {
+        // Realistic violence has these minimum ages for each intensity
+        let attributes = match minimum_age_opt {
+            Some(0) => Vec::new(),
+            Some(4) => vec![("violence-realistic".to_string(), ContentIntensity::Mild)],
+            Some(14) => vec![("violence-realistic".to_string(), ContentIntensity::Intense)],
+            Some(age) => panic!("no attribute for age {}", age),
+            None => return AppInfo::default(),
+        };
+        AppInfo {
+            content_rating_opt: Some(ContentRating { attributes }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn parental_allows() {
+        let off = ParentalControls::default();
+        for age_opt in [None, Some(0), Some(4), Some(14)] {
+            assert!(off.allows(&info(age_opt)));
+        }
+
+        let mut limited = ParentalControls {
+            max_age_opt: Some(12),
+            ..Default::default()
+        };
+        assert!(limited.allows(&info(Some(0))));
+        assert!(limited.allows(&info(Some(4))));
+        assert!(!limited.allows(&info(Some(14))));
+        assert!(!limited.allows(&info(None)));
+
+        limited.allow_unrated = true;
+        assert!(limited.allows(&info(None)));
+        assert!(!limited.allows(&info(Some(14))));
+    }
+
+    #[test]
+    fn parental_shows() {
+        let mut parental_controls = ParentalControls {
+            max_age_opt: Some(12),
+            ..Default::default()
+        };
+        // Restricted apps are still shown unless hiding them
+        assert!(parental_controls.shows(&info(Some(14))));
+        assert!(parental_controls.shows(&info(None)));
+        parental_controls.hide = true;
+        assert!(!parental_controls.shows(&info(Some(14))));
+        assert!(!parental_controls.shows(&info(None)));
+        assert!(parental_controls.shows(&info(Some(4))));
+    }
+
+    #[test]
+    fn parental_policy() {
+        assert_eq!(
+            ParentalControls::parse_policy(""),
+            ParentalControls::default()
+        );
+        assert_eq!(
+            ParentalControls::parse_policy(
+                "# Limits for children\nmax-age = 12\n hide=true \nallow-unrated = true\n"
+            ),
+            ParentalControls {
+                max_age_opt: Some(12),
+                hide: true,
+                allow_unrated: true,
+            }
+        );
+        // Invalid and unknown lines are ignored
+        assert_eq!(
+            ParentalControls::parse_policy(
+                "max-age = twelve\nhide = maybe\nallow-unrated\ncolor = blue\nmax-age = 7"
+            ),
+            ParentalControls {
+                max_age_opt: Some(7),
+                ..Default::default()
+            }
+        );
+        assert_eq!(
+            ParentalControls::parse_policy("max-age = 16\nmax-age = none").max_age_opt,
+            None
+        );
+    }
+
+    #[test]
+    fn filter_shows() {
+        let proprietary = AppInfo {
+            project_license_opt: Some("LicenseRef-proprietary".to_string()),
+            ..Default::default()
+        };
+        let free = AppInfo {
+            project_license_opt: Some("GPL-3.0-or-later".to_string()),
+            ..Default::default()
+        };
+        let unknown = AppInfo {
+            project_license_opt: Some("curl".to_string()),
+            ..Default::default()
+        };
+        let missing = AppInfo::default();
+        let mut filter = AppFilter::default();
+        for info in [&proprietary, &free, &unknown, &missing] {
+            assert!(filter.shows(info));
         }
+        filter.hide_proprietary = true;
+        assert!(!filter.shows(&proprietary));
+        assert!(filter.shows(&free));
+        assert!(!filter.shows(&unknown));
+        assert!(!filter.shows(&missing));
     }
 }
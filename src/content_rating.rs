@@ -0,0 +1,180 @@
+use appstream::enums::ContentState;
+
+use crate::fl;
+
+/// Intensity of an OARS content attribute
+#[derive(
+    Clone, Copy, Debug, Hash, Eq, Ord, PartialEq, PartialOrd, bitcode::Decode, bitcode::Encode,
+)]
+pub enum ContentIntensity {
+    None,
+    Mild,
+    Moderate,
+    Intense,
+}
+
+impl ContentIntensity {
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "none" => Some(Self::None),
+            "mild" => Some(Self::Mild),
+            "moderate" => Some(Self::Moderate),
+            "intense" => Some(Self::Intense),
+            _ => None,
+        }
+    }
+
+    pub fn title(&self) -> String {
+        match self {
+            Self::None => fl!("content-intensity-none"),
+            Self::Mild => fl!("content-intensity-mild"),
+            Self::Moderate => fl!("content-intensity-moderate"),
+            Self::Intense => fl!("content-intensity-intense"),
+        }
+    }
+}
+
+/// Minimum ages for each intensity of OARS attributes, as used by libappstream
+fn attribute_ages(id: &str) -> Option<[u32; 4]> {
+    Some(match id {
+        "violence-cartoon" => [0, 3, 4, 6],
+        "violence-fantasy" => [0, 3, 7, 8],
+        "violence-realistic" => [0, 4, 9, 14],
+        "violence-bloodshed" => [0, 9, 11, 18],
+        "violence-sexual" => [0, 18, 18, 18],
+        "violence-desecration" => [0, 3, 7, 13],
+        "violence-slavery" => [0, 3, 13, 15],
+        "violence-worship" => [0, 3, 13, 15],
+        "drugs-alcohol" => [0, 11, 13, 16],
+        "drugs-narcotics" => [0, 12, 14, 17],
+        "drugs-tobacco" => [0, 10, 13, 13],
+        "sex-nudity" => [0, 12, 14, 14],
+        "sex-themes" => [0, 13, 14, 15],
+        "sex-homosexuality" => [0, 13, 14, 15],
+        "sex-prostitution" => [0, 12, 14, 18],
+        "sex-adultery" => [0, 8, 10, 18],
+        "sex-appearance" => [0, 10, 10, 15],
+        "language-profanity" => [0, 8, 11, 14],
+        "language-humor" => [0, 3, 8, 14],
+        "language-discrimination" => [0, 9, 10, 11],
+        "money-advertising" => [0, 0, 0, 0],
+        "money-gambling" => [0, 7, 10, 18],
+        "money-purchasing" => [0, 12, 14, 18],
+        "social-chat" => [0, 4, 10, 13],
+        "social-audio" => [0, 15, 15, 15],
+        "social-contacts" => [0, 12, 12, 12],
+        "social-info" => [0, 0, 13, 13],
+        "social-location" => [0, 13, 13, 13],
+        _ => return None,
+    })
+}
+
+/// Translated name of an OARS attribute
+fn attribute_title(id: &str) -> String {
+    match id {
+        "violence-cartoon" => fl!("oars-violence-cartoon"),
+        "violence-fantasy" => fl!("oars-violence-fantasy"),
+        "violence-realistic" => fl!("oars-violence-realistic"),
+        "violence-bloodshed" => fl!("oars-violence-bloodshed"),
+        "violence-sexual" => fl!("oars-violence-sexual"),
+        "violence-desecration" => fl!("oars-violence-desecration"),
+        "violence-slavery" => fl!("oars-violence-slavery"),
+        "violence-worship" => fl!("oars-violence-worship"),
+        "drugs-alcohol" => fl!("oars-drugs-alcohol"),
+        "drugs-narcotics" => fl!("oars-drugs-narcotics"),
+        "drugs-tobacco" => fl!("oars-drugs-tobacco"),
+        "sex-nudity" => fl!("oars-sex-nudity"),
+        "sex-themes" => fl!("oars-sex-themes"),
+        "sex-homosexuality" => fl!("oars-sex-homosexuality"),
+        "sex-prostitution" => fl!("oars-sex-prostitution"),
+        "sex-adultery" => fl!("oars-sex-adultery"),
+        "sex-appearance" => fl!("oars-sex-appearance"),
+        "language-profanity" => fl!("oars-language-profanity"),
+        "language-humor" => fl!("oars-language-humor"),
+        "language-discrimination" => fl!("oars-language-discrimination"),
+        "money-advertising" => fl!("oars-money-advertising"),
+        "money-gambling" => fl!("oars-money-gambling"),
+        "money-purchasing" => fl!("oars-money-purchasing"),
+        "social-chat" => fl!("oars-social-chat"),
+        "social-audio" => fl!("oars-social-audio"),
+        "social-contacts" => fl!("oars-social-contacts"),
+        "social-info" => fl!("oars-social-info"),
+        "social-location" => fl!("oars-social-location"),
+        _ => id.to_string(),
+    }
+}
+
+/// Open Age Ratings Service content rating
+#[derive(Clone, Debug, Default, Hash, Eq, PartialEq, bitcode::Decode, bitcode::Encode)]
+pub struct ContentRating {
+    /// OARS attribute IDs and their intensities, attributes not listed have no intensity
+    pub attributes: Vec<(String, ContentIntensity)>,
+}
+
+impl ContentRating {
+    pub fn new(content_rating: &appstream::ContentRating) -> Self {
+        let attributes = content_rating
+            .attributes
+            .iter()
+            .map(|attribute| {
+                let intensity = match attribute.value {
+                    ContentState::None => ContentIntensity::None,
+                    ContentState::Mild => ContentIntensity::Mild,
+                    ContentState::Moderate => ContentIntensity::Moderate,
+                    ContentState::Intense => ContentIntensity::Intense,
+                };
+                (attribute.id.clone(), intensity)
+            })
+            .collect();
+        Self { attributes }
+    }
+
+    /// Parse the ContentRating field of YAML catalogs, which maps rating versions to
+    /// attributes and their intensities
+    pub fn from_yaml(value: &serde_yaml::Value) -> Option<Self> {
+        let mut attributes = Vec::new();
+        for (_version, rating) in value.as_mapping()?.iter() {
+            let Some(rating) = rating.as_mapping() else {
+                continue;
+            };
+            for (id, intensity) in rating.iter() {
+                let (Some(id), Some(intensity)) = (
+                    id.as_str(),
+                    intensity.as_str().and_then(ContentIntensity::parse),
+                ) else {
+                    log::debug!("invalid content rating attribute {:?}: {:?}", id, intensity);
+                    continue;
+                };
+                attributes.push((id.to_string(), intensity));
+            }
+        }
+        Some(Self { attributes })
+    }
+
+    /// Minimum age this content is suitable for
+    pub fn minimum_age(&self) -> u32 {
+        self.attributes
+            .iter()
+            .filter_map(|(id, intensity)| {
+                let ages = attribute_ages(id)?;
+                Some(ages[*intensity as usize])
+            })
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Translated names and intensities of attributes that are present
+    pub fn details(&self) -> Vec<(String, String)> {
+        let mut attributes: Vec<_> = self
+            .attributes
+            .iter()
+            .filter(|(_id, intensity)| *intensity != ContentIntensity::None)
+            .collect();
+        // Most intense attributes first
+        attributes.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        attributes
+            .into_iter()
+            .map(|(id, intensity)| (attribute_title(id), intensity.title()))
+            .collect()
+    }
+}
@@ -0,0 +1,229 @@
+use crate::fl;
+
+use self::spdx::FREE_LICENSES;
+mod spdx;
+
+/// Classification of a license expression, ordered so that combining licenses with OR
+/// takes the maximum and with AND takes the minimum
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub enum LicenseKind {
+    Proprietary,
+    /// Missing, invalid, or not known to be free or proprietary
+    Unknown,
+    Free,
+}
+
+impl LicenseKind {
+    pub fn title(&self) -> String {
+        match self {
+            Self::Proprietary => fl!("license-proprietary"),
+            Self::Unknown => fl!("license-unknown"),
+            Self::Free => fl!("license-free"),
+        }
+    }
+}
+
+fn contains_id(list: &[&str], id: &str) -> bool {
+    // Lists are sorted, but IDs are matched case insensitively
+    list.binary_search(&id).is_ok() || list.iter().any(|x| x.eq_ignore_ascii_case(id))
+}
+
+/// Creative Commons licenses that forbid commercial use or derivative works
+fn is_restricted_cc(id: &str) -> bool {
+    let mut parts = id.split('-');
+    parts.next().map_or(false, |x| x.eq_ignore_ascii_case("CC"))
+        && parts.any(|x| x.eq_ignore_ascii_case("NC") || x.eq_ignore_ascii_case("ND"))
+}
+
+fn id_kind(id: &str) -> LicenseKind {
+    if let Some(reference) = id.strip_prefix("LicenseRef-") {
+        return match reference.split('=').next() {
+            Some("free") | Some("public-domain") => LicenseKind::Free,
+            Some("proprietary") => LicenseKind::Proprietary,
+            _ => LicenseKind::Unknown,
+        };
+    }
+    if is_restricted_cc(id) {
+        return LicenseKind::Proprietary;
+    }
+    // A plus means this version or later, which is only listed for GNU licenses
+    if [id, id.trim_end_matches('+')]
+        .iter()
+        .any(|id| contains_id(FREE_LICENSES, id))
+    {
+        return LicenseKind::Free;
+    }
+    // Many licenses without an OSI or FSF flag are still free, so they are not assumed to
+    // be proprietary
+    LicenseKind::Unknown
+}
+
+/// Parser for SPDX license expressions, where AND binds tighter than OR
+struct Parser<'a> {
+    tokens: Vec<&'a str>,
+    index: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn next_if(&mut self, f: impl Fn(&str) -> bool) -> bool {
+        match self.tokens.get(self.index) {
+            Some(token) if f(token) => {
+                self.index += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    // Either license is enough
+    fn or(&mut self) -> Option<LicenseKind> {
+        let mut kind = self.and()?;
+        while self.next_if(|x| x.eq_ignore_ascii_case("OR") || x == "|") {
+            kind = kind.max(self.and()?);
+        }
+        Some(kind)
+    }
+
+    // Both licenses apply
+    fn and(&mut self) -> Option<LicenseKind> {
+        let mut kind = self.license()?;
+        while self.next_if(|x| x.eq_ignore_ascii_case("AND") || x == "&") {
+            kind = kind.min(self.license()?);
+        }
+        Some(kind)
+    }
+
+    fn license(&mut self) -> Option<LicenseKind> {
+        if self.next_if(|x| x == "(") {
+            let kind = self.or()?;
+            return self.next_if(|x| x == ")").then_some(kind);
+        }
+        let id: &'a str = self.tokens.get(self.index)?;
+        if matches!(id, ")" | "&" | "|") {
+            return None;
+        }
+        self.index += 1;
+        // Exceptions only grant additional permissions
+        if self.next_if(|x| x.eq_ignore_ascii_case("WITH")) {
+            self.tokens.get(self.index)?;
+            self.index += 1;
+        }
+        Some(id_kind(id))
+    }
+}
+
+/// Classify an SPDX license expression as free and open source, proprietary, or unknown
+pub fn kind(expression: &str) -> LicenseKind {
+    let spaced = expression.replace('(', " ( ").replace(')', " ) ");
+    let mut parser = Parser {
+        tokens: spaced.split_whitespace().collect(),
+        index: 0,
+    };
+    match parser.or() {
+        Some(kind) if parser.index == parser.tokens.len() => kind,
+        _ => {
+            log::debug!("invalid license expression {:?}", expression);
+            LicenseKind::Unknown
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn list_is_sorted() {
+        assert!(FREE_LICENSES.windows(2).all(|x| x[0] < x[1]));
+    }
+
+    #[test]
+    fn free_ids() {
+        for id in [
+            "0BSD",
+            "AGPL-3.0-or-later",
+            "Apache-2.0",
+            "Artistic-2.0",
+            "BSD-2-Clause",
+            "BSD-3-Clause",
+            "BSL-1.0",
+            "CC0-1.0",
+            "CC-BY-SA-4.0",
+            "EPL-2.0",
+            "EUPL-1.2",
+            "GPL-2.0",
+            "GPL-2.0+",
+            "GPL-2.0-only",
+            "GPL-3.0-or-later",
+            "ISC",
+            "LGPL-2.1+",
+            "LGPL-2.1-or-later",
+            "LGPL-3.0-only",
+            "MIT",
+            "mit",
+            "MPL-2.0",
+            "OFL-1.1",
+            "Unlicense",
+            "Zlib",
+            "LicenseRef-free",
+            "LicenseRef-free=https://example.org/license",
+            "LicenseRef-public-domain",
+        ] {
+            assert_eq!(kind(id), LicenseKind::Free, "{}", id);
+        }
+    }
+
+    #[test]
+    fn proprietary_ids() {
+        for id in [
+            "CC-BY-NC-4.0",
+            "CC-BY-ND-4.0",
+            "CC-BY-NC-SA-4.0",
+            "CC-BY-NC-ND-3.0-IGO",
+            "cc-by-nc-2.0",
+            "LicenseRef-proprietary",
+            "LicenseRef-proprietary=https://example.org/eula",
+        ] {
+            assert_eq!(kind(id), LicenseKind::Proprietary, "{}", id);
+        }
+    }
+
+    #[test]
+    fn unflagged_ids() {
+        // Free licenses that SPDX does not flag as OSI approved or FSF libre
+        for id in ["curl", "Libpng", "CC-BY-3.0", "bzip2-1.0.6", "CC-PDDC"] {
+            assert_eq!(kind(id), LicenseKind::Unknown, "{}", id);
+        }
+    }
+
+    #[test]
+    fn unknown_ids() {
+        for id in [
+            "",
+            "Foo-1.0",
+            "LicenseRef-custom",
+            "GPL-3.0 AND",
+            "(MIT",
+            "MIT)",
+        ] {
+            assert_eq!(kind(id), LicenseKind::Unknown, "{:?}", id);
+        }
+    }
+
+    #[test]
+    fn expressions() {
+        assert_eq!(kind("MIT OR LicenseRef-proprietary"), LicenseKind::Free);
+        assert_eq!(
+            kind("MIT AND LicenseRef-proprietary"),
+            LicenseKind::Proprietary
+        );
+        assert_eq!(kind("MIT AND Foo-1.0"), LicenseKind::Unknown);
+        assert_eq!(kind("Foo-1.0 OR CC-BY-NC-4.0"), LicenseKind::Unknown);
+        assert_eq!(
+            kind("(GPL-2.0-or-later WITH Classpath-exception-2.0 OR MIT) AND Apache-2.0"),
+            LicenseKind::Free
+        );
+        assert_eq!(kind("GPL-3.0+ & CC-BY-SA-4.0"), LicenseKind::Free);
+        assert_eq!(kind("MIT or LGPL-2.1-only"), LicenseKind::Free);
+    }
+}
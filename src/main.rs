@@ -16,33 +16,41 @@ use cosmic::{
     },
     theme, widget, Application, ApplicationExt, Element,
 };
+use notify::Watcher;
 use rayon::prelude::*;
 use std::{
     any::TypeId,
+    borrow::Cow,
     cmp,
-    collections::{BTreeMap, HashMap, VecDeque},
+    collections::{BTreeMap, HashMap, HashSet, VecDeque},
     env,
     future::pending,
+    path::PathBuf,
     process,
-    sync::{Arc, Mutex},
-    time::Instant,
+    sync::{mpsc, Arc, Mutex},
+    time::{Duration, Instant},
 };
 
 use app_id::AppId;
 mod app_id;
 
-use app_info::{AppIcon, AppInfo};
+use app_info::{AppIcon, AppInfo, AppKind};
 mod app_info;
 
-use appstream_cache::AppstreamCache;
+use appstream_cache::{AppstreamCache, AppstreamCacheStatus};
 mod appstream_cache;
 
-use backend::{Backends, Package};
+use backend::{BackendError, BackendStatuses, Backends, Capabilities, Package, Repo};
 mod backend;
 
-use config::{AppTheme, Config, CONFIG_VERSION};
+mod component_reader;
+
+use config::{AppFilter, AppTheme, Config, ParentalControls, CONFIG_VERSION, PARENTAL_AGES};
 mod config;
 
+use content_rating::ContentRating;
+mod content_rating;
+
 use editors_choice::EDITORS_CHOICE;
 mod editors_choice;
 
@@ -52,6 +60,9 @@ mod icon_cache;
 use key_bind::{key_binds, KeyBind};
 mod key_bind;
 
+use license::LicenseKind;
+mod license;
+
 mod localize;
 
 mod logind;
@@ -62,12 +73,22 @@ mod operation;
 use priority::priority;
 mod priority;
 
+use provides::AppProvide;
+mod provides;
+
+mod remote_icon;
+
+use rich_text::RichText;
+mod rich_text;
+
 mod stats;
 
 const ICON_SIZE_SEARCH: u16 = 48;
 const ICON_SIZE_PACKAGE: u16 = 64;
 const ICON_SIZE_DETAILS: u16 = 128;
 const MAX_GRID_WIDTH: f32 = 1600.0;
+// Appstream changes are reloaded after no more changes happen for this long
+const WATCH_DELAY: Duration = Duration::from_secs(2);
 
 /// Runs application with these settings
 #[rustfmt::skip]
@@ -114,12 +135,14 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum Action {
     SearchActivate,
+    Settings,
 }
 
 impl Action {
     pub fn message(&self) -> Message {
         match self {
             Self::SearchActivate => Message::SearchActivate,
+            Self::Settings => Message::ToggleContextPage(ContextPage::Settings),
         }
     }
 }
@@ -130,8 +153,52 @@ pub struct AppEntry {
     installed: bool,
 }
 
+impl AppEntry {
+    /// Order of sources for an app: installed first, then by preferred backend order from
+    /// config, then by priority. Backends without a configured order share the lowest rank,
+    /// so priority decides between them.
+    fn cmp_preferred(a: &Self, b: &Self, config: &Config, id: &AppId) -> cmp::Ordering {
+        // Sort with installed first
+        match b.installed.cmp(&a.installed) {
+            cmp::Ordering::Equal => {
+                // Sort by preferred backend order from config, which overrides priorities
+                // so users can prefer a backend even for apps the priorities favor elsewhere
+                let a_rank = config.backend_rank(a.backend_name);
+                let b_rank = config.backend_rank(b.backend_name);
+                match a_rank.cmp(&b_rank) {
+                    cmp::Ordering::Equal => {
+                        // Sort by highest priority first to lowest priority
+                        let a_priority = priority(a.backend_name, &a.info.source_id, id);
+                        let b_priority = priority(b.backend_name, &b.info.source_id, id);
+                        match b_priority.cmp(&a_priority) {
+                            cmp::Ordering::Equal => {
+                                match lexical_sort::natural_lexical_cmp(
+                                    &a.info.source_id,
+                                    &b.info.source_id,
+                                ) {
+                                    cmp::Ordering::Equal => lexical_sort::natural_lexical_cmp(
+                                        &a.backend_name,
+                                        &b.backend_name,
+                                    ),
+                                    ordering => ordering,
+                                }
+                            }
+                            ordering => ordering,
+                        }
+                    }
+                    ordering => ordering,
+                }
+            }
+            ordering => ordering,
+        }
+    }
+}
+
 pub type Apps = HashMap<AppId, Vec<AppEntry>>;
 
+/// Plain text descriptions by ID, with the source ID of each info
+pub type Descriptions = HashMap<AppId, Vec<(String, String)>>;
+
 #[derive(Clone, Debug)]
 pub struct Flags {
     subcommand_opt: Option<String>,
@@ -153,25 +220,43 @@ impl CosmicFlags for Flags {
 #[derive(Clone, Debug)]
 pub enum Message {
     AppTheme(AppTheme),
-    Backends(Backends),
+    AppstreamChanged,
+    BackendEnabled(&'static str, bool),
+    BackendPrefer(&'static str),
+    Backends(Backends, BackendStatuses, Arc<Descriptions>),
+    CancelOperation(u64),
     CategoryResults(&'static [Category], Vec<SearchResult>),
     CheckUpdates,
+    ClearCache,
     Config(Config),
     DialogCancel,
     DialogConfirm,
     DialogPage(DialogPage),
     ExplorePage(Option<ExplorePage>),
     ExploreResults(ExplorePage, Vec<SearchResult>),
+    HideProprietary(bool),
     Installed(Vec<(&'static str, Package)>),
     InstalledResults(Vec<SearchResult>),
     Key(Modifiers, Key),
     MaybeExit,
     Notification(Arc<Mutex<notify_rust::NotificationHandle>>),
+    OfflineUpdate,
+    OfflineUpdateResult(Result<(), String>),
     OpenDesktopId(String),
+    OpenUrl(String),
     Operation(OperationKind, &'static str, AppId, Arc<AppInfo>),
+    ParentalAllowUnrated(bool),
+    ParentalHide(bool),
+    ParentalMaxAge(Option<u32>),
     PendingComplete(u64),
-    PendingError(u64, String),
+    PendingError(u64, BackendError),
     PendingProgress(u64, f32),
+    Permissions(&'static str, AppId, Vec<String>),
+    RemoteIcon(String),
+    RemoteIconFailed(String),
+    RepoEnabled(&'static str, String, bool),
+    Repos(&'static str, Vec<Repo>),
+    ReposChanged,
     ScrollView(scrollable::Viewport),
     SearchActivate,
     SearchClear,
@@ -188,6 +273,7 @@ pub enum Message {
     SelectedScreenshot(usize, String, Vec<u8>),
     SelectedScreenshotShown(usize),
     SelectedSource(usize),
+    Simulated(AppId, Vec<String>),
     SystemThemeModeChange(cosmic_theme::ThemeMode),
     ToggleContextPage(ContextPage),
     UpdateAll,
@@ -198,12 +284,14 @@ pub enum Message {
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum ContextPage {
+    Diagnostics,
     Settings,
 }
 
 impl ContextPage {
     fn title(&self) -> String {
         match self {
+            Self::Diagnostics => fl!("diagnostics"),
             Self::Settings => fl!("settings"),
         }
     }
@@ -345,6 +433,14 @@ pub enum ExplorePage {
     MusicAndVideoApps,
     AppsForLearning,
     Utilities,
+    ConsoleApps,
+    WebApps,
+    Addons,
+    Fonts,
+    Codecs,
+    InputMethods,
+    Drivers,
+    Runtimes,
 }
 
 impl ExplorePage {
@@ -363,6 +459,14 @@ impl ExplorePage {
             Self::MusicAndVideoApps,
             Self::AppsForLearning,
             Self::Utilities,
+            Self::ConsoleApps,
+            Self::WebApps,
+            Self::Addons,
+            Self::Fonts,
+            Self::Codecs,
+            Self::InputMethods,
+            Self::Drivers,
+            Self::Runtimes,
         ]
     }
 
@@ -381,6 +485,29 @@ impl ExplorePage {
             Self::MusicAndVideoApps => fl!("music-and-video-apps"),
             Self::AppsForLearning => fl!("apps-for-learning"),
             Self::Utilities => fl!("utilities"),
+            Self::ConsoleApps => fl!("console-apps"),
+            Self::WebApps => fl!("web-apps"),
+            Self::Addons => fl!("addons"),
+            Self::Fonts => fl!("fonts"),
+            Self::Codecs => fl!("codecs"),
+            Self::InputMethods => fl!("input-methods"),
+            Self::Drivers => fl!("drivers"),
+            Self::Runtimes => fl!("runtimes"),
+        }
+    }
+
+    /// Kind of component shown on this page
+    fn kind(&self) -> AppKind {
+        match self {
+            Self::ConsoleApps => AppKind::ConsoleApplication,
+            Self::WebApps => AppKind::WebApplication,
+            Self::Addons => AppKind::Addon,
+            Self::Fonts => AppKind::Font,
+            Self::Codecs => AppKind::Codec,
+            Self::InputMethods => AppKind::InputMethod,
+            Self::Drivers => AppKind::Driver,
+            Self::Runtimes => AppKind::Runtime,
+            _ => AppKind::DesktopApplication,
         }
     }
 
@@ -424,6 +551,72 @@ impl GridMetrics {
     }
 }
 
+/// Infos of results that may be shown, for fetching their remote icons
+fn results_infos(results: &[SearchResult]) -> impl Iterator<Item = &AppInfo> {
+    //TODO: fetch more icons when paging is supported
+    results.iter().take(256).map(|result| &*result.info)
+}
+
+/// Read the descriptions of all cached infos once, so searching does not read the cache files
+fn load_descriptions(backends: &Backends) -> Descriptions {
+    let mut descriptions = Descriptions::new();
+    for backend in backends.values() {
+        for appstream_cache in backend.info_caches() {
+            for (id, info) in appstream_cache.infos.iter() {
+                descriptions.entry(id.clone()).or_default().push((
+                    info.source_id.clone(),
+                    appstream_cache.description(id, info).into_owned(),
+                ));
+            }
+        }
+    }
+    descriptions
+}
+
+/// Description of an info, which is only missing for infos that are not in a cache
+fn info_description<'a>(
+    descriptions: &'a Descriptions,
+    id: &AppId,
+    info: &AppInfo,
+) -> Cow<'a, str> {
+    match descriptions
+        .get(id)
+        .and_then(|x| x.iter().find(|(source_id, _)| source_id == &info.source_id))
+    {
+        Some((_, description)) => Cow::Borrowed(description),
+        None => Cow::Owned(info.description.to_string()),
+    }
+}
+
+/// Look up the icon of an info again, after a remote icon was downloaded
+fn reload_icon(
+    backends: &Backends,
+    backend_name: &str,
+    url: &str,
+    icon: &mut widget::icon::Handle,
+    info: &AppInfo,
+    size: u16,
+    scale: f32,
+) {
+    let has_url = info
+        .icons
+        .iter()
+        .any(|x| matches!(x, AppIcon::Remote(remote_url, ..) if remote_url == url));
+    if !has_url {
+        return;
+    }
+    let Some(backend) = backends.get(backend_name) else {
+        return;
+    };
+    if let Some(appstream_cache) = backend
+        .info_caches()
+        .iter()
+        .find(|x| x.source_id == info.source_id)
+    {
+        *icon = appstream_cache.icon_sized(info, size, scale);
+    }
+}
+
 fn package_card_view<'a>(
     info: &'a AppInfo,
     icon: &'a widget::icon::Handle,
@@ -612,6 +805,17 @@ pub struct Selected {
     screenshot_images: HashMap<usize, widget::image::Handle>,
     screenshot_shown: usize,
     sources: Vec<SelectedSource>,
+    addons: Vec<(AppId, widget::icon::Handle, Arc<AppInfo>)>,
+    // Loaded for installed apps if the backend supports viewing permissions
+    permissions_opt: Option<Vec<String>>,
+}
+
+/// Progress of preparing updates to apply on restart
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum OfflineUpdateState {
+    Preparing,
+    Ready,
+    Failed(String),
 }
 
 /// The [`App`] stores application-specific state.
@@ -622,8 +826,19 @@ pub struct App {
     config: Config,
     locale: String,
     app_themes: Vec<String>,
+    parental_ages: Vec<String>,
+    // System policy, which overrides the parental controls in config
+    parental_policy_opt: Option<ParentalControls>,
     apps: Arc<Apps>,
     backends: Backends,
+    backend_statuses: BackendStatuses,
+    descriptions: Arc<Descriptions>,
+    // Package sources of backends that support managing them
+    repos: BTreeMap<&'static str, Vec<Repo>>,
+    // Names of caches being rebuilt because they are outdated or corrupt
+    caches_rebuilding: Vec<String>,
+    // Paths watched for appstream changes, updated when backends are loaded
+    watch_paths: Vec<PathBuf>,
     context_page: ContextPage,
     dialog_pages: VecDeque<DialogPage>,
     explore_page_opt: Option<ExplorePage>,
@@ -632,7 +847,12 @@ pub struct App {
     notification_opt: Option<Arc<Mutex<notify_rust::NotificationHandle>>>,
     pending_operation_id: u64,
     pending_operations: BTreeMap<u64, (Operation, f32)>,
-    failed_operations: BTreeMap<u64, (Operation, String)>,
+    // Set when appstream data changed during operations, to reload after they complete
+    reload_pending: bool,
+    failed_operations: BTreeMap<u64, (Operation, BackendError)>,
+    offline_update_opt: Option<OfflineUpdateState>,
+    // Packages that the uninstall being confirmed would remove, if the backend supports dry runs
+    simulated_opt: Option<(AppId, Vec<String>)>,
     scrollable_id: widget::Id,
     scroll_views: HashMap<ScrollContext, scrollable::Viewport>,
     search_active: bool,
@@ -652,6 +872,7 @@ pub struct App {
     installed_results: Option<Vec<SearchResult>>,
     search_results: Option<(String, Vec<SearchResult>)>,
     selected_opt: Option<Selected>,
+    remote_icons_requested: HashSet<String>,
 }
 
 impl App {
@@ -708,9 +929,51 @@ impl App {
         self.pending_operations.insert(id, (operation, 0.0));
     }
 
+    fn capabilities(&self, backend_name: &str) -> Capabilities {
+        self.backends
+            .get(backend_name)
+            .map(|backend| backend.capabilities())
+            .unwrap_or_default()
+    }
+
+    /// Find the packages an uninstall would remove, if the backend supports dry runs
+    fn simulate_uninstall(
+        &mut self,
+        backend_name: &'static str,
+        id: AppId,
+        info: Arc<AppInfo>,
+    ) -> Command<Message> {
+        self.simulated_opt = None;
+        let Some(backend) = self.backends.get(backend_name).cloned() else {
+            return Command::none();
+        };
+        if !backend.capabilities().dry_run {
+            return Command::none();
+        }
+        Command::perform(
+            async move {
+                tokio::task::spawn_blocking(move || {
+                    match backend.simulate(OperationKind::Uninstall, &id, &info) {
+                        Ok(package_names) => message::app(Message::Simulated(id, package_names)),
+                        Err(err) => {
+                            log::warn!("failed to simulate uninstalling {:?}: {}", id, err);
+                            message::none()
+                        }
+                    }
+                })
+                .await
+                .unwrap_or(message::none())
+            },
+            |x| x,
+        )
+    }
+
+    /// Search apps, skipping apps that are not installed and are hidden by the filter
     fn generic_search<F: Fn(&AppId, &AppInfo, bool) -> Option<i64> + Send + Sync>(
         apps: &Apps,
         backends: &Backends,
+        scale: f32,
+        filter: AppFilter,
         filter_map: F,
     ) -> Vec<SearchResult> {
         let mut results: Vec<SearchResult> = apps
@@ -723,6 +986,10 @@ impl App {
                     installed,
                 } in infos.iter()
                 {
+                    // Installed apps are always shown, so they can be removed
+                    if !*installed && !filter.shows(info) {
+                        continue;
+                    }
                     if let Some(weight) = filter_map(id, info, *installed) {
                         //TODO: optimize
                         let Some(backend) = backends.get(backend_name) else {
@@ -739,7 +1006,7 @@ impl App {
                         let result = SearchResult {
                             backend_name,
                             id: id.clone(),
-                            icon: appstream_cache.icon(info),
+                            icon: appstream_cache.icon_sized(info, ICON_SIZE_SEARCH, scale),
                             info: info.clone(),
                             weight,
                         };
@@ -775,12 +1042,18 @@ impl App {
     fn categories(&self, categories: &'static [Category]) -> Command<Message> {
         let apps = self.apps.clone();
         let backends = self.backends.clone();
+        let scale = self.core.scale_factor();
+        let filter = self.app_filter();
         Command::perform(
             async move {
                 tokio::task::spawn_blocking(move || {
                     let start = Instant::now();
-                    let results =
-                        Self::generic_search(&apps, &backends, |_id, info, _installed| {
+                    let results = Self::generic_search(
+                        &apps,
+                        &backends,
+                        scale,
+                        filter,
+                        |_id, info, _installed| {
                             for category in categories {
                                 //TODO: contains doesn't work due to type mismatch
                                 if info.categories.iter().any(|x| x == category.id()) {
@@ -788,7 +1061,8 @@ impl App {
                                 }
                             }
                             None
-                        });
+                        },
+                    );
                     let duration = start.elapsed();
                     log::info!(
                         "searched for categories {:?} in {:?}, found {} results",
@@ -808,12 +1082,17 @@ impl App {
     fn explore_results(&self, explore_page: ExplorePage) -> Command<Message> {
         let apps = self.apps.clone();
         let backends = self.backends.clone();
+        let scale = self.core.scale_factor();
+        let filter = self.app_filter();
         Command::perform(
             async move {
                 tokio::task::spawn_blocking(move || {
                     let start = Instant::now();
                     let now = chrono::Utc::now().timestamp();
-                    let results = Self::generic_search(&apps, &backends, |id, info, _installed| {
+                    let results = Self::generic_search(&apps, &backends, scale, filter, |id, info, _installed| {
+                        if info.kind != explore_page.kind() {
+                            return None;
+                        }
                         match explore_page {
                             ExplorePage::EditorsChoice => EDITORS_CHOICE
                                 .iter()
@@ -842,6 +1121,14 @@ impl App {
                                 }
                                 Some(min_weight)
                             }
+                            ExplorePage::ConsoleApps
+                            | ExplorePage::WebApps
+                            | ExplorePage::Addons
+                            | ExplorePage::Fonts
+                            | ExplorePage::Codecs
+                            | ExplorePage::InputMethods
+                            | ExplorePage::Drivers
+                            | ExplorePage::Runtimes => Some(-(info.monthly_downloads as i64)),
                             _ => {
                                 for category in explore_page.categories() {
                                     //TODO: contains doesn't work due to type mismatch
@@ -872,17 +1159,25 @@ impl App {
     fn installed_results(&self) -> Command<Message> {
         let apps = self.apps.clone();
         let backends = self.backends.clone();
+        let scale = self.core.scale_factor();
         Command::perform(
             async move {
                 tokio::task::spawn_blocking(move || {
                     let start = Instant::now();
-                    let results = Self::generic_search(&apps, &backends, |id, _info, installed| {
-                        if installed {
-                            Some(if id.is_system() { -1 } else { 0 })
-                        } else {
-                            None
-                        }
-                    });
+                    let results = Self::generic_search(
+                        &apps,
+                        &backends,
+                        scale,
+                        // Installed apps are never hidden
+                        AppFilter::default(),
+                        |id, _info, installed| {
+                            if installed {
+                                Some(if id.is_system() { -1 } else { 0 })
+                            } else {
+                                None
+                            }
+                        },
+                    );
                     let duration = start.elapsed();
                     log::info!(
                         "searched for installed in {:?}, found {} results",
@@ -913,68 +1208,83 @@ impl App {
         };
         let apps = self.apps.clone();
         let backends = self.backends.clone();
+        let descriptions = self.descriptions.clone();
+        let scale = self.core.scale_factor();
+        let filter = self.app_filter();
         Command::perform(
             async move {
                 tokio::task::spawn_blocking(move || {
                     let start = Instant::now();
-                    let results =
-                        Self::generic_search(&apps, &backends, |_id, info, _installed| {
+                    // Apps that open a media type or provide a command named like the input
+                    let provided_ids: HashSet<AppId> = backends
+                        .values()
+                        .flat_map(|backend| backend.info_caches())
+                        .flat_map(|appstream_cache| {
+                            appstream_cache
+                                .provided_by(&AppProvide::MediaType(input.clone()))
+                                .chain(
+                                    appstream_cache.provided_by(&AppProvide::Binary(input.clone())),
+                                )
+                                .cloned()
+                                .collect::<Vec<_>>()
+                        })
+                        .collect();
+                    let results = Self::generic_search(
+                        &apps,
+                        &backends,
+                        scale,
+                        filter,
+                        |id, info, _installed| {
                             //TODO: improve performance
                             let stats_weight = |weight: i64| {
                                 //TODO: make sure no overflows
                                 (weight << 56) - (info.monthly_downloads as i64)
                             };
-                            //TODO: fuzzy match (nucleus-matcher?)
-                            match regex.find(&info.name) {
-                                Some(mat) => {
-                                    if mat.range().start == 0 {
-                                        if mat.range().end == info.name.len() {
-                                            // Name equals search phrase
-                                            Some(stats_weight(0))
-                                        } else {
-                                            // Name starts with search phrase
-                                            Some(stats_weight(1))
-                                        }
+                            // Weight of a match in text, which is lowest when the text equals
+                            // the search phrase, then when it starts with and contains it
+                            let text_weight = |text: &str, weight: i64| {
+                                let mat = regex.find(text)?;
+                                Some(if mat.range().start == 0 {
+                                    if mat.range().end == text.len() {
+                                        weight
                                     } else {
-                                        // Name contains search phrase
-                                        Some(stats_weight(2))
-                                    }
-                                }
-                                None => match regex.find(&info.summary) {
-                                    Some(mat) => {
-                                        if mat.range().start == 0 {
-                                            if mat.range().end == info.summary.len() {
-                                                // Summary equals search phrase
-                                                Some(stats_weight(3))
-                                            } else {
-                                                // Summary starts with search phrase
-                                                Some(stats_weight(4))
-                                            }
-                                        } else {
-                                            // Summary contains search phrase
-                                            Some(stats_weight(5))
-                                        }
+                                        weight + 1
                                     }
-                                    None => match regex.find(&info.description) {
-                                        Some(mat) => {
-                                            if mat.range().start == 0 {
-                                                if mat.range().end == info.summary.len() {
-                                                    // Description equals search phrase
-                                                    Some(stats_weight(6))
-                                                } else {
-                                                    // Description starts with search phrase
-                                                    Some(stats_weight(7))
-                                                }
-                                            } else {
-                                                // Description contains search phrase
-                                                Some(stats_weight(8))
-                                            }
-                                        }
-                                        None => None,
-                                    },
-                                },
+                                } else {
+                                    weight + 2
+                                })
+                            };
+                            //TODO: fuzzy match (nucleus-matcher?)
+                            if let Some(weight) = text_weight(&info.name, 0) {
+                                return Some(stats_weight(weight));
                             }
-                        });
+                            // Keywords rank between name and summary
+                            if let Some(weight) = info
+                                .keywords
+                                .iter()
+                                .filter_map(|keyword| text_weight(keyword, 3))
+                                .min()
+                            {
+                                return Some(stats_weight(weight));
+                            }
+                            if let Some(weight) = text_weight(&info.developer_name, 6) {
+                                return Some(stats_weight(weight));
+                            }
+                            if let Some(weight) = text_weight(&info.summary, 9) {
+                                return Some(stats_weight(weight));
+                            }
+                            if let Some(weight) =
+                                text_weight(&info_description(&descriptions, id, info), 12)
+                            {
+                                return Some(stats_weight(weight));
+                            }
+                            if provided_ids.contains(id) {
+                                // Provides search phrase
+                                return Some(stats_weight(15));
+                            }
+                            None
+                        },
+                    );
                     let duration = start.elapsed();
                     log::info!(
                         "searched for {:?} in {:?}, found {} results",
@@ -1018,12 +1328,80 @@ impl App {
         sources
     }
 
+    /// Download remote icons that are missing or expired from the cache. Icons found to be
+    /// current stay requested, so they are only checked once.
+    fn fetch_remote_icons<'a, I: IntoIterator<Item = &'a AppInfo>>(
+        &mut self,
+        infos: I,
+    ) -> Command<Message> {
+        let mut commands = Vec::new();
+        for info in infos {
+            for icon in info.icons.iter() {
+                let AppIcon::Remote(url, _width, _height, _scale) = icon else {
+                    continue;
+                };
+                if self.remote_icons_requested.contains(url) || remote_icon::backing_off(url) {
+                    continue;
+                }
+                self.remote_icons_requested.insert(url.clone());
+                let url = url.clone();
+                commands.push(Command::perform(
+                    async move {
+                        match remote_icon::fetch(url.clone()).await {
+                            Ok(true) => message::app(Message::RemoteIcon(url)),
+                            Ok(false) => message::none(),
+                            Err(err) => {
+                                log::warn!("failed to fetch remote icon {}: {}", url, err);
+                                message::app(Message::RemoteIconFailed(url))
+                            }
+                        }
+                    },
+                    |x| x,
+                ));
+            }
+        }
+        Command::batch(commands)
+    }
+
+    /// Find add-ons that extend a component in the same source
+    fn selected_addons(
+        &self,
+        backend_name: &'static str,
+        id: &AppId,
+        info: &AppInfo,
+    ) -> Vec<(AppId, widget::icon::Handle, Arc<AppInfo>)> {
+        let mut addons = Vec::new();
+        let Some(backend) = self.backends.get(backend_name) else {
+            return addons;
+        };
+        for appstream_cache in backend.info_caches() {
+            if appstream_cache.source_id != info.source_id {
+                continue;
+            }
+            for (addon_id, addon_info) in appstream_cache.infos.iter() {
+                if addon_info.extends.iter().any(|x| AppId::new(x) == *id) {
+                    addons.push((
+                        addon_id.clone(),
+                        appstream_cache.icon_sized(
+                            addon_info,
+                            ICON_SIZE_SEARCH,
+                            self.core.scale_factor(),
+                        ),
+                        addon_info.clone(),
+                    ));
+                }
+            }
+        }
+        addons.sort_by(|a, b| lexical_sort::natural_lexical_cmp(&a.2.name, &b.2.name));
+        addons
+    }
+
     fn select(
         &mut self,
         backend_name: &'static str,
         id: AppId,
-        icon: widget::icon::Handle,
-        info: Arc<AppInfo>,
+        mut icon: widget::icon::Handle,
+        mut info: Arc<AppInfo>,
     ) -> Command<Message> {
         log::info!(
             "selected {:?} from backend {:?} and source {:?}",
@@ -1031,7 +1409,49 @@ impl App {
             backend_name,
             info.source_id
         );
+        if let Some(backend) = self.backends.get(backend_name) {
+            if let Some(appstream_cache) = backend
+                .info_caches()
+                .iter()
+                .find(|x| x.source_id == info.source_id)
+            {
+                // Details are not kept in memory for every info
+                info = appstream_cache.details(&id, &info);
+                // Results use smaller icons, so look up the icon again at the details size
+                icon =
+                    appstream_cache.icon_sized(&info, ICON_SIZE_DETAILS, self.core.scale_factor());
+            }
+        }
         let sources = self.selected_sources(backend_name, &id, &info);
+        let addons = self.selected_addons(backend_name, &id, &info);
+        let remote_icons_command = self.fetch_remote_icons(
+            std::iter::once(&*info).chain(addons.iter().map(|(_, _, info)| &**info)),
+        );
+        let mut permissions_command = Command::none();
+        if self.capabilities(backend_name).permissions
+            && self.is_installed(backend_name, &info.source_id, &id)
+        {
+            if let Some(backend) = self.backends.get(backend_name).cloned() {
+                let id = id.clone();
+                let info = info.clone();
+                permissions_command = Command::perform(
+                    async move {
+                        tokio::task::spawn_blocking(move || match backend.permissions(&id, &info) {
+                            Ok(permissions) => {
+                                message::app(Message::Permissions(backend_name, id, permissions))
+                            }
+                            Err(err) => {
+                                log::warn!("failed to load permissions of {:?}: {}", id, err);
+                                message::none()
+                            }
+                        })
+                        .await
+                        .unwrap_or(message::none())
+                    },
+                    |x| x,
+                );
+            }
+        }
         self.selected_opt = Some(Selected {
             backend_name,
             id,
@@ -1040,8 +1460,14 @@ impl App {
             screenshot_images: HashMap::new(),
             screenshot_shown: 0,
             sources,
+            addons,
+            permissions_opt: None,
         });
-        self.update_scroll()
+        Command::batch([
+            self.update_scroll(),
+            remote_icons_command,
+            permissions_command,
+        ])
     }
 
     fn scroll_context(&self) -> ScrollContext {
@@ -1056,6 +1482,70 @@ impl App {
         }
     }
 
+    fn update_remote_icon(&mut self, url: &str) {
+        let backends = &self.backends;
+        let scale = self.core.scale_factor();
+        let mut results: Vec<&mut SearchResult> = Vec::new();
+        if let Some((_, category_results)) = &mut self.category_results {
+            results.extend(category_results.iter_mut());
+        }
+        for explore_results in self.explore_results.values_mut() {
+            results.extend(explore_results.iter_mut());
+        }
+        if let Some(installed_results) = &mut self.installed_results {
+            results.extend(installed_results.iter_mut());
+        }
+        if let Some((_, search_results)) = &mut self.search_results {
+            results.extend(search_results.iter_mut());
+        }
+        for result in results {
+            reload_icon(
+                backends,
+                result.backend_name,
+                url,
+                &mut result.icon,
+                &result.info,
+                ICON_SIZE_SEARCH,
+                scale,
+            );
+        }
+        if let Some(updates) = &mut self.updates {
+            for (backend_name, package) in updates.iter_mut() {
+                reload_icon(
+                    backends,
+                    backend_name,
+                    url,
+                    &mut package.icon,
+                    &package.info,
+                    ICON_SIZE_PACKAGE,
+                    scale,
+                );
+            }
+        }
+        if let Some(selected) = &mut self.selected_opt {
+            reload_icon(
+                backends,
+                selected.backend_name,
+                url,
+                &mut selected.icon,
+                &selected.info,
+                ICON_SIZE_DETAILS,
+                scale,
+            );
+            for (_addon_id, addon_icon, addon_info) in selected.addons.iter_mut() {
+                reload_icon(
+                    backends,
+                    selected.backend_name,
+                    url,
+                    addon_icon,
+                    addon_info,
+                    ICON_SIZE_SEARCH,
+                    scale,
+                );
+            }
+        }
+    }
+
     fn update_scroll(&mut self) -> Command<Message> {
         let scroll_context = self.scroll_context();
         // Clear unused scroll contexts
@@ -1072,12 +1562,17 @@ impl App {
     }
 
     fn update_backends(&mut self, refresh: bool) -> Command<Message> {
+        self.caches_rebuilding = appstream_cache::rebuild_needed();
+        if !self.caches_rebuilding.is_empty() {
+            log::info!("rebuilding caches {:?}", self.caches_rebuilding);
+        }
+        let config = self.config.clone();
         let locale = self.locale.clone();
         Command::perform(
             async move {
                 tokio::task::spawn_blocking(move || {
                     let start = Instant::now();
-                    let backends = backend::backends(&locale, refresh);
+                    let (backends, backend_statuses) = backend::backends(&config, &locale, refresh);
                     let duration = start.elapsed();
                     log::info!(
                         "loaded backends {} in {:?}",
@@ -1088,7 +1583,18 @@ impl App {
                         },
                         duration
                     );
-                    message::app(Message::Backends(backends))
+                    let start = Instant::now();
+                    let descriptions = load_descriptions(&backends);
+                    log::info!(
+                        "loaded {} descriptions in {:?}",
+                        descriptions.len(),
+                        start.elapsed()
+                    );
+                    message::app(Message::Backends(
+                        backends,
+                        backend_statuses,
+                        Arc::new(descriptions),
+                    ))
                 })
                 .await
                 .unwrap_or(message::none())
@@ -1097,10 +1603,84 @@ impl App {
         )
     }
 
+    fn update_repos(&self) -> Command<Message> {
+        let mut commands = Vec::new();
+        for (backend_name, backend) in self.backends.iter() {
+            if !backend.capabilities().repo_management {
+                continue;
+            }
+            let backend_name = *backend_name;
+            let backend = backend.clone();
+            commands.push(Command::perform(
+                async move {
+                    tokio::task::spawn_blocking(move || match backend.repos() {
+                        Ok(repos) => message::app(Message::Repos(backend_name, repos)),
+                        Err(err) => {
+                            log::warn!("failed to load {} sources: {}", backend_name, err);
+                            message::none()
+                        }
+                    })
+                    .await
+                    .unwrap_or(message::none())
+                },
+                |x| x,
+            ));
+        }
+        Command::batch(commands)
+    }
+
     fn update_config(&mut self) -> Command<Message> {
         cosmic::app::command::set_theme(self.config.app_theme.theme())
     }
 
+    /// Compiled in backends, sorted by preferred order from config
+    fn backend_order(&self) -> Vec<&'static str> {
+        let mut backend_names = backend::backend_names();
+        backend_names.sort_by_key(|backend_name| self.config.backend_rank(backend_name));
+        backend_names
+    }
+
+    /// Re-sort sources after the preferred backend order changes
+    fn update_backend_order(&mut self) -> Command<Message> {
+        self.update_apps();
+        let mut commands = Vec::new();
+        commands.push(self.installed_results());
+        for explore_page in ExplorePage::all() {
+            commands.push(self.explore_results(*explore_page));
+        }
+        Command::batch(commands)
+    }
+
+    /// Search again for the explore, category and search results, after changing how apps
+    /// are filtered
+    fn refresh_results(&self) -> Command<Message> {
+        let mut commands = Vec::new();
+        for explore_page in ExplorePage::all() {
+            commands.push(self.explore_results(*explore_page));
+        }
+        if let Some((categories, _results)) = &self.category_results {
+            commands.push(self.categories(*categories));
+        }
+        if self.search_results.is_some() {
+            commands.push(self.search());
+        }
+        Command::batch(commands)
+    }
+
+    /// Parental controls from the system policy if there is one, otherwise from config
+    fn parental_controls(&self) -> ParentalControls {
+        self.parental_policy_opt
+            .unwrap_or(self.config.parental_controls)
+    }
+
+    /// Filters for browsing and searching
+    fn app_filter(&self) -> AppFilter {
+        AppFilter {
+            parental_controls: self.parental_controls(),
+            hide_proprietary: self.config.hide_proprietary,
+        }
+    }
+
     fn is_installed(&self, backend_name: &'static str, source_id: &str, id: &AppId) -> bool {
         if let Some(installed) = &self.installed {
             for (installed_backend_name, package) in installed {
@@ -1120,44 +1700,18 @@ impl App {
         let start = Instant::now();
         let mut apps = Apps::new();
 
-        let entry_sort = |a: &AppEntry, b: &AppEntry, id: &AppId| {
-            // Sort with installed first
-            match b.installed.cmp(&a.installed) {
-                cmp::Ordering::Equal => {
-                    // Sort by highest priority first to lowest priority
-                    let a_priority = priority(a.backend_name, &a.info.source_id, id);
-                    let b_priority = priority(b.backend_name, &b.info.source_id, id);
-                    match b_priority.cmp(&a_priority) {
-                        cmp::Ordering::Equal => {
-                            match lexical_sort::natural_lexical_cmp(
-                                &a.info.source_id,
-                                &b.info.source_id,
-                            ) {
-                                cmp::Ordering::Equal => lexical_sort::natural_lexical_cmp(
-                                    &a.backend_name,
-                                    &b.backend_name,
-                                ),
-                                ordering => ordering,
-                            }
-                        }
-                        ordering => ordering,
-                    }
-                }
-                ordering => ordering,
-            }
-        };
-
         //TODO: par_iter?
         for (backend_name, backend) in self.backends.iter() {
             for appstream_cache in backend.info_caches() {
                 for (id, info) in appstream_cache.infos.iter() {
+                    let installed = self.is_installed(backend_name, &info.source_id, id);
                     let entry = apps.entry(id.clone()).or_insert_with(|| Vec::new());
                     entry.push(AppEntry {
                         backend_name,
                         info: info.clone(),
-                        installed: self.is_installed(backend_name, &info.source_id, id),
+                        installed,
                     });
-                    entry.sort_by(|a, b| entry_sort(a, b, id));
+                    entry.sort_by(|a, b| AppEntry::cmp_preferred(a, b, &self.config, id));
                 }
             }
         }
@@ -1172,23 +1726,27 @@ impl App {
                         info: package.info.clone(),
                         installed: true,
                     });
-                    entry.sort_by(|a, b| entry_sort(a, b, &package.id));
+                    entry.sort_by(|a, b| AppEntry::cmp_preferred(a, b, &self.config, &package.id));
                 }
             }
         }
 
         self.apps = Arc::new(apps);
 
-        // Update selected sources
+        // Update selected sources and add-ons
         {
             let sources_opt = if let Some(selected) = &self.selected_opt {
-                Some(self.selected_sources(selected.backend_name, &selected.id, &selected.info))
+                Some((
+                    self.selected_sources(selected.backend_name, &selected.id, &selected.info),
+                    self.selected_addons(selected.backend_name, &selected.id, &selected.info),
+                ))
             } else {
                 None
             };
-            if let Some(sources) = sources_opt {
+            if let Some((sources, addons)) = sources_opt {
                 if let Some(selected) = &mut self.selected_opt {
                     selected.sources = sources;
+                    selected.addons = addons;
                 }
             }
         }
@@ -1338,20 +1896,37 @@ impl App {
         // https://freedesktop.org/software/appstream/docs/sect-AppStream-Misc-URIHandler.html
         let apps = self.apps.clone();
         let backends = self.backends.clone();
+        let scale = self.core.scale_factor();
         let component_id = AppId::new(path.trim_start_matches('/'));
         Command::perform(
             async move {
                 tokio::task::spawn_blocking(move || {
                     let start = Instant::now();
-                    let results =
-                        Self::generic_search(&apps, &backends, |id, _info, _installed| {
+                    // Components may have been renamed, with the new component providing
+                    // the old ID
+                    let renamed_ids: Vec<AppId> = backends
+                        .values()
+                        .flat_map(|backend| backend.info_caches())
+                        .filter_map(|appstream_cache| appstream_cache.renamed_id(&component_id))
+                        .cloned()
+                        .collect();
+                    let results = Self::generic_search(
+                        &apps,
+                        &backends,
+                        scale,
+                        // Apps requested by ID are shown even if they would be hidden
+                        AppFilter::default(),
+                        |id, _info, _installed| {
                             //TODO: fuzzy search with lower weight?
                             if id == &component_id {
                                 Some(0)
+                            } else if renamed_ids.contains(id) {
+                                Some(1)
                             } else {
                                 None
                             }
-                        });
+                        },
+                    );
                     let duration = start.elapsed();
                     log::info!(
                         "searched for ID {:?} in {:?}, found {} results",
@@ -1386,10 +1961,13 @@ impl App {
                     let start = Instant::now();
                     let mut packages = Vec::new();
                     for (backend_name, backend) in backends.iter() {
+                        if !backend.capabilities().file_install {
+                            continue;
+                        }
                         match backend.file_packages(&path) {
                             Ok(backend_packages) => {
                                 for package in backend_packages {
-                                    packages.push((backend_name, package));
+                                    packages.push((*backend_name, package));
                                 }
                             }
                             Err(err) => {
@@ -1409,8 +1987,16 @@ impl App {
                         duration,
                         packages.len()
                     );
-                    log::warn!("TODO: do something with {:?}", packages);
-                    message::none()
+                    //TODO: allow choosing between packages from multiple backends
+                    match packages.into_iter().next() {
+                        Some((backend_name, package)) => message::app(Message::Select(
+                            backend_name,
+                            package.id,
+                            package.icon,
+                            package.info,
+                        )),
+                        None => message::none(),
+                    }
                 })
                 .await
                 .unwrap_or(message::none())
@@ -1458,22 +2044,266 @@ impl App {
             AppTheme::Light => 2,
             AppTheme::System => 0,
         };
-        widget::settings::view_column(vec![widget::settings::view_section(fl!("appearance"))
-            .add(
-                widget::settings::item::builder(fl!("theme")).control(widget::dropdown(
-                    &self.app_themes,
-                    Some(app_theme_selected),
-                    move |index| {
-                        Message::AppTheme(match index {
-                            1 => AppTheme::Dark,
-                            2 => AppTheme::Light,
-                            _ => AppTheme::System,
-                        })
+
+        let mut backends_section = widget::settings::view_section(fl!("package-managers"));
+        for (i, backend_name) in self.backend_order().into_iter().enumerate() {
+            let mut prefer_button = widget::button::icon(widget::icon::from_name("go-up-symbolic"));
+            if i > 0 {
+                prefer_button = prefer_button.on_press(Message::BackendPrefer(backend_name));
+            }
+            backends_section = backends_section.add(
+                widget::settings::item::builder(backend::backend_title(backend_name)).control(
+                    widget::row::with_children(vec![
+                        prefer_button.into(),
+                        widget::toggler(
+                            None,
+                            self.config.backend_enabled(backend_name),
+                            move |enabled| Message::BackendEnabled(backend_name, enabled),
+                        )
+                        .into(),
+                    ])
+                    .align_items(Alignment::Center),
+                ),
+            );
+        }
+
+        let mut sources_section = widget::settings::view_section(fl!("sources"));
+        for (backend_name, repos) in self.repos.iter() {
+            let backend_name = *backend_name;
+            for repo in repos.iter() {
+                let repo_id = repo.id.clone();
+                sources_section = sources_section.add(
+                    widget::settings::item::builder(repo.name.clone())
+                        .description(backend::backend_title(backend_name))
+                        .toggler(repo.enabled, move |enabled| {
+                            Message::RepoEnabled(backend_name, repo_id.clone(), enabled)
+                        }),
+                );
+            }
+        }
+
+        let mut sections: Vec<Element<Message>> = vec![
+            widget::settings::view_section(fl!("appearance"))
+                .add(
+                    widget::settings::item::builder(fl!("theme")).control(widget::dropdown(
+                        &self.app_themes,
+                        Some(app_theme_selected),
+                        move |index| {
+                            Message::AppTheme(match index {
+                                1 => AppTheme::Dark,
+                                2 => AppTheme::Light,
+                                _ => AppTheme::System,
+                            })
+                        },
+                    )),
+                )
+                .into(),
+            backends_section.into(),
+        ];
+        if !self.repos.is_empty() {
+            sections.push(sources_section.into());
+        }
+        sections.extend([
+            self.parental_section().into(),
+            widget::settings::view_section(fl!("licenses"))
+                .add(
+                    widget::settings::item::builder(fl!("hide-proprietary"))
+                        .description(fl!("hide-proprietary-description"))
+                        .toggler(self.config.hide_proprietary, Message::HideProprietary),
+                )
+                .into(),
+            widget::settings::view_section(fl!("diagnostics"))
+                .add(
+                    widget::settings::item::builder(fl!("diagnostics-description")).control(
+                        widget::button::standard(fl!("view"))
+                            .on_press(Message::ToggleContextPage(ContextPage::Diagnostics)),
+                    ),
+                )
+                .into(),
+        ]);
+        widget::settings::view_column(sections).into()
+    }
+
+    fn parental_section(&self) -> widget::settings::Section<Message> {
+        let parental_controls = self.parental_controls();
+        let max_age_selected = match parental_controls.max_age_opt {
+            Some(max_age) => PARENTAL_AGES
+                .iter()
+                .position(|x| *x == max_age)
+                .map(|x| x + 1),
+            None => Some(0),
+        };
+        let mut section = widget::settings::view_section(fl!("parental-controls"));
+        if self.parental_policy_opt.is_some() {
+            // Controls are only shown as text, because they cannot be changed
+            let max_age = match max_age_selected {
+                Some(i) => self.parental_ages[i].clone(),
+                None => parental_controls
+                    .max_age_opt
+                    .map(|age| fl!("age-rating", age = age))
+                    .unwrap_or_default(),
+            };
+            section = section
+                .add(
+                    widget::settings::item::builder(fl!("parental-locked")).control(
+                        widget::icon::from_name("changes-prevent-symbolic")
+                            .size(16)
+                            .icon(),
+                    ),
+                )
+                .add(
+                    widget::settings::item::builder(fl!("parental-max-age"))
+                        .control(widget::text(max_age)),
+                )
+                .add(
+                    widget::settings::item::builder(fl!("parental-hide")).control(widget::text(
+                        if parental_controls.hide {
+                            fl!("yes")
+                        } else {
+                            fl!("no")
+                        },
+                    )),
+                )
+                .add(
+                    widget::settings::item::builder(fl!("parental-allow-unrated")).control(
+                        widget::text(if parental_controls.allow_unrated {
+                            fl!("yes")
+                        } else {
+                            fl!("no")
+                        }),
+                    ),
+                );
+        } else {
+            section = section
+                .add(
+                    widget::settings::item::builder(fl!("parental-max-age")).control(
+                        widget::dropdown(&self.parental_ages, max_age_selected, |index| {
+                            Message::ParentalMaxAge(
+                                index
+                                    .checked_sub(1)
+                                    .and_then(|i| PARENTAL_AGES.get(i).copied()),
+                            )
+                        }),
+                    ),
+                )
+                .add(
+                    widget::settings::item::builder(fl!("parental-hide"))
+                        .toggler(parental_controls.hide, Message::ParentalHide),
+                )
+                .add(
+                    widget::settings::item::builder(fl!("parental-allow-unrated")).toggler(
+                        parental_controls.allow_unrated,
+                        Message::ParentalAllowUnrated,
+                    ),
+                );
+        }
+        section
+    }
+
+    fn diagnostics(&self) -> Element<Message> {
+        let mut sections = Vec::new();
+
+        sections.push(
+            widget::settings::view_section(fl!("maintenance"))
+                .add(
+                    widget::settings::item::builder(fl!("force-refresh")).control(
+                        widget::button::standard(fl!("refresh")).on_press(Message::CheckUpdates),
+                    ),
+                )
+                .add(widget::settings::item::builder(fl!("clear-cache")).control(
+                    widget::button::destructive(fl!("clear")).on_press(Message::ClearCache),
+                ))
+                .into(),
+        );
+
+        for backend_name in self.backend_order() {
+            let mut section = widget::settings::view_section(backend::backend_title(backend_name));
+            let status = if !self.config.backend_enabled(backend_name) {
+                fl!("backend-disabled")
+            } else {
+                match self.backend_statuses.get(backend_name) {
+                    Some(status) => match status
+                        .init_error_opt
+                        .as_ref()
+                        .or(status.load_error_opt.as_ref())
+                    {
+                        Some(err) => fl!("backend-failed", error = err.as_str()),
+                        None => fl!("backend-loaded"),
                     },
-                )),
-            )
-            .into()])
-        .into()
+                    None => fl!("backend-loading"),
+                }
+            };
+            section = section
+                .add(widget::settings::item::builder(fl!("status")).control(widget::text(status)));
+            if let Some(status) = self.backend_statuses.get(backend_name) {
+                section = section.add(
+                    widget::settings::item::builder(fl!("initialized-in"))
+                        .control(widget::text(format!("{:?}", status.init_duration))),
+                );
+                if let Some(load_duration) = status.load_duration_opt {
+                    section = section.add(
+                        widget::settings::item::builder(fl!("caches-loaded-in"))
+                            .control(widget::text(format!("{:?}", load_duration))),
+                    );
+                }
+            }
+            sections.push(section.into());
+
+            let Some(backend) = self.backends.get(backend_name) else {
+                continue;
+            };
+            for appstream_cache in backend.info_caches() {
+                let cache_status = match &appstream_cache.status {
+                    AppstreamCacheStatus::Uncached => fl!("cache-uncached"),
+                    AppstreamCacheStatus::Hit => fl!("cache-hit"),
+                    AppstreamCacheStatus::Miss(reason) => {
+                        fl!("cache-miss", reason = reason.as_str())
+                    }
+                };
+                let mut section = widget::settings::view_section(format!(
+                    "{} ({})",
+                    appstream_cache.source_name, appstream_cache.source_id
+                ))
+                .add(
+                    widget::settings::item::builder(fl!("locale"))
+                        .control(widget::text(&appstream_cache.locale)),
+                )
+                .add(
+                    widget::settings::item::builder(fl!("items"))
+                        .control(widget::text(appstream_cache.infos.len().to_string())),
+                )
+                .add(
+                    widget::settings::item::builder(fl!("cache"))
+                        .control(widget::text(cache_status)),
+                );
+                for (path, tag) in appstream_cache.path_tags.iter() {
+                    let modified = match chrono::DateTime::<chrono::Utc>::from_timestamp(
+                        tag.modified as i64,
+                        0,
+                    ) {
+                        Some(utc) => chrono::DateTime::<chrono::Local>::from(utc)
+                            .format("%Y-%m-%d %H:%M:%S")
+                            .to_string(),
+                        None => tag.modified.to_string(),
+                    };
+                    section = section.add(widget::settings::item_row(vec![
+                        widget::column::with_children(vec![
+                            widget::text::body(path).into(),
+                            widget::text::caption(fl!(
+                                "path-tag",
+                                size = tag.size,
+                                modified = modified
+                            ))
+                            .into(),
+                        ])
+                        .into(),
+                    ]));
+                }
+                sections.push(section.into());
+            }
+        }
+
+        widget::settings::view_column(sections).into()
     }
 
     fn view_responsive(&self, size: Size) -> Element<Message> {
@@ -1527,15 +2357,16 @@ impl App {
                     }
                 }
                 let mut progress_opt = None;
-                for (_id, (op, progress)) in self.pending_operations.iter() {
+                for (id, (op, progress)) in self.pending_operations.iter() {
                     if op.backend_name == selected.backend_name
                         && &op.info.source_id == &selected.info.source_id
                         && &op.package_id == &selected.id
                     {
-                        progress_opt = Some(*progress);
+                        progress_opt = Some((*id, *progress));
                         break;
                     }
                 }
+                let capabilities = self.capabilities(selected.backend_name);
 
                 let mut selected_source = None;
                 for (i, source) in selected.sources.iter().enumerate() {
@@ -1558,13 +2389,20 @@ impl App {
                         .on_press(Message::SelectNone),
                 );
                 let mut buttons = Vec::with_capacity(2);
-                if let Some(progress) = progress_opt {
+                if let Some((op_id, progress)) = progress_opt {
                     //TODO: get height from theme?
                     buttons.push(
                         widget::progress_bar(0.0..=100.0, progress)
                             .height(Length::Fixed(4.0))
                             .into(),
-                    )
+                    );
+                    if capabilities.cancel {
+                        buttons.push(
+                            widget::button::standard(fl!("cancel"))
+                                .on_press(Message::CancelOperation(op_id))
+                                .into(),
+                        );
+                    }
                 } else if waiting_refresh {
                     // Do not show buttons while waiting for refresh
                 } else if is_installed {
@@ -1583,6 +2421,18 @@ impl App {
                                 .into(),
                         );
                     }
+                    if capabilities.rollback && !selected.id.is_system() {
+                        buttons.push(
+                            widget::button::standard(fl!("rollback"))
+                                .on_press(Message::Operation(
+                                    OperationKind::Rollback,
+                                    selected.backend_name,
+                                    selected.id.clone(),
+                                    selected.info.clone(),
+                                ))
+                                .into(),
+                        );
+                    }
                     if !selected.id.is_system() {
                         buttons.push(
                             widget::button::destructive(fl!("uninstall"))
@@ -1594,7 +2444,7 @@ impl App {
                                 .into(),
                         );
                     }
-                } else {
+                } else if self.parental_controls().allows(&selected.info) {
                     buttons.push(
                         widget::button::suggested(fl!("install"))
                             .on_press(Message::Operation(
@@ -1605,6 +2455,29 @@ impl App {
                             ))
                             .into(),
                     )
+                } else {
+                    buttons.push(widget::button::suggested(fl!("install")).into());
+                    buttons.push(widget::text::body(fl!("parental-blocked")).into());
+                }
+                let license_badge = selected.info.license_kind().title();
+                let mut title_row = widget::row::with_capacity(3)
+                    .push(widget::text::title2(&selected.info.name))
+                    .push(
+                        widget::container(widget::text::caption_heading(license_badge))
+                            .padding([space_xxxs, space_xxs])
+                            .style(theme::Container::Card),
+                    )
+                    .align_items(Alignment::Center)
+                    .spacing(space_xs);
+                if let Some(content_rating) = &selected.info.content_rating_opt {
+                    title_row = title_row.push(
+                        widget::container(widget::text::caption_heading(fl!(
+                            "age-rating",
+                            age = content_rating.minimum_age()
+                        )))
+                        .padding([space_xxxs, space_xxs])
+                        .style(theme::Container::Card),
+                    );
                 }
                 column = column.push(
                     widget::row::with_children(vec![
@@ -1612,7 +2485,7 @@ impl App {
                             .size(ICON_SIZE_DETAILS)
                             .into(),
                         widget::column::with_children(vec![
-                            widget::text::title2(&selected.info.name).into(),
+                            title_row.into(),
                             widget::text(&selected.info.summary).into(),
                             widget::vertical_space(Length::Fixed(space_s.into())).into(),
                             widget::row::with_children(buttons).spacing(space_xs).into(),
@@ -1735,7 +2608,7 @@ impl App {
                     }
                     column = column.push(row);
                 }
-                column = column.push(widget::text::body(&selected.info.description));
+                column = column.push(selected.info.description.view(space_xxs));
 
                 for release in selected.info.releases.iter() {
                     let mut release_col = widget::column::with_capacity(2).spacing(space_xxxs);
@@ -1754,13 +2627,139 @@ impl App {
                         }
                     }
                     if let Some(description) = &release.description {
-                        release_col = release_col.push(widget::text::body(description));
+                        release_col = release_col.push(description.view(space_xxs));
                     }
                     column = column.push(release_col);
                     //TODO: show more releases, or make sure this is the latest?
                     break;
                 }
 
+                if let Some(content_rating) = &selected.info.content_rating_opt {
+                    let details = content_rating.details();
+                    let mut rating_col =
+                        widget::column::with_capacity(details.len() + 1).spacing(space_xxxs);
+                    rating_col = rating_col.push(widget::text::title4(fl!(
+                        "content-rating-title",
+                        age = content_rating.minimum_age()
+                    )));
+                    if details.is_empty() {
+                        rating_col =
+                            rating_col.push(widget::text::body(fl!("content-rating-all-ages")));
+                    }
+                    for (attribute, intensity) in details {
+                        rating_col = rating_col
+                            .push(widget::text::body(format!("{}: {}", attribute, intensity)));
+                    }
+                    column = column.push(rating_col);
+                }
+
+                if selected.info.project_license_opt.is_some() || !selected.info.urls.is_empty() {
+                    let mut links_col = widget::column::with_capacity(3).spacing(space_xxxs);
+                    links_col = links_col.push(widget::text::title4(fl!("links")));
+                    if let Some(project_license) = &selected.info.project_license_opt {
+                        let license_kind = license::kind(project_license).title();
+                        links_col = links_col.push(widget::text::body(format!(
+                            "{}: {} ({})",
+                            fl!("license"),
+                            project_license,
+                            license_kind
+                        )));
+                    }
+                    if !selected.info.urls.is_empty() {
+                        let mut links = Vec::with_capacity(selected.info.urls.len());
+                        for app_url in selected.info.urls.iter() {
+                            links.push(
+                                widget::button::link(app_url.kind.title())
+                                    .on_press(Message::OpenUrl(app_url.url.clone()))
+                                    .into(),
+                            );
+                        }
+                        links_col = links_col.push(
+                            widget::flex_row(links)
+                                .column_spacing(space_s)
+                                .row_spacing(space_xxxs),
+                        );
+                    }
+                    column = column.push(links_col);
+                }
+
+                if let Some(permissions) = &selected.permissions_opt {
+                    let mut permissions_col =
+                        widget::column::with_capacity(permissions.len() + 1).spacing(space_xxxs);
+                    permissions_col =
+                        permissions_col.push(widget::text::title4(fl!("permissions")));
+                    if permissions.is_empty() {
+                        permissions_col =
+                            permissions_col.push(widget::text::body(fl!("no-permissions")));
+                    }
+                    for permission in permissions.iter() {
+                        permissions_col = permissions_col.push(widget::text::body(permission));
+                    }
+                    column = column.push(permissions_col);
+                }
+
+                if !selected.addons.is_empty() {
+                    let mut addon_col =
+                        widget::column::with_capacity(selected.addons.len() + 1).spacing(space_xxs);
+                    addon_col = addon_col.push(widget::text::title4(fl!("addons")));
+                    for (addon_id, addon_icon, addon_info) in selected.addons.iter() {
+                        let mut addon_progress_opt = None;
+                        for (_id, (op, progress)) in self.pending_operations.iter() {
+                            if op.backend_name == selected.backend_name
+                                && &op.info.source_id == &addon_info.source_id
+                                && &op.package_id == addon_id
+                            {
+                                addon_progress_opt = Some(*progress);
+                                break;
+                            }
+                        }
+                        let control: Element<_> = if let Some(progress) = addon_progress_opt {
+                            widget::progress_bar(0.0..=100.0, progress)
+                                .width(Length::Fixed(96.0))
+                                .height(Length::Fixed(4.0))
+                                .into()
+                        } else if self.is_installed(
+                            selected.backend_name,
+                            &addon_info.source_id,
+                            addon_id,
+                        ) {
+                            widget::button::destructive(fl!("uninstall"))
+                                .on_press(Message::DialogPage(DialogPage::Uninstall(
+                                    selected.backend_name,
+                                    addon_id.clone(),
+                                    addon_info.clone(),
+                                )))
+                                .into()
+                        } else {
+                            widget::button::standard(fl!("install"))
+                                .on_press(Message::Operation(
+                                    OperationKind::Install,
+                                    selected.backend_name,
+                                    addon_id.clone(),
+                                    addon_info.clone(),
+                                ))
+                                .into()
+                        };
+                        addon_col = addon_col.push(
+                            widget::row::with_children(vec![
+                                widget::icon::icon(addon_icon.clone())
+                                    .size(ICON_SIZE_SEARCH)
+                                    .into(),
+                                widget::column::with_children(vec![
+                                    widget::text::body(&addon_info.name).into(),
+                                    widget::text::caption(&addon_info.summary).into(),
+                                ])
+                                .width(Length::Fill)
+                                .into(),
+                                control,
+                            ])
+                            .align_items(Alignment::Center)
+                            .spacing(space_s),
+                        );
+                    }
+                    column = column.push(addon_col);
+                }
+
                 column.into()
             }
             None => match &self.search_results {
@@ -1823,6 +2822,11 @@ impl App {
                                     }
                                     None => {
                                         //TODO: loading message?
+                                        if !self.caches_rebuilding.is_empty() {
+                                            column = column.push(widget::text::body(fl!(
+                                                "rebuilding-catalog"
+                                            )));
+                                        }
                                     }
                                 }
                                 column.into()
@@ -1940,6 +2944,10 @@ impl App {
                             }
                             None => {
                                 //TODO: loading message?
+                                if !self.caches_rebuilding.is_empty() {
+                                    column =
+                                        column.push(widget::text::body(fl!("rebuilding-catalog")));
+                                }
                             }
                         }
                         column.into()
@@ -1960,12 +2968,50 @@ impl App {
                                             .on_press(Message::CheckUpdates),
                                     );
                                 } else {
-                                    column = column.push(widget::row::with_children(vec![
-                                        widget::button::standard(fl!("update-all"))
-                                            .on_press(Message::UpdateAll)
-                                            .into(),
-                                        widget::horizontal_space(Length::Fill).into(),
-                                    ]));
+                                    let mut row = widget::row::with_capacity(4)
+                                        .align_items(Alignment::Center)
+                                        .spacing(space_xs)
+                                        .push(
+                                            widget::button::standard(fl!("update-all"))
+                                                .on_press(Message::UpdateAll),
+                                        );
+                                    if updates.iter().any(|(backend_name, _)| {
+                                        self.capabilities(backend_name).offline_update
+                                    }) {
+                                        let mut button =
+                                            widget::button::standard(fl!("update-on-restart"));
+                                        if !matches!(
+                                            self.offline_update_opt,
+                                            Some(
+                                                OfflineUpdateState::Preparing
+                                                    | OfflineUpdateState::Ready
+                                            )
+                                        ) {
+                                            button = button.on_press(Message::OfflineUpdate);
+                                        }
+                                        row = row.push(button);
+                                        match &self.offline_update_opt {
+                                            Some(OfflineUpdateState::Preparing) => {
+                                                row = row.push(widget::text::body(fl!(
+                                                    "offline-update-preparing"
+                                                )));
+                                            }
+                                            Some(OfflineUpdateState::Ready) => {
+                                                row = row.push(widget::text::body(fl!(
+                                                    "offline-update-ready"
+                                                )));
+                                            }
+                                            Some(OfflineUpdateState::Failed(err)) => {
+                                                row = row.push(widget::text::body(fl!(
+                                                    "offline-update-failed",
+                                                    error = err.as_str()
+                                                )));
+                                            }
+                                            None => {}
+                                        }
+                                    }
+                                    column = column
+                                        .push(row.push(widget::horizontal_space(Length::Fill)));
                                 }
 
                                 let GridMetrics {
@@ -2067,6 +3113,10 @@ impl App {
                             }
                             None => {
                                 //TODO: loading message?
+                                if !self.caches_rebuilding.is_empty() {
+                                    column =
+                                        column.push(widget::text::body(fl!("rebuilding-catalog")));
+                                }
                             }
                         }
                         column.into()
@@ -2111,6 +3161,11 @@ impl Application for App {
 
         let app_themes = vec![fl!("match-desktop"), fl!("dark"), fl!("light")];
 
+        let mut parental_ages = vec![fl!("parental-max-age-off")];
+        for age in PARENTAL_AGES.iter() {
+            parental_ages.push(fl!("age-rating", age = age));
+        }
+
         let mut nav_model = widget::nav_bar::Model::default();
         for &nav_page in NavPage::all() {
             let id = nav_model
@@ -2132,8 +3187,15 @@ impl Application for App {
             config: flags.config,
             locale,
             app_themes,
+            parental_ages,
+            parental_policy_opt: ParentalControls::policy(),
             apps: Arc::new(Apps::new()),
             backends: Backends::new(),
+            descriptions: Arc::new(Descriptions::new()),
+            backend_statuses: BackendStatuses::new(),
+            repos: BTreeMap::new(),
+            caches_rebuilding: Vec::new(),
+            watch_paths: Vec::new(),
             context_page: ContextPage::Settings,
             dialog_pages: VecDeque::new(),
             explore_page_opt: None,
@@ -2142,7 +3204,10 @@ impl Application for App {
             notification_opt: None,
             pending_operation_id: 0,
             pending_operations: BTreeMap::new(),
+            reload_pending: false,
             failed_operations: BTreeMap::new(),
+            offline_update_opt: None,
+            simulated_opt: None,
             scrollable_id: widget::Id::unique(),
             scroll_views: HashMap::new(),
             search_active: false,
@@ -2158,6 +3223,7 @@ impl Application for App {
             installed_results: None,
             search_results: None,
             selected_opt: None,
+            remote_icons_requested: HashSet::new(),
         };
 
         let command = Command::batch([app.update_title(), app.update_backends(false)]);
@@ -2267,13 +3333,94 @@ impl Application for App {
                 config_set!(app_theme, app_theme);
                 return self.update_config();
             }
-            Message::Backends(backends) => {
+            Message::AppstreamChanged => {
+                // Operations change installed state, so reloading waits for them to complete
+                if self.pending_operations.is_empty() {
+                    log::info!("appstream data changed, reloading");
+                    return self.update_backends(false);
+                } else {
+                    log::info!("appstream data changed, reloading after operations complete");
+                    self.reload_pending = true;
+                }
+            }
+            Message::BackendEnabled(backend_name, enabled) => {
+                let mut disabled_backends = self.config.disabled_backends.clone();
+                disabled_backends.retain(|x| x != backend_name);
+                if !enabled {
+                    disabled_backends.push(backend_name.to_string());
+                }
+                config_set!(disabled_backends, disabled_backends);
+                return self.update_backends(false);
+            }
+            Message::BackendPrefer(backend_name) => {
+                // Move backend one position earlier in the preferred order
+                let mut backend_order = self.backend_order();
+                if let Some(i) = backend_order.iter().position(|x| *x == backend_name) {
+                    if i > 0 {
+                        backend_order.swap(i - 1, i);
+                    }
+                }
+                config_set!(
+                    backend_order,
+                    backend_order.into_iter().map(|x| x.to_string()).collect()
+                );
+                return self.update_backend_order();
+            }
+            Message::Backends(backends, backend_statuses, descriptions) => {
                 self.backends = backends;
-                return Command::batch([self.update_installed(), self.update_updates()]);
+                self.backend_statuses = backend_statuses;
+                self.descriptions = descriptions;
+                self.caches_rebuilding.clear();
+                self.watch_paths.clear();
+                for backend in self.backends.values() {
+                    for path in backend.watch_paths() {
+                        if !self.watch_paths.contains(&path) {
+                            self.watch_paths.push(path);
+                        }
+                    }
+                }
+                self.watch_paths.sort();
+                self.repos.clear();
+                return Command::batch([
+                    self.update_installed(),
+                    self.update_updates(),
+                    self.update_repos(),
+                ]);
+            }
+            Message::CancelOperation(id) => {
+                let Some((op, _)) = self.pending_operations.get(&id) else {
+                    return Command::none();
+                };
+                let Some(backend) = self.backends.get(op.backend_name).cloned() else {
+                    return Command::none();
+                };
+                if !backend.capabilities().cancel {
+                    log::warn!("backend {:?} cannot cancel operations", op.backend_name);
+                    return Command::none();
+                }
+                let package_id = op.package_id.clone();
+                return Command::perform(
+                    async move {
+                        tokio::task::spawn_blocking(move || {
+                            if let Err(err) = backend.cancel(&package_id) {
+                                log::warn!(
+                                    "failed to cancel operation on {:?}: {}",
+                                    package_id,
+                                    err
+                                );
+                            }
+                            message::none()
+                        })
+                        .await
+                        .unwrap_or(message::none())
+                    },
+                    |x| x,
+                );
             }
             Message::CategoryResults(categories, results) => {
+                let remote_icons_command = self.fetch_remote_icons(results_infos(&results));
                 self.category_results = Some((categories, results));
-                return self.update_scroll();
+                return Command::batch([self.update_scroll(), remote_icons_command]);
             }
             Message::CheckUpdates => {
                 //TODO: this only checks updates if they have already been checked
@@ -2287,19 +3434,53 @@ impl Application for App {
                     log::warn!("already checking for updates");
                 }
             }
+            Message::ClearCache => {
+                if self.pending_operations.is_empty() {
+                    for backend in self.backends.values() {
+                        for appstream_cache in backend.info_caches() {
+                            if let Err(err) =
+                                appstream_cache.remove_cache(&appstream_cache.source_id)
+                            {
+                                log::warn!("failed to clear cache: {}", err);
+                            }
+                        }
+                    }
+                    return self.update_backends(false);
+                } else {
+                    log::warn!("cannot clear cache, operations are in progress");
+                }
+            }
             Message::Config(config) => {
                 if config != self.config {
                     log::info!("update config");
+                    let backends_changed = config.disabled_backends
+                        != self.config.disabled_backends
+                        || config.appimage_dir != self.config.appimage_dir;
+                    let backend_order_changed = config.backend_order != self.config.backend_order;
+                    let results_changed = config.parental_controls != self.config.parental_controls
+                        || config.hide_proprietary != self.config.hide_proprietary;
                     //TODO: update syntax theme by clearing tabs, only if needed
                     self.config = config;
-                    return self.update_config();
+                    let mut commands = vec![self.update_config()];
+                    if backends_changed {
+                        commands.push(self.update_backends(false));
+                    } else if backend_order_changed {
+                        commands.push(self.update_backend_order());
+                    } else if results_changed {
+                        commands.push(self.refresh_results());
+                    }
+                    return Command::batch(commands);
                 }
             }
             Message::DialogCancel => {
-                self.dialog_pages.pop_front();
+                self.simulated_opt = None;
+                if let Some(DialogPage::FailedOperation(id)) = self.dialog_pages.pop_front() {
+                    self.failed_operations.remove(&id);
+                }
             }
             Message::DialogConfirm => match self.dialog_pages.pop_front() {
                 Some(DialogPage::Uninstall(backend_name, id, info)) => {
+                    self.simulated_opt = None;
                     return self.update(Message::Operation(
                         OperationKind::Uninstall,
                         backend_name,
@@ -2307,17 +3488,38 @@ impl Application for App {
                         info,
                     ));
                 }
-                _ => {}
+                Some(DialogPage::FailedOperation(id)) => {
+                    if let Some((op, err)) = self.failed_operations.remove(&id) {
+                        if err.is_retryable() {
+                            log::info!("retrying operation {id}");
+                            self.operation(op);
+                        }
+                    }
+                }
+                None => {}
             },
             Message::DialogPage(dialog_page) => {
+                let command = match &dialog_page {
+                    DialogPage::Uninstall(backend_name, id, info) => {
+                        self.simulate_uninstall(*backend_name, id.clone(), info.clone())
+                    }
+                    DialogPage::FailedOperation(_) => Command::none(),
+                };
                 self.dialog_pages.push_back(dialog_page);
+                return command;
             }
             Message::ExplorePage(explore_page_opt) => {
                 self.explore_page_opt = explore_page_opt;
                 return self.update_scroll();
             }
             Message::ExploreResults(explore_page, results) => {
+                let remote_icons_command = self.fetch_remote_icons(results_infos(&results));
                 self.explore_results.insert(explore_page, results);
+                return remote_icons_command;
+            }
+            Message::HideProprietary(hide_proprietary) => {
+                config_set!(hide_proprietary, hide_proprietary);
+                return self.refresh_results();
             }
             Message::Installed(installed) => {
                 self.installed = Some(installed);
@@ -2333,7 +3535,10 @@ impl Application for App {
                 return Command::batch(commands);
             }
             Message::InstalledResults(installed_results) => {
+                let remote_icons_command =
+                    self.fetch_remote_icons(results_infos(&installed_results));
                 self.installed_results = Some(installed_results);
+                return remote_icons_command;
             }
             Message::Key(modifiers, key) => {
                 for (key_bind, action) in self.key_binds.iter() {
@@ -2351,10 +3556,64 @@ impl Application for App {
             Message::Notification(notification) => {
                 self.notification_opt = Some(notification);
             }
+            Message::OfflineUpdate => {
+                if self.offline_update_opt == Some(OfflineUpdateState::Preparing) {
+                    log::warn!("already preparing offline update");
+                    return Command::none();
+                }
+                let backends: Vec<_> = self
+                    .backends
+                    .iter()
+                    .filter(|(_, backend)| backend.capabilities().offline_update)
+                    .map(|(backend_name, backend)| (*backend_name, backend.clone()))
+                    .collect();
+                self.offline_update_opt = Some(OfflineUpdateState::Preparing);
+                return Command::perform(
+                    async move {
+                        tokio::task::spawn_blocking(move || {
+                            for (backend_name, backend) in backends {
+                                if let Err(err) = backend.offline_update() {
+                                    log::warn!(
+                                        "failed to prepare {} offline update: {}",
+                                        backend_name,
+                                        err
+                                    );
+                                    return message::app(Message::OfflineUpdateResult(Err(
+                                        err.to_string()
+                                    )));
+                                }
+                            }
+                            message::app(Message::OfflineUpdateResult(Ok(())))
+                        })
+                        .await
+                        .unwrap_or(message::none())
+                    },
+                    |x| x,
+                );
+            }
+            Message::OfflineUpdateResult(res) => {
+                self.offline_update_opt = Some(match res {
+                    Ok(()) => OfflineUpdateState::Ready,
+                    Err(err) => OfflineUpdateState::Failed(err),
+                });
+            }
             Message::OpenDesktopId(desktop_id) => {
                 return self.open_desktop_id(desktop_id);
             }
+            Message::OpenUrl(url) => match process::Command::new("xdg-open").arg(&url).spawn() {
+                Ok(_child) => {}
+                Err(err) => {
+                    log::error!("failed to open {:?}: {}", url, err);
+                }
+            },
             Message::Operation(kind, backend_name, package_id, info) => {
+                if kind == OperationKind::Install && !self.parental_controls().allows(&info) {
+                    log::warn!(
+                        "installing {:?} is blocked by parental controls",
+                        package_id
+                    );
+                    return Command::none();
+                }
                 self.operation(Operation {
                     kind,
                     backend_name,
@@ -2362,6 +3621,36 @@ impl Application for App {
                     info,
                 });
             }
+            Message::ParentalHide(hide) => {
+                if self.parental_policy_opt.is_some() {
+                    log::warn!("parental controls are set by system policy");
+                } else {
+                    let mut parental_controls = self.config.parental_controls;
+                    parental_controls.hide = hide;
+                    config_set!(parental_controls, parental_controls);
+                    return self.refresh_results();
+                }
+            }
+            Message::ParentalMaxAge(max_age_opt) => {
+                if self.parental_policy_opt.is_some() {
+                    log::warn!("parental controls are set by system policy");
+                } else {
+                    let mut parental_controls = self.config.parental_controls;
+                    parental_controls.max_age_opt = max_age_opt;
+                    config_set!(parental_controls, parental_controls);
+                    return self.refresh_results();
+                }
+            }
+            Message::ParentalAllowUnrated(allow_unrated) => {
+                if self.parental_policy_opt.is_some() {
+                    log::warn!("parental controls are set by system policy");
+                } else {
+                    let mut parental_controls = self.config.parental_controls;
+                    parental_controls.allow_unrated = allow_unrated;
+                    config_set!(parental_controls, parental_controls);
+                    return self.refresh_results();
+                }
+            }
             Message::PendingComplete(id) => {
                 if let Some((op, _)) = self.pending_operations.remove(&id) {
                     self.waiting_installed.push((
@@ -2376,6 +3665,13 @@ impl Application for App {
                     ));
                     //TODO: self.complete_operations.insert(id, op);
                 }
+                if self.reload_pending && self.pending_operations.is_empty() {
+                    self.reload_pending = false;
+                    return Command::batch([
+                        self.update_notification(),
+                        self.update_backends(false),
+                    ]);
+                }
                 return Command::batch([
                     self.update_notification(),
                     self.update_installed(),
@@ -2383,10 +3679,31 @@ impl Application for App {
                 ]);
             }
             Message::PendingError(id, err) => {
-                log::warn!("operation {id} failed: {err}");
+                let cancelled = matches!(err, BackendError::Cancelled(_));
+                if cancelled {
+                    log::info!("operation {id} cancelled: {err}");
+                } else {
+                    log::warn!("operation {id} failed: {err}");
+                }
                 if let Some((op, _)) = self.pending_operations.remove(&id) {
-                    self.failed_operations.insert(id, (op, err));
-                    self.dialog_pages.push_back(DialogPage::FailedOperation(id));
+                    // Cancelling is not a failure, but may leave changes behind
+                    if cancelled {
+                        self.waiting_installed.push((
+                            op.backend_name,
+                            op.info.source_id.clone(),
+                            op.package_id.clone(),
+                        ));
+                    } else {
+                        self.failed_operations.insert(id, (op, err));
+                        self.dialog_pages.push_back(DialogPage::FailedOperation(id));
+                    }
+                }
+                if self.reload_pending && self.pending_operations.is_empty() {
+                    self.reload_pending = false;
+                    return self.update_backends(false);
+                }
+                if cancelled {
+                    return self.update_installed();
                 }
             }
             Message::PendingProgress(id, new_progress) => {
@@ -2395,6 +3712,59 @@ impl Application for App {
                 }
                 return self.update_notification();
             }
+            Message::Permissions(backend_name, id, permissions) => {
+                if let Some(selected) = &mut self.selected_opt {
+                    if selected.backend_name == backend_name && selected.id == id {
+                        selected.permissions_opt = Some(permissions);
+                    }
+                }
+            }
+            Message::RemoteIcon(url) => {
+                self.remote_icons_requested.remove(&url);
+                self.update_remote_icon(&url);
+            }
+            Message::RemoteIconFailed(url) => {
+                // Fetched again once the backoff expires
+                self.remote_icons_requested.remove(&url);
+            }
+            Message::RepoEnabled(backend_name, repo_id, enabled) => {
+                let Some(backend) = self.backends.get(backend_name).cloned() else {
+                    return Command::none();
+                };
+                return Command::perform(
+                    async move {
+                        tokio::task::spawn_blocking(move || {
+                            match backend.set_repo_enabled(&repo_id, enabled) {
+                                Ok(()) => message::app(Message::ReposChanged),
+                                Err(err) => {
+                                    log::warn!(
+                                        "failed to set {} source {:?} enabled to {}: {}",
+                                        backend_name,
+                                        repo_id,
+                                        enabled,
+                                        err
+                                    );
+                                    message::none()
+                                }
+                            }
+                        })
+                        .await
+                        .unwrap_or(message::none())
+                    },
+                    |x| x,
+                );
+            }
+            Message::Repos(backend_name, repos) => {
+                self.repos.insert(backend_name, repos);
+            }
+            Message::ReposChanged => {
+                // Sources change which apps are available, so backends are reloaded
+                if self.pending_operations.is_empty() {
+                    return self.update_backends(false);
+                } else {
+                    self.reload_pending = true;
+                }
+            }
             Message::ScrollView(viewport) => {
                 self.scroll_views.insert(self.scroll_context(), viewport);
             }
@@ -2422,8 +3792,9 @@ impl Application for App {
                 if input == self.search_input {
                     // Clear selected item so search results can be shown
                     self.selected_opt = None;
+                    let remote_icons_command = self.fetch_remote_icons(results_infos(&results));
                     self.search_results = Some((input, results));
-                    return self.update_scroll();
+                    return Command::batch([self.update_scroll(), remote_icons_command]);
                 } else {
                     log::warn!(
                         "received {} results for {:?} after search changed to {:?}",
@@ -2441,6 +3812,9 @@ impl Application for App {
             Message::Select(backend_name, id, icon, info) => {
                 return self.select(backend_name, id, icon, info);
             }
+            Message::Simulated(id, package_names) => {
+                self.simulated_opt = Some((id, package_names));
+            }
             Message::SelectInstalled(result_i) => {
                 if let Some(results) = &self.installed_results {
                     match results.get(result_i) {
@@ -2632,8 +4006,11 @@ impl Application for App {
                 }
             }
             Message::Updates(updates) => {
+                let remote_icons_command =
+                    self.fetch_remote_icons(updates.iter().map(|(_, package)| &*package.info));
                 self.updates = Some(updates);
                 self.waiting_updates.clear();
+                return remote_icons_command;
             }
             Message::WindowClose => {
                 if let Some(window_id) = self.window_id_opt.take() {
@@ -2665,6 +4042,7 @@ impl Application for App {
         }
 
         Some(match self.context_page {
+            ContextPage::Diagnostics => self.diagnostics(),
             ContextPage::Settings => self.settings(),
         })
     }
@@ -2681,17 +4059,41 @@ impl Application for App {
                 let (operation, err) = self.failed_operations.get(id)?;
 
                 let (title, body) = operation.failed_dialog(&err);
-                widget::dialog(title)
+                let dialog = widget::dialog(title)
                     .body(body)
-                    .icon(widget::icon::from_name("dialog-error").size(64))
-                    //TODO: retry action
-                    .primary_action(
-                        widget::button::standard(fl!("cancel")).on_press(Message::DialogCancel),
+                    .icon(widget::icon::from_name("dialog-error").size(64));
+                // Retrying cannot help with errors like denied authentication or a full disk
+                if err.is_retryable() {
+                    dialog
+                        .primary_action(
+                            widget::button::suggested(fl!("retry"))
+                                .on_press(Message::DialogConfirm),
+                        )
+                        .secondary_action(
+                            widget::button::standard(fl!("cancel")).on_press(Message::DialogCancel),
+                        )
+                } else {
+                    dialog.primary_action(
+                        widget::button::standard(fl!("close")).on_press(Message::DialogCancel),
                     )
+                }
             }
-            DialogPage::Uninstall(_backend_name, _id, info) => {
+            DialogPage::Uninstall(_backend_name, id, info) => {
+                let mut body = fl!("uninstall-app-warning", name = info.name.as_str());
+                if let Some((simulated_id, package_names)) = &self.simulated_opt {
+                    // Show packages removed along with the app, like dependents and add-ons
+                    let others: Vec<&str> = package_names
+                        .iter()
+                        .filter(|x| simulated_id == id && !info.pkgnames.contains(*x))
+                        .map(|x| x.as_str())
+                        .collect();
+                    if !others.is_empty() {
+                        body.push_str("\n\n");
+                        body.push_str(&fl!("uninstall-also-removes", packages = others.join(", ")));
+                    }
+                }
                 widget::dialog(fl!("uninstall-app", name = info.name.as_str()))
-                    .body(fl!("uninstall-app-warning", name = info.name.as_str()))
+                    .body(body)
                     .icon(widget::icon::from_name(Self::APP_ID).size(64))
                     .primary_action(
                         widget::button::destructive(fl!("uninstall"))
@@ -2722,6 +4124,14 @@ impl Application for App {
         }]
     }
 
+    fn header_end(&self) -> Vec<Element<Message>> {
+        vec![
+            widget::button::icon(widget::icon::from_name("preferences-system-symbolic"))
+                .on_press(Message::ToggleContextPage(ContextPage::Settings))
+                .into(),
+        ]
+    }
+
     /// Creates a view after each update.
     fn view(&self) -> Element<Self::Message> {
         let content: Element<_> = widget::responsive(move |mut size| {
@@ -2764,7 +4174,7 @@ impl Application for App {
                 if !update.errors.is_empty() {
                     log::debug!("errors loading config: {:?}", update.errors);
                 }
-                Message::SystemThemeModeChange(update.config)
+                Message::Config(update.config)
             }),
             cosmic_config::config_subscription::<_, cosmic_theme::ThemeMode>(
                 TypeId::of::<ThemeSubscription>(),
@@ -2826,6 +4236,56 @@ impl Application for App {
             }
         }
 
+        if !self.watch_paths.is_empty() {
+            struct WatchSubscription;
+            let watch_paths = self.watch_paths.clone();
+            subscriptions.push(subscription::channel(
+                (TypeId::of::<WatchSubscription>(), watch_paths.clone()),
+                1,
+                move |mut msg_tx| async move {
+                    tokio::task::spawn_blocking(move || {
+                        let (event_tx, event_rx) = mpsc::channel();
+                        let mut watcher = match notify::recommended_watcher(event_tx) {
+                            Ok(ok) => ok,
+                            Err(err) => {
+                                log::warn!("failed to create appstream watcher: {}", err);
+                                return;
+                            }
+                        };
+                        for path in watch_paths.iter() {
+                            if let Err(err) =
+                                watcher.watch(path, notify::RecursiveMode::NonRecursive)
+                            {
+                                log::warn!("failed to watch {:?}: {}", path, err);
+                            }
+                        }
+                        while let Ok(event_res) = event_rx.recv() {
+                            match event_res {
+                                // Reading appstream files while reloading causes access events
+                                Ok(event) if event.kind.is_access() => continue,
+                                Ok(event) => log::debug!("appstream change: {:?}", event),
+                                Err(err) => {
+                                    log::warn!("failed to watch appstream: {}", err);
+                                    continue;
+                                }
+                            }
+                            // Updates write many files, wait until they are done
+                            while event_rx.recv_timeout(WATCH_DELAY).is_ok() {}
+                            // Stop watching once the subscription is dropped
+                            let send = msg_tx.send(Message::AppstreamChanged);
+                            if futures::executor::block_on(send).is_err() {
+                                break;
+                            }
+                        }
+                    })
+                    .await
+                    .unwrap();
+
+                    pending().await
+                },
+            ));
+        }
+
         for (id, (op, _)) in self.pending_operations.iter() {
             //TODO: use recipe?
             let id = *id;
@@ -2837,27 +4297,28 @@ impl Application for App {
                     Some(backend) => {
                         let msg_tx = msg_tx.clone();
                         tokio::task::spawn_blocking(move || {
-                            backend
-                                .operation(
-                                    op.kind,
-                                    &op.package_id,
-                                    &op.info,
-                                    Box::new(move |progress| -> () {
-                                        let _ = futures::executor::block_on(async {
-                                            msg_tx
-                                                .lock()
-                                                .await
-                                                .send(Message::PendingProgress(id, progress))
-                                                .await
-                                        });
-                                    }),
-                                )
-                                .map_err(|err| err.to_string())
+                            backend.operation(
+                                op.kind,
+                                &op.package_id,
+                                &op.info,
+                                Box::new(move |progress| -> () {
+                                    let _ = futures::executor::block_on(async {
+                                        msg_tx
+                                            .lock()
+                                            .await
+                                            .send(Message::PendingProgress(id, progress))
+                                            .await
+                                    });
+                                }),
+                            )
                         })
                         .await
                         .unwrap()
                     }
-                    None => Err(format!("backend {:?} not found", op.backend_name)),
+                    None => Err(BackendError::Unavailable(format!(
+                        "backend {:?} not found",
+                        op.backend_name
+                    ))),
                 };
 
                 match res {
@@ -2868,7 +4329,7 @@ impl Application for App {
                         let _ = msg_tx
                             .lock()
                             .await
-                            .send(Message::PendingError(id, err.to_string()))
+                            .send(Message::PendingError(id, err))
                             .await;
                     }
                 }
@@ -2917,3 +4378,85 @@ impl Application for App {
         Subscription::batch(subscriptions)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(backend_name: &'static str, source_id: &str, installed: bool) -> AppEntry {
+        AppEntry {
+            backend_name,
+            info: Arc::new(AppInfo {
+                source_id: source_id.to_string(),
+                ..Default::default()
+            }),
+            installed,
+        }
+    }
+
+    fn sorted(
+        config: &Config,
+        id: &AppId,
+        mut entries: Vec<AppEntry>,
+    ) -> Vec<(&'static str, String)> {
+        entries.sort_by(|a, b| AppEntry::cmp_preferred(a, b, config, id));
+        entries
+            .into_iter()
+            .map(|x| (x.backend_name, x.info.source_id.clone()))
+            .collect()
+    }
+
+    fn entries() -> Vec<AppEntry> {
+        vec![
+            entry("packagekit", "fedora", false),
+            entry("flatpak", "fedora", false),
+            entry("flatpak", "flathub", false),
+        ]
+    }
+
+    #[test]
+    fn entry_order_by_priority() {
+        let id = AppId::new("org.example.App");
+        assert_eq!(
+            sorted(&Config::default(), &id, entries()),
+            [
+                ("flatpak", "flathub".to_string()),
+                ("flatpak", "fedora".to_string()),
+                ("packagekit", "fedora".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn entry_order_by_backend_order() {
+        let id = AppId::new("org.example.App");
+        let config = Config {
+            backend_order: vec!["packagekit".to_string()],
+            ..Default::default()
+        };
+        // Configured order overrides priority, which still orders the other backends
+        assert_eq!(
+            sorted(&config, &id, entries()),
+            [
+                ("packagekit", "fedora".to_string()),
+                ("flatpak", "flathub".to_string()),
+                ("flatpak", "fedora".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn entry_order_installed_first() {
+        let id = AppId::new("org.example.App");
+        let config = Config {
+            backend_order: vec!["flatpak".to_string()],
+            ..Default::default()
+        };
+        let mut entries = entries();
+        entries.push(entry("snap", "stable", true));
+        assert_eq!(
+            sorted(&config, &id, entries)[0],
+            ("snap", "stable".to_string())
+        );
+    }
+}
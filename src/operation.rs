@@ -1,12 +1,14 @@
 use std::sync::Arc;
 
-use crate::{AppId, AppInfo};
+use crate::{backend::BackendError, AppId, AppInfo};
 
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
 pub enum OperationKind {
     Install,
     Uninstall,
     Update,
+    /// Revert to the previously installed version
+    Rollback,
 }
 
 #[derive(Clone, Debug, Eq, Hash, PartialEq)]
@@ -18,12 +20,13 @@ pub struct Operation {
 }
 
 impl Operation {
-    pub fn failed_dialog(&self, err: &str) -> (String, String) {
+    pub fn failed_dialog(&self, err: &BackendError) -> (String, String) {
         //TODO: translate
         let verb = match self.kind {
             OperationKind::Install => "install",
             OperationKind::Uninstall => "uninstall",
             OperationKind::Update => "update",
+            OperationKind::Rollback => "roll back",
         };
         (
             format!(
@@ -31,7 +34,9 @@ impl Operation {
                 self.info.name, self.info.source_name
             ),
             format!(
-                "Failed to {verb} {} ({}) from {} ({}):\n{err}",
+                "{}\n\n{}\n\nFailed to {verb} {} ({}) from {} ({}):\n{err}",
+                err.explanation(),
+                err.remedy(),
                 self.info.name,
                 self.package_id.raw(),
                 self.info.source_name,
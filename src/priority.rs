@@ -26,6 +26,9 @@ pub fn priority(backend_name: &str, source_id: &str, id: &AppId) -> i32 {
                 if source_id == "flathub" {
                     priority += 1;
                 }
+            } else if backend_name == "snap" && source_id == "stable" {
+                // Among snap sources, the stable channel is preferred
+                priority += 1;
             }
         }
     }
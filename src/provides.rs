@@ -0,0 +1,117 @@
+use appstream::xmltree;
+
+use crate::app_info::child_elements;
+
+/// Item provided by a component, from the AppStream provides element
+#[derive(Clone, Debug, Hash, Eq, PartialEq, bitcode::Decode, bitcode::Encode)]
+pub enum AppProvide {
+    /// Media type of files the component can open, like `image/png`
+    MediaType(String),
+    /// Name of an executable in the default PATH
+    Binary(String),
+    /// Name of a shared library
+    Library(String),
+    /// Well-known D-Bus service name, on either the user or system bus
+    DBus(String),
+    /// Firmware file name for runtime firmware, or GUID for flashed firmware
+    Firmware(String),
+    /// Modalias glob of hardware supported by the component
+    Modalias(String),
+    /// Previous or alternative component ID
+    Id(String),
+}
+
+impl AppProvide {
+    fn new(kind: &str, value: &str) -> Option<Self> {
+        let value = value.trim();
+        if value.is_empty() {
+            return None;
+        }
+        let value = value.to_string();
+        match kind {
+            "mediatype" | "mimetype" => Some(Self::MediaType(value)),
+            "binary" => Some(Self::Binary(value)),
+            "library" => Some(Self::Library(value)),
+            "dbus" => Some(Self::DBus(value)),
+            "firmware" => Some(Self::Firmware(value)),
+            "modalias" => Some(Self::Modalias(value)),
+            "id" => Some(Self::Id(value)),
+            //TODO: fonts and python modules
+            _ => None,
+        }
+    }
+
+    /// Parse provides of a component element, including media types from the legacy
+    /// mimetypes element
+    pub fn from_element(component_element: &xmltree::Element) -> Vec<Self> {
+        let mut provides = Vec::new();
+        let mut push = |element: &xmltree::Element| {
+            let Some(text) = element.get_text() else {
+                return;
+            };
+            match Self::new(&element.name, &text) {
+                Some(provide) => {
+                    if !provides.contains(&provide) {
+                        provides.push(provide);
+                    }
+                }
+                None => log::debug!("unsupported provides element {:?}", element.name),
+            }
+        };
+        for provides_element in child_elements(component_element, "provides") {
+            for child in provides_element.children.iter() {
+                if let xmltree::XMLNode::Element(element) = child {
+                    push(element);
+                }
+            }
+        }
+        for mimetypes_element in child_elements(component_element, "mimetypes") {
+            for element in child_elements(mimetypes_element, "mimetype") {
+                push(element);
+            }
+        }
+        provides
+    }
+
+    /// Parse the Provides field of YAML catalogs, which maps kinds to lists of items
+    pub fn from_yaml(value: &serde_yaml::Value) -> Vec<Self> {
+        let mut provides = Vec::new();
+        let Some(mapping) = value.as_mapping() else {
+            return provides;
+        };
+        for (kind, items) in mapping.iter() {
+            let (Some(kind), Some(items)) = (kind.as_str(), items.as_sequence()) else {
+                log::debug!("invalid provides {:?}: {:?}", kind, items);
+                continue;
+            };
+            let kind = match kind {
+                "mediatypes" | "mimetypes" => "mediatype",
+                "binaries" => "binary",
+                "libraries" => "library",
+                "dbus" => "dbus",
+                "firmware" => "firmware",
+                "modaliases" => "modalias",
+                "ids" => "id",
+                _ => {
+                    log::debug!("unsupported provides {:?}", kind);
+                    continue;
+                }
+            };
+            for item in items.iter() {
+                // D-Bus services and firmware are mappings with a type
+                let text = match item {
+                    serde_yaml::Value::Mapping(_) => ["service", "file", "guid"]
+                        .iter()
+                        .find_map(|key| item[*key].as_str()),
+                    _ => item.as_str(),
+                };
+                if let Some(provide) = text.and_then(|text| Self::new(kind, text)) {
+                    if !provides.contains(&provide) {
+                        provides.push(provide);
+                    }
+                }
+            }
+        }
+        provides
+    }
+}
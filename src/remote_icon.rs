@@ -0,0 +1,197 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+    sync::{Mutex, OnceLock},
+    time::{Duration, Instant, SystemTime},
+};
+
+/// Icons older than this are fetched again, but still used until the fetch succeeds
+const MAX_AGE: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+/// Oldest icons are removed when the cache grows past this size in bytes
+const MAX_SIZE: u64 = 64 * 1024 * 1024;
+/// Time to wait before fetching an icon again after the first failure, doubled after each
+/// consecutive failure up to the maximum
+const RETRY_MIN: Duration = Duration::from_secs(60);
+const RETRY_MAX: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Consecutive failures and the earliest time to try again, by URL
+static FAILURES: OnceLock<Mutex<HashMap<String, (u32, Instant)>>> = OnceLock::new();
+static CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+
+fn failures() -> &'static Mutex<HashMap<String, (u32, Instant)>> {
+    FAILURES.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Client shared by all icon downloads, so connections are reused
+fn http_client() -> reqwest::Client {
+    CLIENT
+        .get_or_init(|| {
+            reqwest::Client::builder()
+                .user_agent("cosmic-store")
+                .connect_timeout(Duration::from_secs(10))
+                .timeout(Duration::from_secs(30))
+                .build()
+                .unwrap_or_else(|err| {
+                    log::warn!("failed to create remote icon client: {}", err);
+                    reqwest::Client::new()
+                })
+        })
+        .clone()
+}
+
+/// Cached icon paths by URL, so the file system is only checked once per URL
+static CACHED: OnceLock<Mutex<HashMap<String, Option<PathBuf>>>> = OnceLock::new();
+
+fn cached_paths() -> &'static Mutex<HashMap<String, Option<PathBuf>>> {
+    CACHED.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Directory where remote icons are stored
+fn cache_dir() -> Option<PathBuf> {
+    dirs::cache_dir().map(|x| x.join("cosmic-store").join("remote_icons"))
+}
+
+/// Path of the cached icon for a URL, whether or not it exists
+fn cache_path(url: &str) -> Option<PathBuf> {
+    let file_name = format!("{:x}", Sha256::digest(url.as_bytes()));
+    cache_dir().map(|x| x.join(file_name))
+}
+
+fn modified_age(path: &Path) -> Option<Duration> {
+    let modified = fs::metadata(path).ok()?.modified().ok()?;
+    SystemTime::now().duration_since(modified).ok()
+}
+
+/// Get the cached icon for a URL, even if it has expired
+pub fn cached(url: &str) -> Option<PathBuf> {
+    if let Some(path_opt) = cached_paths().lock().unwrap().get(url) {
+        return path_opt.clone();
+    }
+    let path_opt = cache_path(url).filter(|path| path.is_file());
+    cached_paths()
+        .lock()
+        .unwrap()
+        .insert(url.to_string(), path_opt.clone());
+    path_opt
+}
+
+/// Check if fetching the icon for a URL failed recently, without accessing the file system
+pub fn backing_off(url: &str) -> bool {
+    match failures().lock().unwrap().get(url) {
+        Some((_count, retry_at)) => Instant::now() < *retry_at,
+        None => false,
+    }
+}
+
+fn record_failure(url: &str) {
+    let mut failures = failures().lock().unwrap();
+    let count = failures.get(url).map_or(0, |(count, _)| *count);
+    let delay = RETRY_MIN
+        .checked_mul(1 << count.min(16))
+        .map_or(RETRY_MAX, |x| x.min(RETRY_MAX));
+    failures.insert(url.to_string(), (count + 1, Instant::now() + delay));
+}
+
+/// Check if the icon for a URL is missing or expired
+fn needs_fetch(url: &str) -> bool {
+    match cache_path(url) {
+        Some(path) => match modified_age(&path) {
+            Some(age) => age > MAX_AGE,
+            None => true,
+        },
+        None => false,
+    }
+}
+
+/// Download the icon for a URL into the cache if it is missing or expired, returning true
+/// if it was downloaded
+pub async fn fetch(url: String) -> Result<bool, Box<dyn Error + Send + Sync>> {
+    let check_url = url.clone();
+    if !tokio::task::spawn_blocking(move || needs_fetch(&check_url)).await? {
+        return Ok(false);
+    }
+    match download(url.clone()).await {
+        Ok(()) => {
+            failures().lock().unwrap().remove(&url);
+            Ok(true)
+        }
+        Err(err) => {
+            record_failure(&url);
+            Err(err)
+        }
+    }
+}
+
+async fn download(url: String) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let response = http_client().get(&url).send().await?.error_for_status()?;
+    let bytes = response.bytes().await?;
+    tokio::task::spawn_blocking(move || store(&url, &bytes)).await?
+}
+
+fn store(url: &str, bytes: &[u8]) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let path = cache_path(url).ok_or("failed to find cache directory")?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    // Write to a temporary file so partial downloads are never used
+    let part_path = path.with_extension("part");
+    fs::write(&part_path, bytes)?;
+    fs::rename(&part_path, &path)?;
+    prune();
+    cached_paths()
+        .lock()
+        .unwrap()
+        .insert(url.to_string(), Some(path));
+    Ok(())
+}
+
+/// Remove the oldest icons until the cache is within its size limit
+fn prune() {
+    let Some(dir) = cache_dir() else {
+        return;
+    };
+    let entries = match fs::read_dir(&dir) {
+        Ok(ok) => ok,
+        Err(err) => {
+            log::warn!("failed to read remote icon cache {:?}: {}", dir, err);
+            return;
+        }
+    };
+    let mut files = Vec::new();
+    let mut total_size = 0;
+    for entry in entries.filter_map(|x| x.ok()) {
+        let Ok(metadata) = entry.metadata() else {
+            continue;
+        };
+        if !metadata.is_file() {
+            continue;
+        }
+        let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+        total_size += metadata.len();
+        files.push((modified, metadata.len(), entry.path()));
+    }
+    if total_size <= MAX_SIZE {
+        return;
+    }
+    files.sort();
+    for (_modified, size, path) in files {
+        if total_size <= MAX_SIZE {
+            break;
+        }
+        match fs::remove_file(&path) {
+            Ok(()) => {
+                total_size -= size;
+                cached_paths()
+                    .lock()
+                    .unwrap()
+                    .retain(|_url, path_opt| path_opt.as_ref() != Some(&path));
+            }
+            Err(err) => log::warn!("failed to remove {:?}: {}", path, err),
+        }
+    }
+}
@@ -0,0 +1,425 @@
+use appstream::xmltree;
+use cosmic::{
+    font,
+    iced::{font::Style, Alignment},
+    widget, Element,
+};
+use std::{error::Error, fmt};
+
+// Deeper markup is ignored instead of failing the whole description
+const MAX_RECURSION: usize = 16;
+
+#[derive(Clone, Copy, Debug, Default, Hash, Eq, PartialEq, bitcode::Decode, bitcode::Encode)]
+pub struct SpanStyle {
+    pub emphasis: bool,
+    pub strong: bool,
+    pub code: bool,
+}
+
+impl SpanStyle {
+    fn font(&self) -> cosmic::iced::Font {
+        let mut font = if self.code {
+            font::mono()
+        } else if self.strong {
+            font::bold()
+        } else {
+            font::default()
+        };
+        if self.emphasis {
+            font.style = Style::Italic;
+        }
+        font
+    }
+}
+
+#[derive(Clone, Debug, Hash, Eq, PartialEq, bitcode::Decode, bitcode::Encode)]
+pub struct Span {
+    pub text: String,
+    pub style: SpanStyle,
+}
+
+#[derive(Clone, Debug, Hash, Eq, PartialEq, bitcode::Decode, bitcode::Encode)]
+pub enum Block {
+    Paragraph(Vec<Span>),
+    OrderedList(Vec<Vec<Span>>),
+    UnorderedList(Vec<Vec<Span>>),
+    Preformatted(String),
+}
+
+/// Formatted text, like appstream descriptions
+#[derive(Clone, Debug, Default, Hash, Eq, PartialEq, bitcode::Decode, bitcode::Encode)]
+pub struct RichText {
+    pub blocks: Vec<Block>,
+}
+
+/// Append text to spans, collapsing whitespace and joining spans of the same style
+fn push_text(spans: &mut Vec<Span>, text: &str, style: SpanStyle) {
+    let mut collapsed = String::with_capacity(text.len());
+    let ends_with_space = spans.last().map_or(true, |x| x.text.ends_with(' '));
+    let mut last_space = ends_with_space;
+    for c in text.chars() {
+        if c.is_whitespace() {
+            if !last_space {
+                collapsed.push(' ');
+            }
+            last_space = true;
+        } else {
+            collapsed.push(c);
+            last_space = false;
+        }
+    }
+    if collapsed.is_empty() {
+        return;
+    }
+    match spans.last_mut() {
+        Some(last) if last.style == style => last.text.push_str(&collapsed),
+        _ => spans.push(Span {
+            text: collapsed,
+            style,
+        }),
+    }
+}
+
+/// Remove whitespace at the start and end of a paragraph
+fn trim_spans(mut spans: Vec<Span>) -> Vec<Span> {
+    if let Some(last) = spans.last_mut() {
+        let len = last.text.trim_end().len();
+        last.text.truncate(len);
+    }
+    spans.retain(|x| !x.text.is_empty());
+    spans
+}
+
+fn is_block(element: &xmltree::Element) -> bool {
+    match element.name.as_str() {
+        "p" | "ol" | "ul" | "pre" => true,
+        // Unknown elements containing blocks are treated as containers
+        "b" | "em" | "i" | "strong" | "code" | "li" => false,
+        _ => element.children.iter().any(|child| match child {
+            xmltree::XMLNode::Element(child) => is_block(child),
+            _ => false,
+        }),
+    }
+}
+
+fn parse_inline(
+    spans: &mut Vec<Span>,
+    node: &xmltree::XMLNode,
+    style: SpanStyle,
+    recursion: usize,
+) {
+    if recursion >= MAX_RECURSION {
+        log::debug!("maximum recursion level reached, ignoring {:?}", node);
+        return;
+    }
+    match node {
+        xmltree::XMLNode::Element(element) => {
+            let mut style = style;
+            match element.name.as_str() {
+                "em" | "i" => style.emphasis = true,
+                "b" | "strong" => style.strong = true,
+                "code" => style.code = true,
+                "br" => push_text(spans, " ", style),
+                "li" => {}
+                _ => log::debug!("unknown inline element {:?}", element.name),
+            }
+            for child in element.children.iter() {
+                parse_inline(spans, child, style, recursion + 1);
+            }
+        }
+        xmltree::XMLNode::Text(text) | xmltree::XMLNode::CData(text) => {
+            push_text(spans, text, style)
+        }
+        _ => {}
+    }
+}
+
+fn text_content(s: &mut String, node: &xmltree::XMLNode, recursion: usize) {
+    if recursion >= MAX_RECURSION {
+        return;
+    }
+    match node {
+        xmltree::XMLNode::Element(element) => {
+            for child in element.children.iter() {
+                text_content(s, child, recursion + 1);
+            }
+        }
+        xmltree::XMLNode::Text(text) | xmltree::XMLNode::CData(text) => s.push_str(text),
+        _ => {}
+    }
+}
+
+fn parse_list_items(element: &xmltree::Element, recursion: usize) -> Vec<Vec<Span>> {
+    let mut items = Vec::new();
+    for child in element.children.iter() {
+        // Anything other than list items is shown as its own item
+        if let xmltree::XMLNode::Element(child_element) = child {
+            if child_element.name != "li" {
+                log::debug!("unknown list element {:?}", child_element.name);
+            }
+        }
+        let mut spans = Vec::new();
+        parse_inline(&mut spans, child, SpanStyle::default(), recursion + 1);
+        let spans = trim_spans(spans);
+        if !spans.is_empty() {
+            items.push(spans);
+        }
+    }
+    items
+}
+
+fn parse_blocks(blocks: &mut Vec<Block>, nodes: &[xmltree::XMLNode], recursion: usize) {
+    if recursion >= MAX_RECURSION {
+        log::debug!(
+            "maximum recursion level reached, ignoring {} nodes",
+            nodes.len()
+        );
+        return;
+    }
+
+    // Text and inline elements outside of blocks are gathered into paragraphs
+    let mut spans = Vec::new();
+    for node in nodes.iter() {
+        let element = match node {
+            xmltree::XMLNode::Element(element) if is_block(element) => element,
+            _ => {
+                parse_inline(&mut spans, node, SpanStyle::default(), recursion + 1);
+                continue;
+            }
+        };
+
+        let paragraph = trim_spans(std::mem::take(&mut spans));
+        if !paragraph.is_empty() {
+            blocks.push(Block::Paragraph(paragraph));
+        }
+
+        match element.name.as_str() {
+            "p" => {
+                for child in element.children.iter() {
+                    parse_inline(&mut spans, child, SpanStyle::default(), recursion + 1);
+                }
+                let paragraph = trim_spans(std::mem::take(&mut spans));
+                if !paragraph.is_empty() {
+                    blocks.push(Block::Paragraph(paragraph));
+                }
+            }
+            "ol" => {
+                let items = parse_list_items(element, recursion);
+                if !items.is_empty() {
+                    blocks.push(Block::OrderedList(items));
+                }
+            }
+            "ul" => {
+                let items = parse_list_items(element, recursion);
+                if !items.is_empty() {
+                    blocks.push(Block::UnorderedList(items));
+                }
+            }
+            "pre" => {
+                let mut text = String::new();
+                text_content(&mut text, node, recursion + 1);
+                let text = text.trim_matches('\n');
+                if !text.trim().is_empty() {
+                    blocks.push(Block::Preformatted(text.to_string()));
+                }
+            }
+            _ => {
+                log::debug!("unknown block element {:?}", element.name);
+                parse_blocks(blocks, &element.children, recursion + 1);
+            }
+        }
+    }
+
+    let paragraph = trim_spans(spans);
+    if !paragraph.is_empty() {
+        blocks.push(Block::Paragraph(paragraph));
+    }
+}
+
+fn plain_spans(text: &str) -> Vec<Span> {
+    let mut spans = Vec::new();
+    push_text(&mut spans, text, SpanStyle::default());
+    trim_spans(spans)
+}
+
+fn write_spans(f: &mut fmt::Formatter, spans: &[Span]) -> fmt::Result {
+    for span in spans.iter() {
+        write!(f, "{}", span.text)?;
+    }
+    Ok(())
+}
+
+fn spans_view<'a, Message: 'static>(spans: &'a [Span]) -> Element<'a, Message> {
+    if spans.iter().all(|x| x.style == SpanStyle::default()) {
+        let text: String = spans.iter().map(|x| x.text.as_str()).collect();
+        return widget::text::body(text).into();
+    }
+
+    // Styled text is split into words so it can wrap. Pieces of a word with different
+    // styles, like emphasis followed by punctuation, are kept together.
+    let mut words: Vec<Vec<(&'a str, SpanStyle)>> = Vec::new();
+    let mut joined = false;
+    for span in spans.iter() {
+        let mut first = true;
+        for piece in span.text.split(' ') {
+            if !first {
+                joined = false;
+            }
+            first = false;
+            if piece.is_empty() {
+                continue;
+            }
+            match words.last_mut() {
+                Some(word) if joined => word.push((piece, span.style)),
+                _ => words.push(vec![(piece, span.style)]),
+            }
+            joined = true;
+        }
+        if span.text.ends_with(' ') {
+            joined = false;
+        }
+    }
+
+    let mut children = Vec::with_capacity(words.len());
+    for word in words {
+        let mut row = widget::row::with_capacity(word.len());
+        for (piece, style) in word {
+            row = row.push(widget::text::body(piece).font(style.font()));
+        }
+        children.push(row.into());
+    }
+    widget::flex_row(children)
+        .column_spacing(4)
+        .row_spacing(4)
+        .into()
+}
+
+impl RichText {
+    /// Parse appstream description markup. Unknown elements are shown as plain text.
+    pub fn parse(markup: &str) -> Result<Self, Box<dyn Error>> {
+        let nodes = xmltree::Element::parse_all(markup.as_bytes())?;
+        Ok(Self::from_nodes(&nodes))
+    }
+
+    /// Convert already parsed appstream description markup
+    pub fn from_nodes(nodes: &[xmltree::XMLNode]) -> Self {
+        let mut blocks = Vec::new();
+        parse_blocks(&mut blocks, nodes, 0);
+        Self { blocks }
+    }
+
+    /// Convert plain text, where empty lines separate paragraphs and lines starting with
+    /// `*` are list items
+    pub fn from_plain(text: &str) -> Self {
+        let mut blocks = Vec::new();
+        let mut paragraph = String::new();
+        let mut items = Vec::new();
+        for line in text.lines().map(|x| x.trim()) {
+            let item_opt = line.strip_prefix("* ");
+            if line.is_empty() || item_opt.is_some() {
+                let spans = plain_spans(&paragraph);
+                if !spans.is_empty() {
+                    blocks.push(Block::Paragraph(spans));
+                }
+                paragraph.clear();
+            }
+            match item_opt {
+                Some(item) => {
+                    let spans = plain_spans(item);
+                    if !spans.is_empty() {
+                        items.push(spans);
+                    }
+                    continue;
+                }
+                None => {
+                    if !items.is_empty() {
+                        blocks.push(Block::UnorderedList(std::mem::take(&mut items)));
+                    }
+                }
+            }
+            paragraph.push(' ');
+            paragraph.push_str(line);
+        }
+        let spans = plain_spans(&paragraph);
+        if !spans.is_empty() {
+            blocks.push(Block::Paragraph(spans));
+        }
+        if !items.is_empty() {
+            blocks.push(Block::UnorderedList(items));
+        }
+        Self { blocks }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.blocks.is_empty()
+    }
+
+    pub fn view<Message: 'static>(&self, spacing: u16) -> Element<Message> {
+        let mut column = widget::column::with_capacity(self.blocks.len()).spacing(spacing);
+        for block in self.blocks.iter() {
+            match block {
+                Block::Paragraph(spans) => {
+                    column = column.push(spans_view(spans));
+                }
+                Block::OrderedList(items) | Block::UnorderedList(items) => {
+                    let ordered = matches!(block, Block::OrderedList(_));
+                    let mut list = widget::column::with_capacity(items.len()).spacing(spacing / 2);
+                    for (i, item) in items.iter().enumerate() {
+                        let marker = if ordered {
+                            format!("{}.", i + 1)
+                        } else {
+                            "•".to_string()
+                        };
+                        list = list.push(
+                            widget::row::with_children(vec![
+                                widget::text::body(marker).into(),
+                                spans_view(item),
+                            ])
+                            .align_items(Alignment::Start)
+                            .spacing(spacing),
+                        );
+                    }
+                    column = column.push(list);
+                }
+                Block::Preformatted(text) => {
+                    column = column.push(widget::text::body(text).font(font::mono()));
+                }
+            }
+        }
+        column.into()
+    }
+}
+
+/// Plain text, used for searching and where formatting is not supported
+impl fmt::Display for RichText {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (block_i, block) in self.blocks.iter().enumerate() {
+            if block_i > 0 {
+                writeln!(f)?;
+            }
+            match block {
+                Block::Paragraph(spans) => write_spans(f, spans)?,
+                Block::OrderedList(items) => {
+                    for (i, item) in items.iter().enumerate() {
+                        if i > 0 {
+                            writeln!(f)?;
+                        }
+                        write!(f, "{:2}. ", i + 1)?;
+                        write_spans(f, item)?;
+                    }
+                }
+                Block::UnorderedList(items) => {
+                    for (i, item) in items.iter().enumerate() {
+                        if i > 0 {
+                            writeln!(f)?;
+                        }
+                        write!(f, " * ")?;
+                        write_spans(f, item)?;
+                    }
+                }
+                Block::Preformatted(text) => write!(f, "{}", text)?,
+            }
+        }
+        Ok(())
+    }
+}